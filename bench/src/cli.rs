@@ -1,5 +1,7 @@
 use clap::{command, Parser};
 
+use crate::{output::OutputFormat, slot_alignment::SlotAlignment, workload::Workload};
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
@@ -12,12 +14,90 @@ pub struct Args {
     /// Interval between each bench run (ms)
     #[arg(short = 'i', long, default_value_t = 1000)]
     pub run_interval_ms: u64,
-    /// Metrics output file name
+    /// Metrics output file name, its extension follows the output format
     #[arg(short = 'm', long, default_value_t = String::from("metrics.csv"))]
     pub metrics_file_name: String,
-    /// Lite Rpc Address
-    #[arg(short = 'l', long, default_value_t = String::from("http://127.0.0.1:8890"))]
-    pub lite_rpc_addr: String,
+    /// Comma separated Lite Rpc Address(es), each one receives the tx(s) of every run
+    #[arg(
+        short = 'l',
+        long,
+        value_delimiter = ',',
+        default_value = "http://127.0.0.1:8890"
+    )]
+    pub lite_rpc_addr: Vec<String>,
+    /// Reference rpc address, every batch sent to lite-rpc is paired with one sent to it at the same time
+    #[arg(long)]
+    pub compare_rpc_addr: Option<String>,
+    /// Output file for the per target summary when more than one rpc is benched
+    #[arg(long, default_value_t = String::from("targets.csv"))]
+    pub targets_file_name: String,
+    /// Rpc address used to fetch the slot leaders for the per leader landing report, no report when unset
+    #[arg(long)]
+    pub leaders_rpc_addr: Option<String>,
+    /// Output file for the per leader landing report
+    #[arg(long, default_value_t = String::from("leaders.csv"))]
+    pub leaders_file_name: String,
+    /// Run as a synthetic monitor, sending `probe_tps` tx(s) per second indefinitely and exposing the metrics of
+    /// each probe window on `prometheus_addr`, the run, comparison and report options are ignored
+    #[arg(long)]
+    pub daemon: bool,
+    /// Probe rate in daemon mode (tx(s) per second)
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub probe_tps: u64,
+    /// Length of a probe window in daemon mode (s), metrics are updated once its tx(s) are confirmed or timed out
+    #[arg(long, default_value_t = 60)]
+    pub probe_window_secs: u64,
+    /// Address serving the prometheus metrics in daemon mode
+    #[arg(long, default_value_t = String::from("[::]:9092"))]
+    pub prometheus_addr: String,
+    /// Per transaction records output file name, its extension follows the output format, empty to disable
     #[arg(short = 't', long, default_value_t = String::from("transactions.csv"))]
     pub transaction_save_file: String,
+    /// Directory of payer keypair json files to rotate across, defaults to the solana cli keypair
+    #[arg(long)]
+    pub payers_dir: Option<String>,
+    /// Shape of the transactions sent
+    #[arg(short = 'w', long, value_enum, default_value_t = Workload::Memo)]
+    pub workload: Workload,
+    /// Format of the metrics, transactions and priority fees output files
+    #[arg(short = 'o', long, value_enum, default_value_t = OutputFormat::Csv)]
+    pub output: OutputFormat,
+    /// Sustain this rate of tx(s) per second for `duration_secs` instead of sending `tx_count` tx(s) at once
+    #[arg(long, conflicts_with = "slot_alignment", value_parser = clap::value_parser!(u64).range(1..))]
+    pub target_tps: Option<u64>,
+    /// Duration of each bench run (s), only used along with `target_tps`
+    #[arg(long, default_value_t = 60)]
+    pub duration_secs: u64,
+    /// Warm-up at the start of each run (s), tx(s) sent during it are left out of the statistics
+    #[arg(long, default_value_t = 0)]
+    pub warmup_secs: u64,
+    /// Time the txs relative to slot boundaries, sending `txs_per_slot` tx(s) per slot
+    #[arg(long, value_enum)]
+    pub slot_alignment: Option<SlotAlignment>,
+    /// Number of tx(s) sent per slot when aligned to slots
+    #[arg(long, default_value_t = 10)]
+    pub txs_per_slot: usize,
+    /// Delay after the start of a slot before sending (ms) when aligned to slots
+    #[arg(long, default_value_t = 0)]
+    pub slot_offset_ms: u64,
+    /// Websocket address of the rpc used to subscribe to slots when aligned to slots
+    #[arg(long, default_value_t = String::from("ws://127.0.0.1:8900"))]
+    pub ws_addr: String,
+    /// Comma separated compute unit prices (micro-lamports) to sweep, each run sends one batch per price
+    #[arg(long, value_delimiter = ',')]
+    pub priority_fees: Vec<u64>,
+    /// Output file for the per compute unit price report of a priority fee sweep
+    #[arg(long, default_value_t = String::from("priority_fees.csv"))]
+    pub priority_fees_file_name: String,
+}
+
+impl Args {
+    /// number of tx(s) sent in each run, derived from target tps and duration if set,
+    /// in which case the warm-up is sent on top of the measured duration
+    pub fn txs_per_run(&self) -> usize {
+        match self.target_tps {
+            Some(target_tps) => (target_tps * (self.warmup_secs + self.duration_secs)) as usize,
+            None => self.tx_count,
+        }
+    }
 }
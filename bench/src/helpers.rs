@@ -4,6 +4,7 @@ use rand::{distributions::Alphanumeric, prelude::Distribution, SeedableRng};
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    compute_budget::ComputeBudgetInstruction,
     hash::Hash,
     instruction::Instruction,
     message::Message,
@@ -13,7 +14,7 @@ use solana_sdk::{
     system_instruction,
     transaction::Transaction,
 };
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{str::FromStr, time::Duration};
 use tokio::time::Instant;
 
@@ -34,15 +35,42 @@ pub struct BenchHelper;
 
 impl BenchHelper {
     pub async fn get_payer() -> anyhow::Result<Keypair> {
-        let payer = tokio::fs::read_to_string(USER_KEYPAIR.as_path())
+        Self::read_keypair(USER_KEYPAIR.as_path()).await
+    }
+
+    pub async fn read_keypair(path: &Path) -> anyhow::Result<Keypair> {
+        let payer = tokio::fs::read_to_string(path)
             .await
-            .context("Error reading payer file")?;
+            .with_context(|| format!("Error reading payer file {path:?}"))?;
         let payer: Vec<u8> = serde_json::from_str(&payer)?;
         let payer = Keypair::from_bytes(&payer)?;
 
         Ok(payer)
     }
 
+    /// reads every `.json` keypair file of `dir`, sorted by file name
+    pub async fn get_payers_from_dir(dir: &Path) -> anyhow::Result<Vec<Keypair>> {
+        let mut paths = vec![];
+        let mut entries = tokio::fs::read_dir(dir)
+            .await
+            .with_context(|| format!("Error reading payers directory {dir:?}"))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().map_or(false, |ext| ext == "json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut payers = Vec::with_capacity(paths.len());
+        for path in paths {
+            payers.push(Self::read_keypair(&path).await?);
+        }
+        anyhow::ensure!(!payers.is_empty(), "No payer keypair found in {dir:?}");
+
+        Ok(payers)
+    }
+
     pub async fn wait_till_signature_status(
         rpc_client: &RpcClient,
         sig: &Signature,
@@ -102,10 +130,27 @@ impl BenchHelper {
     }
 
     pub fn create_memo_tx(msg: &[u8], payer: &Keypair, blockhash: Hash) -> Transaction {
+        Self::create_memo_tx_with_cu_price(msg, payer, blockhash, None)
+    }
+
+    pub fn create_memo_instruction(msg: &[u8]) -> Instruction {
         let memo = Pubkey::from_str(MEMO_PROGRAM_ID).unwrap();
+        Instruction::new_with_bytes(memo, msg, vec![])
+    }
 
-        let instruction = Instruction::new_with_bytes(memo, msg, vec![]);
-        let message = Message::new(&[instruction], Some(&payer.pubkey()));
+    /// memo tx paying a priority fee of `cu_price` micro-lamports per compute unit
+    pub fn create_memo_tx_with_cu_price(
+        msg: &[u8],
+        payer: &Keypair,
+        blockhash: Hash,
+        cu_price: Option<u64>,
+    ) -> Transaction {
+        let mut instructions = vec![];
+        if let Some(cu_price) = cu_price {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(cu_price));
+        }
+        instructions.push(Self::create_memo_instruction(msg));
+        let message = Message::new(&instructions, Some(&payer.pubkey()));
         Transaction::new(&[payer], message, blockhash)
     }
 }
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, slot_history::Slot};
+
+/// max number of leaders returned by a single `getSlotLeaders` request
+const MAX_SLOT_LEADERS_PER_REQUEST: u64 = 5000;
+
+/// slot a tx was sent in and the one it landed in, if it did
+#[derive(Clone, Copy, Debug)]
+pub struct TxLanding {
+    pub sent_slot: Slot,
+    pub confirmed_slot: Option<Slot>,
+}
+
+/// txs sent while a validator was leader and how many of them landed, along with
+/// the number of benched txs included in the blocks it produced
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct LeaderMetric {
+    pub leader: String,
+    pub txs_sent: u64,
+    pub txs_confirmed: u64,
+    pub landing_rate: f64,
+    pub txs_included: u64,
+}
+
+/// attributes every tx to the leader of the slot it was sent in, sorted by landing rate, worst first
+pub async fn leader_report(
+    rpc_client: &RpcClient,
+    landings: &[TxLanding],
+) -> anyhow::Result<Vec<LeaderMetric>> {
+    let slots = landings
+        .iter()
+        .flat_map(|landing| std::iter::once(landing.sent_slot).chain(landing.confirmed_slot));
+    let (Some(first_slot), Some(last_slot)) = (slots.clone().min(), slots.max()) else {
+        return Ok(vec![]);
+    };
+
+    let mut slot_leaders: HashMap<Slot, Pubkey> = HashMap::new();
+    let mut start_slot = first_slot;
+    while start_slot <= last_slot {
+        let limit = (last_slot - start_slot + 1).min(MAX_SLOT_LEADERS_PER_REQUEST);
+        let leaders = rpc_client.get_slot_leaders(start_slot, limit).await?;
+        slot_leaders.extend((start_slot..).zip(leaders));
+        start_slot += limit;
+    }
+
+    Ok(attribute_to_leaders(&slot_leaders, landings))
+}
+
+fn attribute_to_leaders(
+    slot_leaders: &HashMap<Slot, Pubkey>,
+    landings: &[TxLanding],
+) -> Vec<LeaderMetric> {
+    let mut leader_metrics: HashMap<Pubkey, LeaderMetric> = HashMap::new();
+    for landing in landings {
+        if let Some(leader) = slot_leaders.get(&landing.sent_slot) {
+            let leader_metric = leader_metrics.entry(*leader).or_default();
+            leader_metric.txs_sent += 1;
+            if landing.confirmed_slot.is_some() {
+                leader_metric.txs_confirmed += 1;
+            }
+        }
+        if let Some(leader) = landing
+            .confirmed_slot
+            .and_then(|confirmed_slot| slot_leaders.get(&confirmed_slot))
+        {
+            leader_metrics.entry(*leader).or_default().txs_included += 1;
+        }
+    }
+
+    let mut leader_metrics = leader_metrics
+        .into_iter()
+        .map(|(leader, mut leader_metric)| {
+            leader_metric.leader = leader.to_string();
+            if leader_metric.txs_sent > 0 {
+                leader_metric.landing_rate =
+                    leader_metric.txs_confirmed as f64 / leader_metric.txs_sent as f64;
+            }
+            leader_metric
+        })
+        .collect::<Vec<_>>();
+    leader_metrics.sort_by(|a, b| a.landing_rate.total_cmp(&b.landing_rate));
+    leader_metrics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn txs_are_attributed_to_sending_and_including_leaders() {
+        let (good, bad) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let slot_leaders = HashMap::from([(1, bad), (2, good), (3, good)]);
+        let landings = [
+            TxLanding {
+                sent_slot: 1,
+                confirmed_slot: None,
+            },
+            TxLanding {
+                sent_slot: 1,
+                confirmed_slot: Some(3),
+            },
+            TxLanding {
+                sent_slot: 2,
+                confirmed_slot: Some(2),
+            },
+            // slot without a known leader
+            TxLanding {
+                sent_slot: 9,
+                confirmed_slot: None,
+            },
+        ];
+
+        let leader_metrics = attribute_to_leaders(&slot_leaders, &landings);
+        assert_eq!(leader_metrics.len(), 2);
+        let worst = &leader_metrics[0];
+        assert_eq!(worst.leader, bad.to_string());
+        assert_eq!(
+            (worst.txs_sent, worst.txs_confirmed, worst.txs_included),
+            (2, 1, 0)
+        );
+        assert_eq!(worst.landing_rate, 0.5);
+        let best = &leader_metrics[1];
+        assert_eq!(best.leader, good.to_string());
+        assert_eq!(
+            (best.txs_sent, best.txs_confirmed, best.txs_included),
+            (1, 1, 2)
+        );
+        assert_eq!(best.landing_rate, 1.0);
+    }
+}
@@ -1,3 +1,8 @@
 pub mod cli;
 pub mod helpers;
+pub mod leader_report;
 pub mod metrics;
+pub mod output;
+pub mod probe_metrics;
+pub mod slot_alignment;
+pub mod workload;
@@ -1,20 +1,34 @@
 use bench::{
     cli::Args,
     helpers::BenchHelper,
-    metrics::{AvgMetric, Metric, TxMetricData},
+    leader_report::{leader_report, TxLanding},
+    metrics::{AvgMetric, Metric, PriorityFeeMetric, TargetMetric, TxMetricData},
+    output::{OutputFormat, RecordWriter},
+    probe_metrics::record_probe_window,
+    slot_alignment::{spawn_slot_subscription, SlotAligner},
+    workload::WorkloadGenerator,
 };
 use clap::Parser;
 use dashmap::DashMap;
 use futures::future::join_all;
 use log::{error, info, warn};
+use solana_lite_rpc_services::prometheus_sync::PrometheusSync;
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    commitment_config::CommitmentConfig, hash::Hash, signature::Keypair, signer::Signer,
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    signature::{Keypair, Signature},
+    signer::Signer,
     slot_history::Slot,
+    transaction::VersionedTransaction,
 };
-use std::sync::{
-    atomic::{AtomicU64, Ordering},
-    Arc,
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 use tokio::{
     sync::{mpsc::UnboundedSender, RwLock},
@@ -25,34 +39,93 @@ use tokio::{
 async fn main() {
     tracing_subscriber::fmt::init();
 
+    let args = Args::parse();
+    let tx_count = args.txs_per_run();
     let Args {
-        tx_count,
         runs,
         run_interval_ms,
         metrics_file_name,
         lite_rpc_addr,
         transaction_save_file,
-    } = Args::parse();
+        target_tps,
+        priority_fees,
+        priority_fees_file_name,
+        output,
+        payers_dir,
+        workload,
+        compare_rpc_addr,
+        targets_file_name,
+        slot_alignment,
+        txs_per_slot,
+        slot_offset_ms,
+        ws_addr,
+        warmup_secs,
+        leaders_rpc_addr,
+        leaders_file_name,
+        daemon,
+        probe_tps,
+        probe_window_secs,
+        prometheus_addr,
+        ..
+    } = args;
 
     let mut run_interval_ms = tokio::time::interval(Duration::from_millis(run_interval_ms));
 
-    info!("Connecting to {lite_rpc_addr}");
-
-    let mut avg_metric = AvgMetric::default();
+    info!("Connecting to {}", lite_rpc_addr.join(", "));
 
     let mut tasks = vec![];
+    let mut task_labels = vec![];
 
-    let funded_payer = BenchHelper::get_payer().await.unwrap();
-    println!("payer : {}", funded_payer.pubkey());
+    // txs are spread across payers so that they do not all lock the same fee payer account
+    let funded_payers = match &payers_dir {
+        Some(payers_dir) => BenchHelper::get_payers_from_dir(Path::new(payers_dir))
+            .await
+            .unwrap(),
+        None => vec![BenchHelper::get_payer().await.unwrap()],
+    };
+    for funded_payer in &funded_payers {
+        println!("payer : {}", funded_payer.pubkey());
+    }
+    let funded_payers = Arc::new(funded_payers);
 
-    let rpc_client = Arc::new(RpcClient::new_with_commitment(
-        lite_rpc_addr.clone(),
-        CommitmentConfig::confirmed(),
-    ));
+    // lite-rpc targets come first, followed by the reference rpc when comparing
+    let mut targets = lite_rpc_addr
+        .iter()
+        .map(|addr| {
+            let rpc_client = Arc::new(RpcClient::new_with_commitment(
+                addr.clone(),
+                CommitmentConfig::confirmed(),
+            ));
+            (addr.clone(), rpc_client)
+        })
+        .collect::<Vec<_>>();
+    let lite_rpc_count = targets.len();
+    // the first lite-rpc provides the blockhash and slot shared by all targets
+    let rpc_client = targets[0].1.clone();
     let bh = rpc_client.get_latest_blockhash().await.unwrap();
     let slot = rpc_client.get_slot().await.unwrap();
     let block_hash: Arc<RwLock<Hash>> = Arc::new(RwLock::new(bh));
     let current_slot = Arc::new(AtomicU64::new(slot));
+
+    // the reference rpc confirms the txs of every target when comparing so that all are measured
+    // the same way, otherwise each target confirms its own txs
+    let status_rpc_client = compare_rpc_addr.map(|compare_rpc_addr| {
+        info!("Comparing against {compare_rpc_addr}");
+        let reference_rpc_client = Arc::new(RpcClient::new_with_commitment(
+            compare_rpc_addr.clone(),
+            CommitmentConfig::confirmed(),
+        ));
+        targets.push((compare_rpc_addr, reference_rpc_client.clone()));
+        reference_rpc_client
+    });
+
+    let slot_rx = slot_alignment.map(|_| spawn_slot_subscription(ws_addr));
+
+    let workload = Arc::new(
+        WorkloadGenerator::new(workload, &rpc_client, &funded_payers[0])
+            .await
+            .unwrap(),
+    );
     {
         // block hash updater task
         let block_hash = block_hash.clone();
@@ -72,7 +145,7 @@ async fn main() {
                 let slot = rpc_client.get_slot().await;
                 match slot {
                     Ok(slot) => {
-                        current_slot.store(slot, std::sync::atomic::Ordering::Relaxed);
+                        current_slot.store(slot, Ordering::Relaxed);
                     }
                     Err(e) => println!("slot {}", e),
                 }
@@ -81,60 +154,328 @@ async fn main() {
         })
     };
 
+    if daemon {
+        run_daemon(
+            rpc_client,
+            funded_payers,
+            workload,
+            block_hash,
+            current_slot,
+            probe_tps,
+            Duration::from_secs(probe_window_secs),
+            prometheus_addr,
+        )
+        .await;
+        return;
+    }
+
     // transaction logger
     let (tx_log_sx, mut tx_log_rx) = tokio::sync::mpsc::unbounded_channel::<TxMetricData>();
     let log_transactions = !transaction_save_file.is_empty();
-    if log_transactions {
+    let tx_logger_jh = log_transactions.then(|| {
         tokio::spawn(async move {
-            let mut tx_writer = csv::Writer::from_path(transaction_save_file).unwrap();
+            let mut tx_writer = RecordWriter::create(&transaction_save_file, output).unwrap();
             while let Some(x) = tx_log_rx.recv().await {
-                tx_writer.serialize(x).unwrap();
+                tx_writer.write(&x).unwrap();
             }
-        });
-    }
+            tx_writer.flush().unwrap();
+        })
+    });
+
+    // without a fee sweep each run sends a single batch without priority fee,
+    // with a sweep each run sends one batch per compute unit price concurrently
+    let fee_levels: Vec<Option<u64>> = if priority_fees.is_empty() {
+        vec![None]
+    } else {
+        priority_fees.iter().copied().map(Some).collect()
+    };
 
-    for seed in 0..runs {
-        let funded_payer = Keypair::from_bytes(funded_payer.to_bytes().as_slice()).unwrap();
-        tasks.push(tokio::spawn(bench(
-            rpc_client.clone(),
-            tx_count,
-            funded_payer,
-            seed as u64,
-            block_hash.clone(),
-            current_slot.clone(),
-            tx_log_sx.clone(),
-            log_transactions,
-        )));
+    for run in 0..runs {
+        for cu_price in &fee_levels {
+            for (target, (_, target_rpc_client)) in targets.iter().enumerate() {
+                // each target gets its own random memos so that paired txs have distinct signatures
+                let seed = (target * runs + run) as u64;
+                let slot_aligner =
+                    slot_alignment
+                        .zip(slot_rx.as_ref())
+                        .map(|(alignment, slot_rx)| {
+                            SlotAligner::new(
+                                alignment,
+                                txs_per_slot,
+                                Duration::from_millis(slot_offset_ms),
+                                slot_rx.clone(),
+                            )
+                        });
+                tasks.push(tokio::spawn(bench(
+                    target_rpc_client.clone(),
+                    status_rpc_client
+                        .clone()
+                        .unwrap_or_else(|| target_rpc_client.clone()),
+                    tx_count,
+                    funded_payers.clone(),
+                    workload.clone(),
+                    seed,
+                    block_hash.clone(),
+                    current_slot.clone(),
+                    tx_log_sx.clone(),
+                    log_transactions,
+                    target_tps,
+                    slot_aligner,
+                    *cu_price,
+                    Duration::from_secs(warmup_secs),
+                )));
+                task_labels.push((run + 1, *cu_price, target));
+            }
+        }
         // wait for an interval
         run_interval_ms.tick().await;
     }
 
     let join_res = join_all(tasks).await;
 
-    let mut run_num = 1;
+    let mut avg_metric = AvgMetric::default();
+    let mut target_metrics: Vec<AvgMetric> = targets.iter().map(|_| AvgMetric::default()).collect();
+    let mut fee_level_metrics: BTreeMap<u64, AvgMetric> = BTreeMap::new();
+    let mut landings: Vec<TxLanding> = vec![];
 
-    let mut csv_writer = csv::Writer::from_path(metrics_file_name).unwrap();
-    for res in join_res {
+    // the metrics file holds the runs against lite-rpc, the reference rpc is summarized separately
+    let mut metrics_writer = RecordWriter::create(&metrics_file_name, output).unwrap();
+    for ((run_num, cu_price, target), res) in task_labels.into_iter().zip(join_res) {
+        let (target_addr, _) = &targets[target];
         match res {
-            Ok(metric) => {
+            Ok((metric, run_landings)) => {
+                target_metrics[target] += &metric;
+                if target >= lite_rpc_count {
+                    info!("Run {run_num}: {target_addr}: {}", metric.latency_summary());
+                    continue;
+                }
+
+                if let Some(cu_price) = cu_price {
+                    info!("Run {run_num}: cu price {cu_price} micro-lamports");
+                    *fee_level_metrics.entry(cu_price).or_default() += &metric;
+                }
                 info!("Run {run_num}: Sent and Confirmed {tx_count} tx(s) in {metric:?} with",);
-                // update avg metric
+                info!("Run {run_num}: {target_addr}: {}", metric.latency_summary());
+                if let Some(target_tps) = target_tps {
+                    info!(
+                        "Run {run_num}: requested {target_tps} tps, achieved {:.2} tps",
+                        metric.achieved_tps
+                    );
+                }
                 avg_metric += &metric;
-                csv_writer.serialize(metric).unwrap();
+                landings.extend(run_landings);
+                metrics_writer.write(&metric).unwrap();
             }
             Err(_) => {
-                error!("join error for run {}", run_num);
+                error!("join error for run {} against {}", run_num, target_addr);
             }
         }
-        run_num += 1;
+    }
+
+    if targets.len() > 1 {
+        let target_metrics = target_metrics
+            .into_iter()
+            .map(Metric::from)
+            .collect::<Vec<_>>();
+        report_targets(
+            &targets,
+            &target_metrics,
+            lite_rpc_count,
+            &targets_file_name,
+            output,
+        );
     }
 
     let avg_metric = Metric::from(avg_metric);
 
     info!("Avg Metric {avg_metric:?}",);
-    csv_writer.serialize(avg_metric).unwrap();
+    info!("Overall: {}", avg_metric.latency_summary());
+    metrics_writer.write(&avg_metric).unwrap();
+
+    metrics_writer.flush().unwrap();
+
+    if !fee_level_metrics.is_empty() {
+        report_priority_fees(fee_level_metrics, &priority_fees_file_name, output);
+    }
+
+    if let Some(leaders_rpc_addr) = leaders_rpc_addr {
+        report_leaders(leaders_rpc_addr, &landings, &leaders_file_name, output).await;
+    }
+
+    // all runs are done, close the channel so that the logger flushes the remaining records
+    drop(tx_log_sx);
+    if let Some(tx_logger_jh) = tx_logger_jh {
+        if let Err(e) = tx_logger_jh.await {
+            error!("transaction logger failed {e:?}");
+        }
+    }
+}
+
+/// sends probe windows back to back indefinitely, exporting the metrics of each window once its txs
+/// are confirmed or timed out
+#[allow(clippy::too_many_arguments)]
+async fn run_daemon(
+    rpc_client: Arc<RpcClient>,
+    funded_payers: Arc<Vec<Keypair>>,
+    workload: Arc<WorkloadGenerator>,
+    block_hash: Arc<RwLock<Hash>>,
+    current_slot: Arc<AtomicU64>,
+    probe_tps: u64,
+    probe_window: Duration,
+    prometheus_addr: String,
+) {
+    info!("Probing at {probe_tps} tps, metrics on {prometheus_addr}");
+    let mut prometheus = PrometheusSync::sync(prometheus_addr);
+    let tx_count = (probe_tps * probe_window.as_secs()) as usize;
+    // probe txs are not logged, the receiver is dropped right away
+    let (tx_log_sx, _) = tokio::sync::mpsc::unbounded_channel::<TxMetricData>();
+    let mut window_interval = tokio::time::interval(probe_window);
 
-    csv_writer.flush().unwrap();
+    for seed in 0.. {
+        tokio::select! {
+            _ = window_interval.tick() => {
+                // windows overlap while the txs of the previous one are being confirmed
+                let window = tokio::spawn(bench(
+                    rpc_client.clone(),
+                    rpc_client.clone(),
+                    tx_count,
+                    funded_payers.clone(),
+                    workload.clone(),
+                    seed,
+                    block_hash.clone(),
+                    current_slot.clone(),
+                    tx_log_sx.clone(),
+                    false,
+                    Some(probe_tps),
+                    None,
+                    None,
+                    Duration::ZERO,
+                ));
+                tokio::spawn(async move {
+                    match window.await {
+                        Ok((metric, _)) => {
+                            info!("Probe window {seed}: {}", metric.latency_summary());
+                            record_probe_window(&metric);
+                        }
+                        Err(e) => error!("probe window {seed} failed {e:?}"),
+                    }
+                });
+            }
+            res = &mut prometheus => {
+                error!("prometheus endpoint stopped {res:?}");
+                return;
+            }
+        }
+    }
+}
+
+/// logs landing rate and latency of every target, comparing lite-rpc targets to the reference rpc
+fn report_targets(
+    targets: &[(String, Arc<RpcClient>)],
+    target_metrics: &[Metric],
+    lite_rpc_count: usize,
+    file_name: &str,
+    output: OutputFormat,
+) {
+    let mut report_writer = RecordWriter::create(file_name, output).unwrap();
+    for ((target_addr, _), metric) in targets.iter().zip(target_metrics) {
+        info!("{target_addr}: {}", metric.latency_summary());
+        report_writer
+            .write(&TargetMetric::new(target_addr.clone(), metric))
+            .unwrap();
+    }
+    report_writer.flush().unwrap();
+
+    let Some((reference_addr, _)) = targets.get(lite_rpc_count) else {
+        return;
+    };
+    let reference = &target_metrics[lite_rpc_count];
+    for ((target_addr, _), metric) in targets.iter().zip(target_metrics).take(lite_rpc_count) {
+        info!(
+            "{target_addr} vs {reference_addr}: landing rate {:+.2} pts, p50 confirmation {:+} ms, p90 confirmation {:+} ms",
+            (metric.landing_rate - reference.landing_rate) * 100.0,
+            metric.p50_confirmation_time_ms as i64 - reference.p50_confirmation_time_ms as i64,
+            metric.p90_confirmation_time_ms as i64 - reference.p90_confirmation_time_ms as i64,
+        );
+    }
+}
+
+/// logs the landing rate of the txs sent to lite-rpc per leader of the slot they were sent in
+async fn report_leaders(
+    leaders_rpc_addr: String,
+    landings: &[TxLanding],
+    file_name: &str,
+    output: OutputFormat,
+) {
+    let leaders_rpc_client = RpcClient::new(leaders_rpc_addr);
+    let leader_metrics = match leader_report(&leaders_rpc_client, landings).await {
+        Ok(leader_metrics) => leader_metrics,
+        Err(e) => {
+            error!("error fetching slot leaders {e:?}");
+            return;
+        }
+    };
+
+    let mut report_writer = RecordWriter::create(file_name, output).unwrap();
+    for leader_metric in &leader_metrics {
+        info!(
+            "leader {:<44}: landed {}/{} tx(s) ({:.2}%), included {} tx(s)",
+            leader_metric.leader,
+            leader_metric.txs_confirmed,
+            leader_metric.txs_sent,
+            leader_metric.landing_rate * 100.0,
+            leader_metric.txs_included
+        );
+        report_writer.write(leader_metric).unwrap();
+    }
+    report_writer.flush().unwrap();
+}
+
+/// logs landing rate and latency per compute unit price along with the cheapest price worth paying
+fn report_priority_fees(
+    fee_level_metrics: BTreeMap<u64, AvgMetric>,
+    file_name: &str,
+    output: OutputFormat,
+) {
+    // fee levels within this margin of the best landing rate are considered as good as the best
+    const LANDING_RATE_MARGIN: f64 = 0.05;
+
+    let reports = fee_level_metrics
+        .into_iter()
+        .map(|(cu_price, avg_metric)| PriorityFeeMetric::new(cu_price, &Metric::from(avg_metric)))
+        .collect::<Vec<_>>();
+
+    let mut report_writer = RecordWriter::create(file_name, output).unwrap();
+    for report in &reports {
+        info!(
+            "cu price {:>10} micro-lamports: landed {}/{} tx(s) ({:.2}%), avg confirmation {:.2} ms",
+            report.cu_price,
+            report.txs_confirmed,
+            report.txs_sent,
+            report.landing_rate * 100.0,
+            report.average_confirmation_time_ms
+        );
+        report_writer.write(report).unwrap();
+    }
+    report_writer.flush().unwrap();
+
+    let best_landing_rate = reports
+        .iter()
+        .map(|report| report.landing_rate)
+        .fold(0.0, f64::max);
+    // reports are sorted by cu price so the first one good enough is the cheapest
+    if let Some(report) = reports
+        .iter()
+        .find(|report| report.landing_rate >= best_landing_rate - LANDING_RATE_MARGIN)
+    {
+        info!(
+            "Lowest cu price landing close to the best rate of {:.2}%: {} micro-lamports ({:.2}% landed, avg confirmation {:.2} ms)",
+            best_landing_rate * 100.0,
+            report.cu_price,
+            report.landing_rate * 100.0,
+            report.average_confirmation_time_ms
+        );
+    }
 }
 
 #[derive(Clone, Debug, Copy)]
@@ -142,56 +483,93 @@ struct TxSendData {
     sent_duration: Duration,
     sent_instant: Instant,
     sent_slot: Slot,
+    /// sent during the warm-up, not part of the statistics
+    warmup: bool,
 }
 
 #[allow(clippy::too_many_arguments)]
 async fn bench(
     rpc_client: Arc<RpcClient>,
+    status_rpc_client: Arc<RpcClient>,
     tx_count: usize,
-    funded_payer: Keypair,
+    funded_payers: Arc<Vec<Keypair>>,
+    workload: Arc<WorkloadGenerator>,
     seed: u64,
     block_hash: Arc<RwLock<Hash>>,
     current_slot: Arc<AtomicU64>,
     tx_metric_sx: UnboundedSender<TxMetricData>,
     log_txs: bool,
-) -> Metric {
-    let map_of_txs = Arc::new(DashMap::new());
+    target_tps: Option<u64>,
+    mut slot_aligner: Option<SlotAligner>,
+    cu_price: Option<u64>,
+    warmup: Duration,
+) -> (Metric, Vec<TxLanding>) {
+    // give paced runs enough time to send all their transactions before timing out
+    let send_phase = match (&slot_aligner, target_tps) {
+        (Some(slot_aligner), _) => slot_aligner.send_phase(tx_count),
+        (None, Some(target_tps)) => Duration::from_secs_f64(tx_count as f64 / target_tps as f64),
+        (None, None) => Duration::ZERO,
+    };
+    let paced = slot_aligner.is_some() || target_tps.is_some();
+
+    let map_of_txs: Arc<DashMap<Signature, TxSendData>> = Arc::new(DashMap::new());
     // transaction sender task
-    {
+    let sender_jh = {
         let map_of_txs = map_of_txs.clone();
         let rpc_client = rpc_client.clone();
-        let current_slot = current_slot.clone();
         tokio::spawn(async move {
-            let map_of_txs = map_of_txs.clone();
             let rand_strings = BenchHelper::generate_random_strings(tx_count, Some(seed));
+            let send_start = Instant::now();
+            let mut paced_sends = vec![];
+
+            for (index, rand_string) in rand_strings.into_iter().enumerate() {
+                if let Some(slot_aligner) = &mut slot_aligner {
+                    slot_aligner.wait_for_turn(index).await;
+                } else if let Some(target_tps) = target_tps {
+                    // the n-th tx is due at n / tps
+                    let send_at =
+                        send_start + Duration::from_secs_f64(index as f64 / target_tps as f64);
+                    tokio::time::sleep_until(send_at).await;
+                }
 
-            for rand_string in rand_strings {
                 let blockhash = { *block_hash.read().await };
-                let tx = BenchHelper::create_memo_tx(&rand_string, &funded_payer, blockhash);
-                let start_time = Instant::now();
-                match rpc_client.send_transaction(&tx).await {
-                    Ok(signature) => {
-                        map_of_txs.insert(
-                            signature,
-                            TxSendData {
-                                sent_duration: start_time.elapsed(),
-                                sent_instant: Instant::now(),
-                                sent_slot: current_slot.load(std::sync::atomic::Ordering::Relaxed),
-                            },
-                        );
-                    }
+                let tx = match workload.create_tx(
+                    &rand_string,
+                    &funded_payers[index % funded_payers.len()],
+                    blockhash,
+                    cu_price,
+                ) {
+                    Ok(tx) => tx,
                     Err(e) => {
-                        warn!("tx send failed with error {}", e);
+                        warn!("tx creation failed with error {}", e);
+                        continue;
                     }
+                };
+                let send = send_tx(
+                    rpc_client.clone(),
+                    tx,
+                    map_of_txs.clone(),
+                    current_slot.clone(),
+                    send_start.elapsed() < warmup,
+                );
+                if paced {
+                    // spawned so that a slow rpc does not throttle the requested rate
+                    paced_sends.push(tokio::spawn(send));
+                } else {
+                    send.await;
                 }
             }
-        });
-    }
+            join_all(paced_sends).await;
+            // the warm-up is left out of the achieved tps along with its txs
+            send_start.elapsed().saturating_sub(warmup)
+        })
+    };
 
     let mut metric = Metric::default();
+    let mut landings = vec![];
     let confirmation_time = Instant::now();
     let mut confirmed_count = 0;
-    while confirmation_time.elapsed() < Duration::from_secs(60)
+    while confirmation_time.elapsed() < send_phase + Duration::from_secs(60)
         && !(map_of_txs.is_empty() && confirmed_count == tx_count)
     {
         let signatures = map_of_txs.iter().map(|x| *x.key()).collect::<Vec<_>>();
@@ -200,19 +578,25 @@ async fn bench(
             continue;
         }
 
-        if let Ok(res) = rpc_client.get_signature_statuses(&signatures).await {
+        if let Ok(res) = status_rpc_client.get_signature_statuses(&signatures).await {
             for (i, signature) in signatures.iter().enumerate() {
-                let tx_status = &res.value[i];
-                if tx_status.is_some() {
+                if let Some(tx_status) = &res.value[i] {
                     let tx_data = map_of_txs.get(signature).unwrap();
                     let time_to_confirm = tx_data.sent_instant.elapsed();
-                    metric.add_successful_transaction(tx_data.sent_duration, time_to_confirm);
+                    let warmup_tx = tx_data.warmup;
+                    if !warmup_tx {
+                        metric.add_successful_transaction(tx_data.sent_duration, time_to_confirm);
+                        landings.push(TxLanding {
+                            sent_slot: tx_data.sent_slot,
+                            confirmed_slot: Some(tx_status.slot),
+                        });
+                    }
 
-                    if log_txs {
+                    if log_txs && !warmup_tx {
                         let _ = tx_metric_sx.send(TxMetricData {
                             signature: signature.to_string(),
                             sent_slot: tx_data.sent_slot,
-                            confirmed_slot: current_slot.load(Ordering::Relaxed),
+                            confirmed_slot: tx_status.slot,
                             time_to_send_in_millis: tx_data.sent_duration.as_millis() as u64,
                             time_to_confirm_in_millis: time_to_confirm.as_millis() as u64,
                         });
@@ -225,9 +609,43 @@ async fn bench(
         }
     }
 
-    for tx in map_of_txs.iter() {
+    for tx in map_of_txs.iter().filter(|tx| !tx.warmup) {
         metric.add_unsuccessful_transaction(tx.sent_duration);
+        landings.push(TxLanding {
+            sent_slot: tx.sent_slot,
+            confirmed_slot: None,
+        });
+    }
+    match sender_jh.await {
+        Ok(send_duration) => metric.set_send_duration(send_duration),
+        Err(e) => error!("transaction sender task failed {e:?}"),
     }
     metric.finalize();
-    metric
+    (metric, landings)
+}
+
+async fn send_tx(
+    rpc_client: Arc<RpcClient>,
+    tx: VersionedTransaction,
+    map_of_txs: Arc<DashMap<Signature, TxSendData>>,
+    current_slot: Arc<AtomicU64>,
+    warmup: bool,
+) {
+    let start_time = Instant::now();
+    match rpc_client.send_transaction(&tx).await {
+        Ok(signature) => {
+            map_of_txs.insert(
+                signature,
+                TxSendData {
+                    sent_duration: start_time.elapsed(),
+                    sent_instant: Instant::now(),
+                    sent_slot: current_slot.load(Ordering::Relaxed),
+                    warmup,
+                },
+            );
+        }
+        Err(e) => {
+            warn!("tx send failed with error {}", e);
+        }
+    }
 }
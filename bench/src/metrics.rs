@@ -1,22 +1,54 @@
 use std::{
+    fmt,
     ops::{AddAssign, DivAssign},
     time::Duration,
 };
 
 use solana_sdk::slot_history::Slot;
 
-#[derive(Clone, Copy, Debug, Default, serde::Serialize)]
+#[derive(Clone, Debug, Default, serde::Serialize)]
 pub struct Metric {
     pub txs_sent: u64,
     pub txs_confirmed: u64,
     pub txs_un_confirmed: u64,
     pub average_confirmation_time_ms: f64,
     pub average_time_to_send_txs: f64,
+    pub achieved_tps: f64,
+    pub landing_rate: f64,
+    pub p50_confirmation_time_ms: u64,
+    pub p90_confirmation_time_ms: u64,
+    pub p99_confirmation_time_ms: u64,
+    pub max_confirmation_time_ms: u64,
 
     #[serde(skip_serializing)]
     total_sent_time: Duration,
     #[serde(skip_serializing)]
     total_confirmation_time: Duration,
+    #[serde(skip_serializing)]
+    send_duration: Duration,
+    #[serde(skip_serializing)]
+    confirmation_times: Samples,
+}
+
+/// per transaction confirmation times, kept out of debug output as there is one per tx
+#[derive(Clone, Default)]
+struct Samples(Vec<Duration>);
+
+impl fmt::Debug for Samples {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} samples", self.0.len())
+    }
+}
+
+impl Samples {
+    /// nearest rank percentile, expects the samples to be sorted
+    fn percentile_ms(&self, percentile: u64) -> u64 {
+        if self.0.is_empty() {
+            return 0;
+        }
+        let rank = ((percentile as usize * self.0.len() + 99) / 100).max(1);
+        self.0[rank - 1].as_millis() as u64
+    }
 }
 
 impl Metric {
@@ -27,6 +59,7 @@ impl Metric {
     ) {
         self.total_sent_time += time_to_send;
         self.total_confirmation_time += time_to_confrim;
+        self.confirmation_times.0.push(time_to_confrim);
 
         self.txs_confirmed += 1;
         self.txs_sent += 1;
@@ -38,7 +71,16 @@ impl Metric {
         self.txs_sent += 1;
     }
 
+    /// wall clock time it took to send all the transactions of a run
+    pub fn set_send_duration(&mut self, send_duration: Duration) {
+        self.send_duration = send_duration;
+    }
+
     pub fn finalize(&mut self) {
+        if !self.send_duration.is_zero() {
+            self.achieved_tps = self.txs_sent as f64 / self.send_duration.as_secs_f64();
+        }
+
         if self.txs_sent > 0 {
             self.average_time_to_send_txs =
                 self.total_sent_time.as_millis() as f64 / self.txs_sent as f64;
@@ -48,6 +90,34 @@ impl Metric {
             self.average_confirmation_time_ms =
                 self.total_confirmation_time.as_millis() as f64 / self.txs_confirmed as f64;
         }
+
+        if self.txs_sent > 0 {
+            self.landing_rate = self.txs_confirmed as f64 / self.txs_sent as f64;
+        }
+
+        self.confirmation_times.0.sort_unstable();
+        self.p50_confirmation_time_ms = self.confirmation_times.percentile_ms(50);
+        self.p90_confirmation_time_ms = self.confirmation_times.percentile_ms(90);
+        self.p99_confirmation_time_ms = self.confirmation_times.percentile_ms(99);
+        self.max_confirmation_time_ms = self
+            .confirmation_times
+            .0
+            .last()
+            .map_or(0, |max| max.as_millis() as u64);
+    }
+
+    /// one line summary of the confirmation latency distribution and landing rate
+    pub fn latency_summary(&self) -> String {
+        format!(
+            "landed {}/{} tx(s) ({:.2}%), confirmation latency p50 {} ms, p90 {} ms, p99 {} ms, max {} ms",
+            self.txs_confirmed,
+            self.txs_sent,
+            self.landing_rate * 100.0,
+            self.p50_confirmation_time_ms,
+            self.p90_confirmation_time_ms,
+            self.p99_confirmation_time_ms,
+            self.max_confirmation_time_ms
+        )
     }
 }
 
@@ -71,12 +141,17 @@ impl AddAssign<&Self> for Metric {
 
         self.total_confirmation_time += rhs.total_confirmation_time;
         self.total_sent_time += rhs.total_sent_time;
+        self.send_duration += rhs.send_duration;
+        self.confirmation_times
+            .0
+            .extend_from_slice(&rhs.confirmation_times.0);
         self.finalize();
     }
 }
 
 impl DivAssign<u64> for Metric {
     // used to avg metrics, if there were no runs then benchmark averages across 0 runs
+    // confirmation times are kept as is so percentiles of the average span all runs
     fn div_assign(&mut self, rhs: u64) {
         if rhs == 0 {
             return;
@@ -89,6 +164,8 @@ impl DivAssign<u64> for Metric {
             Duration::from_micros((self.total_confirmation_time.as_micros() / rhs as u128) as u64);
         self.total_sent_time =
             Duration::from_micros((self.total_sent_time.as_micros() / rhs as u128) as u64);
+        self.send_duration =
+            Duration::from_micros((self.send_duration.as_micros() / rhs as u128) as u64);
         self.finalize();
     }
 }
@@ -107,6 +184,58 @@ impl From<AvgMetric> for Metric {
     }
 }
 
+/// landing rate and latency of the transactions sent with a given compute unit price
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct PriorityFeeMetric {
+    pub cu_price: u64,
+    pub txs_sent: u64,
+    pub txs_confirmed: u64,
+    pub landing_rate: f64,
+    pub average_confirmation_time_ms: f64,
+    pub p90_confirmation_time_ms: u64,
+}
+
+impl PriorityFeeMetric {
+    pub fn new(cu_price: u64, metric: &Metric) -> Self {
+        Self {
+            cu_price,
+            txs_sent: metric.txs_sent,
+            txs_confirmed: metric.txs_confirmed,
+            landing_rate: metric.landing_rate,
+            average_confirmation_time_ms: metric.average_confirmation_time_ms,
+            p90_confirmation_time_ms: metric.p90_confirmation_time_ms,
+        }
+    }
+}
+
+/// landing rate and latency of the transactions sent to a given rpc
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct TargetMetric {
+    pub target: String,
+    pub txs_sent: u64,
+    pub txs_confirmed: u64,
+    pub landing_rate: f64,
+    pub average_confirmation_time_ms: f64,
+    pub p50_confirmation_time_ms: u64,
+    pub p90_confirmation_time_ms: u64,
+    pub p99_confirmation_time_ms: u64,
+}
+
+impl TargetMetric {
+    pub fn new(target: String, metric: &Metric) -> Self {
+        Self {
+            target,
+            txs_sent: metric.txs_sent,
+            txs_confirmed: metric.txs_confirmed,
+            landing_rate: metric.landing_rate,
+            average_confirmation_time_ms: metric.average_confirmation_time_ms,
+            p50_confirmation_time_ms: metric.p50_confirmation_time_ms,
+            p90_confirmation_time_ms: metric.p90_confirmation_time_ms,
+            p99_confirmation_time_ms: metric.p99_confirmation_time_ms,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, serde::Serialize)]
 pub struct TxMetricData {
     pub signature: String,
@@ -115,3 +244,51 @@ pub struct TxMetricData {
     pub time_to_send_in_millis: u64,
     pub time_to_confirm_in_millis: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(millis: &[u64]) -> Samples {
+        Samples(millis.iter().copied().map(Duration::from_millis).collect())
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        assert_eq!(samples(&[]).percentile_ms(50), 0);
+        assert_eq!(samples(&[]).percentile_ms(99), 0);
+
+        let one = samples(&[7]);
+        assert_eq!(one.percentile_ms(0), 7);
+        assert_eq!(one.percentile_ms(50), 7);
+        assert_eq!(one.percentile_ms(99), 7);
+
+        let four = samples(&[10, 20, 30, 40]);
+        assert_eq!(four.percentile_ms(50), 20);
+        assert_eq!(four.percentile_ms(51), 30);
+        assert_eq!(four.percentile_ms(99), 40);
+
+        let ten = samples(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(ten.percentile_ms(50), 5);
+        assert_eq!(ten.percentile_ms(90), 9);
+        assert_eq!(ten.percentile_ms(99), 10);
+    }
+
+    #[test]
+    fn finalize_sorts_confirmation_times() {
+        let mut metric = Metric::default();
+        for millis in [300, 100, 200] {
+            metric.add_successful_transaction(
+                Duration::from_millis(1),
+                Duration::from_millis(millis),
+            );
+        }
+        metric.add_unsuccessful_transaction(Duration::from_millis(1));
+        metric.finalize();
+
+        assert_eq!(metric.p50_confirmation_time_ms, 200);
+        assert_eq!(metric.p99_confirmation_time_ms, 300);
+        assert_eq!(metric.max_confirmation_time_ms, 300);
+        assert_eq!(metric.landing_rate, 0.75);
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    /// one json object per line
+    Json,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+        }
+    }
+
+    /// file name with its extension matching the output format
+    pub fn file_path(&self, file_name: &str) -> PathBuf {
+        Path::new(file_name).with_extension(self.extension())
+    }
+}
+
+/// writes serializable records to a file in the selected output format
+pub enum RecordWriter {
+    Csv(csv::Writer<File>),
+    Json(BufWriter<File>),
+}
+
+impl RecordWriter {
+    pub fn create(file_name: &str, format: OutputFormat) -> anyhow::Result<Self> {
+        let path = format.file_path(file_name);
+        let file = File::create(&path).with_context(|| format!("Error creating {path:?}"))?;
+
+        Ok(match format {
+            OutputFormat::Csv => RecordWriter::Csv(csv::Writer::from_writer(file)),
+            OutputFormat::Json => RecordWriter::Json(BufWriter::new(file)),
+        })
+    }
+
+    pub fn write<T: Serialize>(&mut self, record: &T) -> anyhow::Result<()> {
+        match self {
+            RecordWriter::Csv(writer) => writer.serialize(record)?,
+            RecordWriter::Json(writer) => {
+                serde_json::to_writer(&mut *writer, record)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        match self {
+            RecordWriter::Csv(writer) => writer.flush()?,
+            RecordWriter::Json(writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Record {
+        signature: &'static str,
+        slot: u64,
+    }
+
+    fn write_records(format: OutputFormat) -> String {
+        let file_name = std::env::temp_dir()
+            .join(format!("bench-output-{}", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let mut writer = RecordWriter::create(&file_name, format).unwrap();
+        writer
+            .write(&Record {
+                signature: "a",
+                slot: 1,
+            })
+            .unwrap();
+        writer
+            .write(&Record {
+                signature: "b",
+                slot: 2,
+            })
+            .unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let path = format.file_path(&file_name);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        contents
+    }
+
+    #[test]
+    fn csv_has_a_header_and_one_row_per_record() {
+        assert_eq!(
+            write_records(OutputFormat::Csv),
+            "signature,slot\na,1\nb,2\n"
+        );
+    }
+
+    #[test]
+    fn json_has_one_object_per_line() {
+        assert_eq!(
+            write_records(OutputFormat::Json),
+            "{\"signature\":\"a\",\"slot\":1}\n{\"signature\":\"b\",\"slot\":2}\n"
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use prometheus::{
+    core::GenericGauge, opts, register_gauge, register_int_counter, register_int_gauge, Gauge,
+    IntCounter,
+};
+
+use crate::metrics::Metric;
+
+lazy_static::lazy_static! {
+    static ref PROBE_TXS_SENT: IntCounter =
+        register_int_counter!("bench_probe_txs_sent", "Number of probe transactions sent").unwrap();
+    static ref PROBE_TXS_CONFIRMED: IntCounter =
+        register_int_counter!("bench_probe_txs_confirmed", "Number of probe transactions confirmed").unwrap();
+    static ref PROBE_TXS_UNCONFIRMED: IntCounter =
+        register_int_counter!("bench_probe_txs_unconfirmed", "Number of probe transactions not confirmed in time").unwrap();
+    static ref PROBE_LANDING_RATE: Gauge =
+        register_gauge!(opts!("bench_probe_landing_rate", "Ratio of probe transactions confirmed in the last window")).unwrap();
+    static ref PROBE_AVG_CONFIRMATION_TIME: GenericGauge<prometheus::core::AtomicI64> =
+        register_int_gauge!(opts!("bench_probe_avg_confirmation_time_ms", "Average confirmation time of the probe transactions of the last window")).unwrap();
+    static ref PROBE_P50_CONFIRMATION_TIME: GenericGauge<prometheus::core::AtomicI64> =
+        register_int_gauge!(opts!("bench_probe_p50_confirmation_time_ms", "Median confirmation time of the probe transactions of the last window")).unwrap();
+    static ref PROBE_P90_CONFIRMATION_TIME: GenericGauge<prometheus::core::AtomicI64> =
+        register_int_gauge!(opts!("bench_probe_p90_confirmation_time_ms", "90th percentile confirmation time of the probe transactions of the last window")).unwrap();
+    static ref PROBE_P99_CONFIRMATION_TIME: GenericGauge<prometheus::core::AtomicI64> =
+        register_int_gauge!(opts!("bench_probe_p99_confirmation_time_ms", "99th percentile confirmation time of the probe transactions of the last window")).unwrap();
+    static ref PROBE_MAX_CONFIRMATION_TIME: GenericGauge<prometheus::core::AtomicI64> =
+        register_int_gauge!(opts!("bench_probe_max_confirmation_time_ms", "Maximum confirmation time of the probe transactions of the last window")).unwrap();
+}
+
+/// exports the metric of a finished probe window
+pub fn record_probe_window(metric: &Metric) {
+    PROBE_TXS_SENT.inc_by(metric.txs_sent);
+    PROBE_TXS_CONFIRMED.inc_by(metric.txs_confirmed);
+    PROBE_TXS_UNCONFIRMED.inc_by(metric.txs_un_confirmed);
+    PROBE_LANDING_RATE.set(metric.landing_rate);
+    PROBE_AVG_CONFIRMATION_TIME.set(metric.average_confirmation_time_ms as i64);
+    PROBE_P50_CONFIRMATION_TIME.set(metric.p50_confirmation_time_ms as i64);
+    PROBE_P90_CONFIRMATION_TIME.set(metric.p90_confirmation_time_ms as i64);
+    PROBE_P99_CONFIRMATION_TIME.set(metric.p99_confirmation_time_ms as i64);
+    PROBE_MAX_CONFIRMATION_TIME.set(metric.max_confirmation_time_ms as i64);
+}
@@ -0,0 +1,125 @@
+use clap::ValueEnum;
+use futures::StreamExt;
+use log::{error, info};
+use solana_pubsub_client::nonblocking::pubsub_client::PubsubClient;
+use solana_sdk::{clock::DEFAULT_MS_PER_SLOT, slot_history::Slot};
+use tokio::{
+    sync::watch,
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SlotAlignment {
+    /// send all the txs of a slot at once, at the slot offset
+    Burst,
+    /// spread the txs of a slot evenly across the slot, starting at the slot offset
+    Spread,
+}
+
+/// paces the txs of a run relative to the start of slots as seen by the slot subscription
+pub struct SlotAligner {
+    alignment: SlotAlignment,
+    txs_per_slot: usize,
+    slot_offset: Duration,
+    slot_rx: watch::Receiver<Slot>,
+    slot_start: Instant,
+}
+
+impl SlotAligner {
+    pub fn new(
+        alignment: SlotAlignment,
+        txs_per_slot: usize,
+        slot_offset: Duration,
+        slot_rx: watch::Receiver<Slot>,
+    ) -> Self {
+        Self {
+            alignment,
+            txs_per_slot: txs_per_slot.max(1),
+            slot_offset,
+            slot_rx,
+            slot_start: Instant::now(),
+        }
+    }
+
+    /// waits until the `index`-th tx of the run is due
+    pub async fn wait_for_turn(&mut self, index: usize) {
+        let position = index % self.txs_per_slot;
+        if position == 0 {
+            // every `txs_per_slot` txs, wait for the next slot to begin
+            self.slot_rx.borrow_and_update();
+            if self.slot_rx.changed().await.is_err() {
+                error!("slot subscription closed, sending unaligned");
+            }
+            self.slot_start = Instant::now();
+        }
+
+        let send_at = match self.alignment {
+            SlotAlignment::Burst => self.slot_start + self.slot_offset,
+            SlotAlignment::Spread => {
+                let slot_time =
+                    Duration::from_millis(DEFAULT_MS_PER_SLOT).saturating_sub(self.slot_offset);
+                self.slot_start
+                    + self.slot_offset
+                    + slot_time.mul_f64(position as f64 / self.txs_per_slot as f64)
+            }
+        };
+        tokio::time::sleep_until(send_at).await;
+    }
+
+    /// expected time to send `tx_count` txs
+    pub fn send_phase(&self, tx_count: usize) -> Duration {
+        let slots = (tx_count + self.txs_per_slot - 1) / self.txs_per_slot;
+        Duration::from_millis(DEFAULT_MS_PER_SLOT * slots as u64)
+    }
+}
+
+/// publishes the latest slot notified by the websocket slot subscription
+pub fn spawn_slot_subscription(ws_addr: String) -> watch::Receiver<Slot> {
+    let (slot_sx, slot_rx) = watch::channel(0);
+    tokio::spawn(async move {
+        let pubsub_client = match PubsubClient::new(&ws_addr).await {
+            Ok(pubsub_client) => pubsub_client,
+            Err(e) => {
+                error!("error connecting to {ws_addr} {e:?}");
+                return;
+            }
+        };
+        let (mut slot_stream, _unsubscribe) = match pubsub_client.slot_subscribe().await {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                error!("error subscribing to slots {e:?}");
+                return;
+            }
+        };
+        info!("subscribed to slots on {ws_addr}");
+
+        while let Some(slot_info) = slot_stream.next().await {
+            if slot_sx.send(slot_info.slot).is_err() {
+                break;
+            }
+        }
+        error!("slot subscription ended");
+    });
+    slot_rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_phase_rounds_up_to_whole_slots() {
+        let (_slot_sx, slot_rx) = watch::channel(0);
+        let aligner = SlotAligner::new(SlotAlignment::Burst, 4, Duration::ZERO, slot_rx);
+
+        assert_eq!(aligner.send_phase(0), Duration::ZERO);
+        assert_eq!(
+            aligner.send_phase(4),
+            Duration::from_millis(DEFAULT_MS_PER_SLOT)
+        );
+        assert_eq!(
+            aligner.send_phase(9),
+            Duration::from_millis(3 * DEFAULT_MS_PER_SLOT)
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::{bail, Context};
+use clap::ValueEnum;
+use log::info;
+use solana_address_lookup_table_program::instruction::{create_lookup_table, extend_lookup_table};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    address_lookup_table_account::AddressLookupTableAccount,
+    commitment_config::CommitmentConfig,
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    message::{v0, Message, VersionedMessage},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction,
+    transaction::{Transaction, VersionedTransaction},
+};
+use tokio::time::Duration;
+
+use crate::helpers::BenchHelper;
+
+/// lamports sent to a new account by each transfer, enough to make it rent exempt
+const TRANSFER_LAMPORTS: u64 = 1_000_000;
+/// highest compute unit limit a transaction can request
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+/// memo of each cu heavy tx, as large as fits in a packet along with the compute budget instructions
+const CU_HEAVY_MEMO_LEN: usize = 900;
+/// added to the compute units a cu heavy tx used in simulation
+const CU_HEAVY_LIMIT_MARGIN_PERCENT: u64 = 10;
+/// addresses of the lookup table, loaded by every large v0 tx, stays below the 64 account locks limit
+const LOOKUP_TABLE_ADDRESS_COUNT: usize = 48;
+/// addresses added per extend transaction so that it fits in a packet
+const LOOKUP_TABLE_EXTEND_CHUNK: usize = 24;
+const LARGE_V0_MEMO_LEN: usize = 512;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Workload {
+    /// memo with a short random message
+    #[default]
+    Memo,
+    /// sol transfer to a new account
+    Transfer,
+    /// memo as large as fits in a packet, under a compute unit limit measured at startup
+    CuHeavy,
+    /// v0 tx loading many accounts from an address lookup table along with a large memo
+    LargeV0,
+}
+
+/// builds the transactions of a workload, creating the accounts it needs beforehand
+pub struct WorkloadGenerator {
+    workload: Workload,
+    lookup_table: Option<AddressLookupTableAccount>,
+    cu_heavy_limit: Option<u32>,
+}
+
+impl WorkloadGenerator {
+    pub async fn new(
+        workload: Workload,
+        rpc_client: &RpcClient,
+        authority: &Keypair,
+    ) -> anyhow::Result<Self> {
+        let lookup_table = match workload {
+            Workload::LargeV0 => Some(Self::create_lookup_table(rpc_client, authority).await?),
+            _ => None,
+        };
+        let cu_heavy_limit = match workload {
+            Workload::CuHeavy => Some(Self::measure_cu_heavy_limit(rpc_client, authority).await?),
+            _ => None,
+        };
+
+        Ok(Self {
+            workload,
+            lookup_table,
+            cu_heavy_limit,
+        })
+    }
+
+    /// `msg` is random and makes every transaction of the workload unique
+    pub fn create_tx(
+        &self,
+        msg: &[u8],
+        payer: &Keypair,
+        blockhash: Hash,
+        cu_price: Option<u64>,
+    ) -> anyhow::Result<VersionedTransaction> {
+        let mut instructions = vec![];
+        if let Some(cu_price) = cu_price {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(cu_price));
+        }
+
+        match self.workload {
+            Workload::Memo => {
+                instructions.push(BenchHelper::create_memo_instruction(msg));
+            }
+            Workload::Transfer => {
+                instructions.push(system_instruction::transfer(
+                    &payer.pubkey(),
+                    &Pubkey::new_unique(),
+                    TRANSFER_LAMPORTS,
+                ));
+            }
+            Workload::CuHeavy => {
+                let cu_limit = self
+                    .cu_heavy_limit
+                    .expect("compute unit limit is measured along with the cu heavy workload");
+                instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(cu_limit));
+                instructions.push(BenchHelper::create_memo_instruction(&Self::cu_heavy_memo(
+                    msg,
+                )));
+            }
+            Workload::LargeV0 => {
+                let lookup_table = self
+                    .lookup_table
+                    .as_ref()
+                    .expect("lookup table is created along with the large v0 workload");
+
+                // a 0 lamport transfer to self does nothing but loads the table accounts read only,
+                // so that txs of different payers do not contend on the same write locks
+                let mut transfer =
+                    system_instruction::transfer(&payer.pubkey(), &payer.pubkey(), 0);
+                transfer.accounts.extend(
+                    lookup_table
+                        .addresses
+                        .iter()
+                        .map(|address| AccountMeta::new_readonly(*address, false)),
+                );
+                instructions.push(transfer);
+
+                let memo = msg
+                    .iter()
+                    .copied()
+                    .cycle()
+                    .take(LARGE_V0_MEMO_LEN)
+                    .collect::<Vec<_>>();
+                instructions.push(BenchHelper::create_memo_instruction(&memo));
+
+                let message = v0::Message::try_compile(
+                    &payer.pubkey(),
+                    &instructions,
+                    std::slice::from_ref(lookup_table),
+                    blockhash,
+                )?;
+                return Ok(VersionedTransaction::try_new(
+                    VersionedMessage::V0(message),
+                    &[payer],
+                )?);
+            }
+        }
+
+        let message = Message::new(&instructions, Some(&payer.pubkey()));
+        Ok(Transaction::new(&[payer], message, blockhash).into())
+    }
+
+    fn cu_heavy_memo(msg: &[u8]) -> Vec<u8> {
+        msg.iter()
+            .copied()
+            .cycle()
+            .take(CU_HEAVY_MEMO_LEN)
+            .collect()
+    }
+
+    /// simulates a cu heavy tx under the maximum limit so that the sent ones only request, and
+    /// pay for, the compute units they use
+    async fn measure_cu_heavy_limit(
+        rpc_client: &RpcClient,
+        payer: &Keypair,
+    ) -> anyhow::Result<u32> {
+        let blockhash = rpc_client.get_latest_blockhash().await?;
+        let instructions = [
+            ComputeBudgetInstruction::set_compute_unit_limit(MAX_COMPUTE_UNIT_LIMIT),
+            BenchHelper::create_memo_instruction(&Self::cu_heavy_memo(b"cu heavy workload")),
+        ];
+        let message = Message::new(&instructions, Some(&payer.pubkey()));
+        let tx = Transaction::new(&[payer], message, blockhash);
+
+        let simulation = rpc_client.simulate_transaction(&tx).await?.value;
+        if let Some(err) = simulation.err {
+            bail!("Simulating a cu heavy tx failed: {err}");
+        }
+        let units_consumed = simulation
+            .units_consumed
+            .context("simulation did not report the consumed compute units")?;
+        let cu_limit = (units_consumed * (100 + CU_HEAVY_LIMIT_MARGIN_PERCENT) / 100)
+            .min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32;
+        info!("cu heavy txs use {units_consumed} compute units, requesting {cu_limit}");
+        Ok(cu_limit)
+    }
+
+    async fn create_lookup_table(
+        rpc_client: &RpcClient,
+        authority: &Keypair,
+    ) -> anyhow::Result<AddressLookupTableAccount> {
+        // the table address is derived from a slot that has to be in the slot hashes sysvar
+        let recent_slot = rpc_client
+            .get_slot_with_commitment(CommitmentConfig::finalized())
+            .await?;
+        let (create_ix, lookup_table_address) =
+            create_lookup_table(authority.pubkey(), authority.pubkey(), recent_slot);
+        Self::send_and_confirm(rpc_client, authority, create_ix).await?;
+
+        let addresses = (0..LOOKUP_TABLE_ADDRESS_COUNT)
+            .map(|_| Pubkey::new_unique())
+            .collect::<Vec<_>>();
+        for chunk in addresses.chunks(LOOKUP_TABLE_EXTEND_CHUNK) {
+            let extend_ix = extend_lookup_table(
+                lookup_table_address,
+                authority.pubkey(),
+                Some(authority.pubkey()),
+                chunk.to_vec(),
+            );
+            Self::send_and_confirm(rpc_client, authority, extend_ix).await?;
+        }
+
+        // addresses can only be used from the slot after the one they were added in
+        let extended_slot = rpc_client.get_slot().await?;
+        while rpc_client.get_slot().await? <= extended_slot {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        info!("created lookup table {lookup_table_address} with {LOOKUP_TABLE_ADDRESS_COUNT} addresses");
+
+        Ok(AddressLookupTableAccount {
+            key: lookup_table_address,
+            addresses,
+        })
+    }
+
+    async fn send_and_confirm(
+        rpc_client: &RpcClient,
+        payer: &Keypair,
+        instruction: Instruction,
+    ) -> anyhow::Result<()> {
+        let blockhash = rpc_client.get_latest_blockhash().await?;
+        let message = Message::new(&[instruction], Some(&payer.pubkey()));
+        let tx = Transaction::new(&[payer], message, blockhash);
+        let signature = rpc_client.send_transaction(&tx).await?;
+        BenchHelper::wait_till_signature_status(
+            rpc_client,
+            &signature,
+            CommitmentConfig::confirmed(),
+        )
+        .await
+    }
+}
@@ -1,7 +1,54 @@
-use solana_lite_rpc_core::types::{BlockStream, ClusterInfoStream, SlotStream, VoteAccountStream};
+use solana_lite_rpc_core::{
+    structures::{produced_block::ProducedBlock, slot_notification::SlotNotification},
+    types::{BlockStream, ClusterInfoStream, SlotStream, VoteAccountStream},
+};
+use solana_rpc_client_api::response::{RpcContactInfo, RpcVoteAccountStatus};
+use tokio::sync::broadcast::Sender;
+
 pub struct EndpointStreaming {
     pub blocks_notifier: BlockStream,
     pub slot_notifier: SlotStream,
     pub vote_account_notifier: VoteAccountStream,
     pub cluster_info_notifier: ClusterInfoStream,
 }
+
+/// Sending side of the endpoint streams, kept to restart the endpoint tasks on the same streams
+#[derive(Clone)]
+pub struct EndpointSenders {
+    pub blocks_sender: Sender<ProducedBlock>,
+    pub slot_sender: Sender<SlotNotification>,
+    pub vote_account_sender: Sender<RpcVoteAccountStatus>,
+    pub cluster_info_sender: Sender<Vec<RpcContactInfo>>,
+}
+
+impl EndpointSenders {
+    pub fn new() -> (Self, EndpointStreaming) {
+        let (blocks_sender, blocks_notifier) = tokio::sync::broadcast::channel(10);
+        let (slot_sender, slot_notifier) = tokio::sync::broadcast::channel(10);
+        let (vote_account_sender, vote_account_notifier) = tokio::sync::broadcast::channel(10);
+        let (cluster_info_sender, cluster_info_notifier) = tokio::sync::broadcast::channel(10);
+
+        let senders = Self {
+            blocks_sender,
+            slot_sender,
+            vote_account_sender,
+            cluster_info_sender,
+        };
+        let streaming = EndpointStreaming {
+            blocks_notifier,
+            slot_notifier,
+            vote_account_notifier,
+            cluster_info_notifier,
+        };
+        (senders, streaming)
+    }
+
+    pub fn subscribe(&self) -> EndpointStreaming {
+        EndpointStreaming {
+            blocks_notifier: self.blocks_sender.subscribe(),
+            slot_notifier: self.slot_sender.subscribe(),
+            vote_account_notifier: self.vote_account_sender.subscribe(),
+            cluster_info_notifier: self.cluster_info_sender.subscribe(),
+        }
+    }
+}
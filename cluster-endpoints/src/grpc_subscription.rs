@@ -1,5 +1,5 @@
 use crate::{
-    endpoint_stremers::EndpointStreaming,
+    endpoint_stremers::{EndpointSenders, EndpointStreaming},
     rpc_polling::vote_accounts_and_cluster_info_polling::poll_vote_accounts_and_cluster_info,
 };
 use anyhow::{bail, Context};
@@ -306,15 +306,31 @@ pub fn create_block_processing_task(
     })
 }
 
+/// version of the geyser grpc plugin at `grpc_addr`
+pub async fn get_grpc_version(grpc_addr: String) -> anyhow::Result<String> {
+    let mut client = GeyserGrpcClient::connect(grpc_addr, None::<&'static str>, None)?;
+    Ok(client.get_version().await?.version)
+}
+
 pub fn create_grpc_subscription(
     rpc_client: Arc<RpcClient>,
     grpc_addr: String,
     expected_grpc_version: String,
 ) -> anyhow::Result<(EndpointStreaming, Vec<AnyhowJoinHandle>)> {
-    let (slot_sx, slot_notifier) = tokio::sync::broadcast::channel(10);
-    let (block_sx, blocks_notifier) = tokio::sync::broadcast::channel(10);
-    let (cluster_info_sx, cluster_info_notifier) = tokio::sync::broadcast::channel(10);
-    let (va_sx, vote_account_notifier) = tokio::sync::broadcast::channel(10);
+    let (senders, streamers) = EndpointSenders::new();
+    let endpoint_tasks = spawn_grpc_tasks(rpc_client, grpc_addr, expected_grpc_version, &senders);
+    Ok((streamers, endpoint_tasks))
+}
+
+/// subscribes to the geyser grpc plugin and sends the notifications on `senders`
+pub fn spawn_grpc_tasks(
+    rpc_client: Arc<RpcClient>,
+    grpc_addr: String,
+    expected_grpc_version: String,
+    senders: &EndpointSenders,
+) -> Vec<AnyhowJoinHandle> {
+    let slot_sx = senders.slot_sender.clone();
+    let block_sx = senders.blocks_sender.clone();
 
     let mut slots = HashMap::new();
     slots.insert("client".to_string(), SubscribeRequestFilterSlots {});
@@ -380,21 +396,16 @@ pub fn create_grpc_subscription(
     let block_finalized_task: AnyhowJoinHandle =
         create_block_processing_task(grpc_addr, block_sx, CommitmentLevel::Finalized);
 
-    let cluster_info_polling =
-        poll_vote_accounts_and_cluster_info(rpc_client, cluster_info_sx, va_sx);
-
-    let streamers = EndpointStreaming {
-        blocks_notifier,
-        slot_notifier,
-        cluster_info_notifier,
-        vote_account_notifier,
-    };
+    let cluster_info_polling = poll_vote_accounts_and_cluster_info(
+        rpc_client,
+        senders.cluster_info_sender.clone(),
+        senders.vote_account_sender.clone(),
+    );
 
-    let endpoint_tasks = vec![
+    vec![
         slot_task,
         block_confirmed_task,
         block_finalized_task,
         cluster_info_polling,
-    ];
-    Ok((streamers, endpoint_tasks))
+    ]
 }
@@ -1,5 +1,5 @@
 use crate::{
-    endpoint_stremers::EndpointStreaming,
+    endpoint_stremers::{EndpointSenders, EndpointStreaming},
     rpc_polling::{
         poll_blocks::poll_block, poll_slots::poll_slots,
         vote_accounts_and_cluster_info_polling::poll_vote_accounts_and_cluster_info,
@@ -13,27 +13,35 @@ use std::sync::Arc;
 pub fn create_json_rpc_polling_subscription(
     rpc_client: Arc<RpcClient>,
 ) -> anyhow::Result<(EndpointStreaming, Vec<AnyhowJoinHandle>)> {
-    let (slot_sx, slot_notifier) = tokio::sync::broadcast::channel(10);
-    let (block_sx, blocks_notifier) = tokio::sync::broadcast::channel(10);
-    let (cluster_info_sx, cluster_info_notifier) = tokio::sync::broadcast::channel(10);
-    let (va_sx, vote_account_notifier) = tokio::sync::broadcast::channel(10);
+    let (senders, streamers) = EndpointSenders::new();
+    let endpoint_tasks = spawn_json_rpc_polling_tasks(rpc_client, &senders)?;
+    Ok((streamers, endpoint_tasks))
+}
 
-    let mut endpoint_tasks =
-        poll_slots(rpc_client.clone(), CommitmentConfig::processed(), slot_sx)?;
+/// polls the rpc node and sends the notifications on `senders`
+pub fn spawn_json_rpc_polling_tasks(
+    rpc_client: Arc<RpcClient>,
+    senders: &EndpointSenders,
+) -> anyhow::Result<Vec<AnyhowJoinHandle>> {
+    let mut endpoint_tasks = poll_slots(
+        rpc_client.clone(),
+        CommitmentConfig::processed(),
+        senders.slot_sender.clone(),
+    )?;
 
-    let mut block_polling_tasks =
-        poll_block(rpc_client.clone(), block_sx, slot_notifier.resubscribe());
+    let mut block_polling_tasks = poll_block(
+        rpc_client.clone(),
+        senders.blocks_sender.clone(),
+        senders.slot_sender.subscribe(),
+    );
     endpoint_tasks.append(&mut block_polling_tasks);
 
-    let cluster_info_polling =
-        poll_vote_accounts_and_cluster_info(rpc_client, cluster_info_sx, va_sx);
+    let cluster_info_polling = poll_vote_accounts_and_cluster_info(
+        rpc_client,
+        senders.cluster_info_sender.clone(),
+        senders.vote_account_sender.clone(),
+    );
     endpoint_tasks.push(cluster_info_polling);
 
-    let streamers = EndpointStreaming {
-        blocks_notifier,
-        slot_notifier,
-        cluster_info_notifier,
-        vote_account_notifier,
-    };
-    Ok((streamers, endpoint_tasks))
+    Ok(endpoint_tasks)
 }
@@ -0,0 +1,282 @@
+//! Node identity used for the QUIC client certificates
+//!
+//! The staked identity can either be a keypair loaded from disk or a remote signer so the
+//! validator identity key does not have to be copied onto the rpc boxes. A remote signer is
+//! reached over http and has to implement the following endpoints:
+//!
+//! - `GET {url}/pubkey` answering `{"pubkey": "<base58 pubkey>"}`
+//! - `POST {url}/sign` with `{"message": "<base64 message>"}` answering `{"signature": "<base58 signature>"}`
+//!
+//! The remote signer signs the self signed certificate once at startup, then the tls handshake
+//! of every new connection to a tpu. The handshakes wait for the signer, at most
+//! `REMOTE_SIGNER_TIMEOUT`, which needs the multi threaded tokio runtime.
+
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{bail, Context};
+use base64::Engine;
+use rcgen::{CertificateParams, DistinguishedName, DnType, RemoteKeyPair, SanType};
+use rustls::{
+    client::ResolvesClientCert,
+    sign::{CertifiedKey, SigningKey},
+    SignatureAlgorithm, SignatureScheme,
+};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+};
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+/// bounds how long a tls handshake waits for the remote signer
+const REMOTE_SIGNER_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub trait IdentitySigner: Send + Sync {
+    fn pubkey(&self) -> Pubkey;
+
+    /// ed25519 signature of `message` by the identity
+    fn sign_message(&self, message: &[u8]) -> anyhow::Result<Signature>;
+}
+
+impl IdentitySigner for Keypair {
+    fn pubkey(&self) -> Pubkey {
+        Signer::pubkey(self)
+    }
+
+    fn sign_message(&self, message: &[u8]) -> anyhow::Result<Signature> {
+        Ok(Signer::try_sign_message(self, message)?)
+    }
+}
+
+#[derive(Serialize)]
+struct SignRequest {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct SignResponse {
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct PubkeyResponse {
+    pubkey: String,
+}
+
+/// Identity held by a remote signer or an HSM behind an http interface
+///
+/// Signing blocks the calling thread, the tls handshakes are driven by tokio so this
+/// requires the multi threaded runtime, `connect` fails on any other.
+pub struct RemoteSigner {
+    url: String,
+    pubkey: Pubkey,
+    client: reqwest::Client,
+}
+
+impl RemoteSigner {
+    /// fetches the identity of the signer at `url`
+    pub async fn connect(url: String) -> anyhow::Result<Self> {
+        let url = url.trim_end_matches('/').to_string();
+        if Handle::current().runtime_flavor() != RuntimeFlavor::MultiThread {
+            bail!("The remote signer requires the multi threaded tokio runtime");
+        }
+        let client = reqwest::Client::builder()
+            .timeout(REMOTE_SIGNER_TIMEOUT)
+            .build()
+            .context("Error creating remote signer client")?;
+        let PubkeyResponse { pubkey } = client
+            .get(format!("{url}/pubkey"))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Error fetching identity from remote signer {url}"))?
+            .json()
+            .await
+            .context("Invalid pubkey response from remote signer")?;
+        let pubkey = Pubkey::from_str(&pubkey).context("Invalid pubkey from remote signer")?;
+
+        Ok(Self {
+            url,
+            pubkey,
+            client,
+        })
+    }
+
+    async fn request_signature(&self, message: &[u8]) -> anyhow::Result<Signature> {
+        let SignResponse { signature } = self
+            .client
+            .post(format!("{}/sign", self.url))
+            .json(&SignRequest {
+                message: base64::engine::general_purpose::STANDARD.encode(message),
+            })
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .context("Error requesting signature from remote signer")?
+            .json()
+            .await
+            .context("Invalid sign response from remote signer")?;
+        Ok(Signature::from_str(&signature)?)
+    }
+}
+
+impl IdentitySigner for RemoteSigner {
+    fn pubkey(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    fn sign_message(&self, message: &[u8]) -> anyhow::Result<Signature> {
+        // block_in_place panics outside of the multi threaded runtime
+        let handle = Handle::try_current().context("Remote signing outside of a tokio runtime")?;
+        if handle.runtime_flavor() != RuntimeFlavor::MultiThread {
+            bail!("Remote signing requires the multi threaded tokio runtime");
+        }
+        let signature =
+            tokio::task::block_in_place(|| handle.block_on(self.request_signature(message)))?;
+
+        if !signature.verify(self.pubkey.as_ref(), message) {
+            bail!(
+                "Remote signer returned a signature not matching {}",
+                self.pubkey
+            );
+        }
+        Ok(signature)
+    }
+}
+
+/// Client certificate of the identity presented to the tpus and the quic forward proxy
+pub struct QuicClientIdentity {
+    certified_key: Arc<CertifiedKey>,
+}
+
+impl QuicClientIdentity {
+    /// self signed certificate of the identity, same as the one solana clients present
+    pub fn new(identity: Arc<dyn IdentitySigner>) -> anyhow::Result<Self> {
+        let signing_key = Arc::new(IdentitySigningKey { identity });
+
+        let mut cert_params = CertificateParams::default();
+        cert_params.subject_alt_names = vec![SanType::IpAddress(IpAddr::V4(Ipv4Addr::UNSPECIFIED))];
+        cert_params.alg = &rcgen::PKCS_ED25519;
+        cert_params.key_pair = Some(rcgen::KeyPair::from_remote(Box::new(
+            IdentityRemoteKeyPair::new(signing_key.clone()),
+        ))?);
+        cert_params.distinguished_name = DistinguishedName::new();
+        cert_params
+            .distinguished_name
+            .push(DnType::CommonName, "Solana node");
+        let certificate = rcgen::Certificate::from_params(cert_params)?
+            .serialize_der()
+            .context("Failed to sign QUIC client certificate")?;
+
+        Ok(Self {
+            certified_key: Arc::new(CertifiedKey::new(
+                vec![rustls::Certificate(certificate)],
+                signing_key,
+            )),
+        })
+    }
+
+    pub fn certificate(&self) -> &rustls::Certificate {
+        &self.certified_key.cert[0]
+    }
+}
+
+impl ResolvesClientCert for QuicClientIdentity {
+    fn resolve(
+        &self,
+        _acceptable_issuers: &[&[u8]],
+        sigschemes: &[SignatureScheme],
+    ) -> Option<Arc<CertifiedKey>> {
+        sigschemes
+            .contains(&SignatureScheme::ED25519)
+            .then(|| self.certified_key.clone())
+    }
+
+    fn has_certs(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone)]
+struct IdentitySigningKey {
+    identity: Arc<dyn IdentitySigner>,
+}
+
+impl SigningKey for IdentitySigningKey {
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn rustls::sign::Signer>> {
+        offered
+            .contains(&SignatureScheme::ED25519)
+            .then(|| Box::new(self.clone()) as Box<dyn rustls::sign::Signer>)
+    }
+
+    fn algorithm(&self) -> SignatureAlgorithm {
+        SignatureAlgorithm::ED25519
+    }
+}
+
+impl rustls::sign::Signer for IdentitySigningKey {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, rustls::Error> {
+        self.identity
+            .sign_message(message)
+            .map(|signature| signature.as_ref().to_vec())
+            .map_err(|e| rustls::Error::General(format!("identity signing failed {e:?}")))
+    }
+
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::ED25519
+    }
+}
+
+/// lets rcgen sign the certificate with the identity
+struct IdentityRemoteKeyPair {
+    signing_key: Arc<IdentitySigningKey>,
+    public_key: [u8; 32],
+}
+
+impl IdentityRemoteKeyPair {
+    fn new(signing_key: Arc<IdentitySigningKey>) -> Self {
+        let public_key = signing_key.identity.pubkey().to_bytes();
+        Self {
+            signing_key,
+            public_key,
+        }
+    }
+}
+
+impl RemoteKeyPair for IdentityRemoteKeyPair {
+    fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, rcgen::RcgenError> {
+        rustls::sign::Signer::sign(self.signing_key.as_ref(), msg)
+            .map_err(|_| rcgen::RcgenError::RemoteKeyError)
+    }
+
+    fn algorithm(&self) -> &'static rcgen::SignatureAlgorithm {
+        &rcgen::PKCS_ED25519
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_streamer::tls_certificates::get_pubkey_from_tls_certificate;
+
+    #[test]
+    fn certificate_carries_the_identity() {
+        let identity = Arc::new(Keypair::new());
+        let client_identity = QuicClientIdentity::new(identity.clone()).unwrap();
+
+        assert_eq!(
+            get_pubkey_from_tls_certificate(client_identity.certificate()),
+            Some(Signer::pubkey(identity.as_ref()))
+        );
+    }
+}
@@ -1,5 +1,7 @@
 pub mod commitment_utils;
+pub mod identity_signer;
 pub mod keypair_loader;
+pub mod prometheus_sync;
 pub mod quic_connection;
 pub mod quic_connection_utils;
 pub mod solana_utils;
@@ -7,5 +9,6 @@ pub mod stores;
 pub mod structures;
 pub mod traits;
 pub mod types;
+pub mod udp_socket;
 
 pub type AnyhowJoinHandle = tokio::task::JoinHandle<anyhow::Result<()>>;
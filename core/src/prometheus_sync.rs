@@ -1,8 +1,8 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
+use crate::AnyhowJoinHandle;
 use log::error;
 use prometheus::{Encoder, TextEncoder};
-use solana_lite_rpc_core::AnyhowJoinHandle;
 use tokio::{
     io::AsyncWriteExt,
     net::{TcpListener, TcpStream, ToSocketAddrs},
@@ -42,16 +42,25 @@ impl PrometheusSync {
     pub fn sync(addr: impl ToSocketAddrs + Send + 'static) -> AnyhowJoinHandle {
         tokio::spawn(async move {
             let listener = TcpListener::bind(addr).await?;
+            Self::accept_loop(&listener).await
+        })
+    }
 
-            loop {
-                let Ok((mut stream, _addr)) = listener.accept().await else {
-                    error!("Error accepting prometheus stream");
-                    tokio::time::sleep(Duration::from_millis(1)).await;
-                    continue;
-                };
+    /// serves the metrics on a listener bound beforehand, so a bind error is reported once
+    /// by the caller and a restarted server keeps the port
+    pub fn serve(listener: Arc<TcpListener>) -> AnyhowJoinHandle {
+        tokio::spawn(async move { Self::accept_loop(&listener).await })
+    }
 
-                let _ = Self::handle_stream(&mut stream).await;
-            }
-        })
+    async fn accept_loop(listener: &TcpListener) -> anyhow::Result<()> {
+        loop {
+            let Ok((mut stream, _addr)) = listener.accept().await else {
+                error!("Error accepting prometheus stream");
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                continue;
+            };
+
+            let _ = Self::handle_stream(&mut stream).await;
+        }
     }
 }
@@ -103,12 +103,12 @@ impl QuicConnection {
         }
     }
 
-    pub async fn send_transaction(&self, tx: Vec<u8>) {
+    /// returns false if the transaction could not be written to the tpu
+    pub async fn send_transaction(&self, tx: Vec<u8>) -> bool {
         let connection_retry_count = self.connection_params.connection_retry_count;
         for _ in 0..connection_retry_count {
             if self.exit_signal.load(Ordering::Relaxed) {
-                // return
-                return;
+                return false;
             }
 
             let mut do_retry = false;
@@ -132,7 +132,7 @@ impl QuicConnection {
                         .await
                         {
                             Ok(()) => {
-                                // do nothing
+                                return true;
                             }
                             Err(QuicConnectionError::ConnectionError { retry }) => {
                                 do_retry = retry;
@@ -165,6 +165,7 @@ impl QuicConnection {
                 break;
             }
         }
+        false
     }
 
     pub fn get_timeout_count(&self) -> u64 {
@@ -245,6 +246,16 @@ impl QuicConnectionPool {
         Ok(PooledConnection { connection, permit })
     }
 
+    /// connects all the connections of the pool ahead of the first transaction
+    pub async fn warm_up(&self) {
+        futures::future::join_all(
+            self.connections
+                .iter()
+                .map(|connection| connection.get_connection()),
+        )
+        .await;
+    }
+
     pub fn len(&self) -> usize {
         self.connections.len()
     }
@@ -1,11 +1,13 @@
+use crate::{identity_signer::QuicClientIdentity, udp_socket::bind_client_socket};
 use log::{trace, warn};
 use quinn::{
     ClientConfig, Connection, ConnectionError, Endpoint, EndpointConfig, IdleTimeout, SendStream,
     TokioRuntime, TransportConfig,
 };
 use solana_sdk::pubkey::Pubkey;
+use solana_streamer::tls_certificates::get_pubkey_from_tls_certificate;
 use std::{
-    net::{IpAddr, Ipv4Addr, SocketAddr},
+    net::SocketAddr,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -35,12 +37,9 @@ pub struct QuicConnectionParameters {
 pub struct QuicConnectionUtils {}
 
 impl QuicConnectionUtils {
-    pub fn create_endpoint(certificate: rustls::Certificate, key: rustls::PrivateKey) -> Endpoint {
+    pub fn create_endpoint(client_identity: Arc<QuicClientIdentity>) -> Endpoint {
         let mut endpoint = {
-            let client_socket =
-                solana_net_utils::bind_in_range(IpAddr::V4(Ipv4Addr::UNSPECIFIED), (8000, 10000))
-                    .expect("create_endpoint bind_in_range")
-                    .1;
+            let client_socket = bind_client_socket().expect("create_endpoint bind_client_socket");
             let config = EndpointConfig::default();
             quinn::Endpoint::new(config, None, client_socket, TokioRuntime)
                 .expect("create_endpoint quinn::Endpoint::new")
@@ -49,8 +48,7 @@ impl QuicConnectionUtils {
         let mut crypto = rustls::ClientConfig::builder()
             .with_safe_defaults()
             .with_custom_certificate_verifier(SkipServerVerification::new())
-            .with_single_cert(vec![certificate], key)
-            .expect("Failed to set QUIC client certificates");
+            .with_client_cert_resolver(client_identity);
 
         crypto.enable_early_data = true;
         crypto.alpn_protocols = vec![ALPN_TPU_PROTOCOL_ID.to_vec()];
@@ -218,6 +216,43 @@ impl rustls::client::ServerCertVerifier for SkipServerVerification {
     }
 }
 
+/// Accepts only the self signed certificate of `expected_identity`, as presented by solana
+/// nodes and the quic forward proxy
+pub struct PinnedServerVerification {
+    expected_identity: Pubkey,
+}
+
+impl PinnedServerVerification {
+    pub fn new(expected_identity: Pubkey) -> Arc<Self> {
+        Arc::new(Self { expected_identity })
+    }
+}
+
+impl rustls::client::ServerCertVerifier for PinnedServerVerification {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: std::time::SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        match get_pubkey_from_tls_certificate(end_entity) {
+            Some(identity) if identity == self.expected_identity => {
+                Ok(rustls::client::ServerCertVerified::assertion())
+            }
+            Some(identity) => Err(rustls::Error::General(format!(
+                "server identity {identity} is not the expected {}",
+                self.expected_identity
+            ))),
+            None => Err(rustls::Error::InvalidCertificateData(
+                "no ed25519 identity in the server certificate".to_string(),
+            )),
+        }
+    }
+}
+
 // connection for sending proxy request: FrameStats {
 // ACK: 2, CONNECTION_CLOSE: 0, CRYPTO: 3, DATA_BLOCKED: 0, DATAGRAM: 0, HANDSHAKE_DONE: 1,
 // MAX_DATA: 0, MAX_STREAM_DATA: 1, MAX_STREAMS_BIDI: 0, MAX_STREAMS_UNI: 0, NEW_CONNECTION_ID: 4,
@@ -11,6 +11,9 @@ use tokio::sync::RwLock;
 
 use crate::structures::produced_block::ProducedBlock;
 
+// base58 encoded hashes are at most 44 characters long
+const BLOCKHASH_STRING_MAX_LEN: usize = 44;
+
 #[derive(Clone, Debug)]
 pub struct BlockInformation {
     pub slot: u64,
@@ -159,6 +162,12 @@ impl BlockInformationStore {
         self.blocks.len()
     }
 
+    /// rough estimate of the memory held by the store, blockhashes are stored twice (key and value)
+    pub fn estimated_size_in_bytes(&self) -> usize {
+        self.blocks.len()
+            * (std::mem::size_of::<(String, BlockInformation)>() + 2 * BLOCKHASH_STRING_MAX_LEN)
+    }
+
     pub async fn is_blockhash_valid(
         &self,
         blockhash: &String,
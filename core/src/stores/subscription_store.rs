@@ -7,6 +7,9 @@ use solana_sdk::{
 use std::{sync::Arc, time::Duration};
 use tokio::time::Instant;
 
+// base58 encoded signatures are at most 88 characters long
+const SIGNATURE_STRING_MAX_LEN: usize = 88;
+
 #[derive(Clone, Default)]
 pub struct SubscriptionStore {
     pub signature_subscribers:
@@ -71,4 +74,11 @@ impl SubscriptionStore {
     pub fn number_of_subscribers(&self) -> usize {
         self.signature_subscribers.len()
     }
+
+    /// rough estimate of the memory held by the store, buffers owned by the sinks are not accounted
+    pub fn estimated_size_in_bytes(&self) -> usize {
+        let entry_size = std::mem::size_of::<(String, CommitmentConfig)>()
+            + std::mem::size_of::<(SubscptionHanderSink, Instant)>();
+        self.signature_subscribers.len() * (entry_size + SIGNATURE_STRING_MAX_LEN)
+    }
 }
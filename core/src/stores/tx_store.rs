@@ -2,6 +2,9 @@ use std::sync::Arc;
 
 use dashmap::DashMap;
 use solana_transaction_status::TransactionStatus;
+
+// base58 encoded signatures are at most 88 characters long
+const SIGNATURE_STRING_MAX_LEN: usize = 88;
 /// Transaction Properties
 
 #[derive(Debug, Clone)]
@@ -46,6 +49,11 @@ impl TxStore {
         self.store.is_empty()
     }
 
+    /// rough estimate of the memory held by the store, map overhead is not accounted
+    pub fn estimated_size_in_bytes(&self) -> usize {
+        self.store.len() * (std::mem::size_of::<(String, TxProps)>() + SIGNATURE_STRING_MAX_LEN)
+    }
+
     pub fn contains_key(&self, signature: &String) -> bool {
         self.store.contains_key(signature)
     }
@@ -0,0 +1,41 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Liveness of a service loop, the loop beats on every iteration and the systemd watchdog
+/// is only pinged while all the heartbeats are recent
+#[derive(Clone)]
+pub struct Heartbeat {
+    name: &'static str,
+    start: Instant,
+    last_beat_ms: Arc<AtomicU64>,
+}
+
+impl Heartbeat {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            start: Instant::now(),
+            last_beat_ms: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn beat(&self) {
+        self.last_beat_ms
+            .store(self.start.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// time since the last beat
+    pub fn elapsed(&self) -> Duration {
+        let last_beat = Duration::from_millis(self.last_beat_ms.load(Ordering::Relaxed));
+        self.start.elapsed().saturating_sub(last_beat)
+    }
+}
@@ -29,18 +29,28 @@ impl Default for IdentityStakesData {
 
 #[derive(Debug, Clone)]
 pub struct IdentityStakes {
-    identity: Pubkey,
+    identity: Arc<RwLock<Pubkey>>,
     stakes_data: Arc<RwLock<IdentityStakesData>>,
 }
 
 impl IdentityStakes {
     pub fn new(identity: Pubkey) -> Self {
         Self {
-            identity,
+            identity: Arc::new(RwLock::new(identity)),
             stakes_data: Arc::new(RwLock::new(IdentityStakesData::default())),
         }
     }
 
+    pub async fn get_identity(&self) -> Pubkey {
+        *self.identity.read().await
+    }
+
+    /// the new identity is unstaked until the next vote accounts update
+    pub async fn set_identity(&self, identity: Pubkey) {
+        *self.identity.write().await = identity;
+        *self.stakes_data.write().await = IdentityStakesData::default();
+    }
+
     pub async fn get_stakes(&self) -> IdentityStakesData {
         *self.stakes_data.read().await
     }
@@ -53,7 +63,8 @@ impl IdentityStakes {
             .map(|x| (x.node_pubkey.clone(), x.activated_stake))
             .collect();
 
-        if let Some(stakes) = map_of_stakes.get(&self.identity.to_string()) {
+        let identity = self.get_identity().await;
+        if let Some(stakes) = map_of_stakes.get(&identity.to_string()) {
             let only_stakes = map_of_stakes.iter().map(|x| *x.1).collect_vec();
             let identity_stakes = IdentityStakesData {
                 peer_type: ConnectionPeerType::Staked,
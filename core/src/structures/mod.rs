@@ -1,10 +1,18 @@
 // this mod will contain all the core structures that are defined for lite-rpc
 
+pub mod heartbeat;
 pub mod identity_stakes;
 pub mod leader_data;
 pub mod notifications;
+pub mod priority_class;
 pub mod produced_block;
+#[cfg(feature = "quic-proxy")]
+pub mod proxy_protocol;
 pub mod proxy_request_format;
 pub mod rotating_queue;
+pub mod runtime_config;
+pub mod send_pacer;
+pub mod shutdown;
 pub mod slot_notification;
+pub mod tps_limiter;
 pub mod transaction_sent_info;
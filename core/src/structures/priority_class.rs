@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+/// Queue a transaction goes through before being forwarded to the tpu, transactions of
+/// the high priority queue are always forwarded first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PriorityClass {
+    High,
+    #[default]
+    BestEffort,
+}
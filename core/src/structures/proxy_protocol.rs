@@ -0,0 +1,260 @@
+//! Versioned framing of the lite-rpc to quic forward proxy protocol
+//!
+//! The version is chosen by ALPN during the TLS handshake: v1 proxies only know
+//! [`ALPN_TPU_FORWARDPROXY_PROTOCOL_ID`] and receive a bare [`TpuForwardingRequest`] per uni
+//! stream. From v2 on, the client opens a bidi control stream, sends [`Frame::Hello`] and the
+//! proxy answers [`Frame::HelloAck`] with the capabilities both sides support. The requests
+//! then go as [`Frame::Forward`] on uni streams and the acks come back on the control stream.
+//! With `BATCHING` several requests share one [`Frame::ForwardBatch`], with `COMPRESSION` the
+//! payload of the forward frames is lz4 compressed, which is flagged in the frame header.
+//! With `LEADER_FANOUT` the client may send [`Frame::ForwardToLeaders`] with the slots to cover
+//! instead of the tpu nodes, the proxy then looks up the leaders itself.
+//!
+//! [`TpuForwardingRequest`]: crate::structures::proxy_request_format::TpuForwardingRequest
+
+use std::ops::{BitAnd, BitOr};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+pub const ALPN_TPU_FORWARDPROXY_PROTOCOL_ID: &[u8] = b"solana-tpu-forward-proxy";
+pub const ALPN_TPU_FORWARDPROXY_PROTOCOL_ID_V2: &[u8] = b"solana-tpu-forward-proxy/2";
+
+/// newest version this build speaks
+pub const PROTOCOL_VERSION: u16 = 2;
+
+const FRAME_MAGIC: [u8; 2] = *b"LP";
+// magic, version, flags, payload length
+const FRAME_HEADER_LEN: usize = 2 + 2 + 1 + 4;
+pub const MAX_FRAME_LEN: usize = 10_000_000;
+/// the payload is lz4 compressed with its uncompressed length prepended
+const FLAG_LZ4: u8 = 1;
+
+/// Optional features of the protocol, unknown bits are ignored so newer peers can add
+/// capabilities without breaking older ones
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    pub const NONE: Self = Self(0);
+    /// several requests coalesced into one frame
+    pub const BATCHING: Self = Self(1);
+    /// lz4 compressed frame payloads
+    pub const COMPRESSION: Self = Self(1 << 1);
+    /// the proxy acks every forward frame on the control stream
+    pub const ACKS: Self = Self(1 << 2);
+    /// the proxy resolves the leaders of a slot range from its own leader schedule
+    pub const LEADER_FANOUT: Self = Self(1 << 3);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// `|` for constants
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for Capabilities {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Frame {
+    /// first frame of the control stream, sent by the client
+    Hello {
+        max_version: u16,
+        capabilities: Capabilities,
+    },
+    /// answer of the proxy to `Hello`
+    HelloAck {
+        version: u16,
+        capabilities: Capabilities,
+    },
+    /// a `TpuForwardingRequest` in the v1 wire format
+    Forward { request_id: u64, request: Vec<u8> },
+    /// several `Forward` requests with consecutive ids, acked one by one
+    ForwardBatch {
+        first_request_id: u64,
+        requests: Vec<Vec<u8>>,
+    },
+    /// raw transactions for the leaders of `first_slot..=last_slot`
+    ForwardToLeaders {
+        request_id: u64,
+        first_slot: u64,
+        last_slot: u64,
+        transactions: Vec<Vec<u8>>,
+    },
+    /// sent by the proxy on the control stream when `ACKS` was negotiated
+    Ack { request_id: u64, accepted: bool },
+}
+
+impl Frame {
+    pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        self.encode_with(false)
+    }
+
+    /// only for peers that negotiated `COMPRESSION`
+    pub fn encode_compressed(&self) -> anyhow::Result<Vec<u8>> {
+        self.encode_with(true)
+    }
+
+    fn encode_with(&self, compress: bool) -> anyhow::Result<Vec<u8>> {
+        let mut payload = bincode::serialize(self).context("serialize proxy frame")?;
+        if payload.len() > MAX_FRAME_LEN {
+            bail!("proxy frame of {} bytes is too large", payload.len());
+        }
+        let mut flags = 0;
+        if compress {
+            payload = lz4_flex::compress_prepend_size(&payload);
+            flags |= FLAG_LZ4;
+        }
+
+        let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+        frame.extend_from_slice(&FRAME_MAGIC);
+        frame.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+        frame.push(flags);
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&payload);
+        Ok(frame)
+    }
+
+    /// decodes the frame at the start of `buf`, returns it with the number of bytes read or
+    /// none if `buf` does not hold a whole frame yet
+    pub fn decode(buf: &[u8]) -> anyhow::Result<Option<(Frame, usize)>> {
+        if buf.len() < FRAME_HEADER_LEN {
+            return Ok(None);
+        }
+        if buf[0..2] != FRAME_MAGIC {
+            bail!("not a proxy frame");
+        }
+        let version = u16::from_le_bytes([buf[2], buf[3]]);
+        if version < 2 {
+            bail!("unsupported proxy frame version {version}");
+        }
+        let flags = buf[4];
+        let len = u32::from_le_bytes([buf[5], buf[6], buf[7], buf[8]]) as usize;
+        if len > MAX_FRAME_LEN {
+            bail!("proxy frame of {len} bytes is too large");
+        }
+        let end = FRAME_HEADER_LEN + len;
+        if buf.len() < end {
+            return Ok(None);
+        }
+
+        let payload = &buf[FRAME_HEADER_LEN..end];
+        let frame = if flags & FLAG_LZ4 != 0 {
+            // checked before decompressing, the prepended length is the allocated size
+            let uncompressed_len = payload
+                .get(..4)
+                .map(|len| u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize)
+                .context("compressed proxy frame without length")?;
+            if uncompressed_len > MAX_FRAME_LEN {
+                bail!("proxy frame of {uncompressed_len} bytes uncompressed is too large");
+            }
+            let payload =
+                lz4_flex::decompress_size_prepended(payload).context("decompress proxy frame")?;
+            bincode::deserialize(&payload)
+        } else {
+            bincode::deserialize(payload)
+        }
+        .context("deserialize proxy frame")?;
+        Ok(Some((frame, end)))
+    }
+}
+
+/// answer of a proxy supporting up to `PROTOCOL_VERSION` and `supported` to `hello`
+pub fn negotiate(hello: &Frame, supported: Capabilities) -> anyhow::Result<Frame> {
+    let Frame::Hello {
+        max_version,
+        capabilities,
+    } = hello
+    else {
+        bail!("expected hello frame, got {hello:?}");
+    };
+    if *max_version < 2 {
+        bail!("client speaks proxy protocol version {max_version}, which has no handshake");
+    }
+    Ok(Frame::HelloAck {
+        version: PROTOCOL_VERSION.min(*max_version),
+        capabilities: *capabilities & supported,
+    })
+}
+
+/// reads the next frame from a stream that may carry several frames, e.g. the control stream
+pub async fn read_frame(
+    recv: &mut quinn::RecvStream,
+    buf: &mut Vec<u8>,
+) -> anyhow::Result<Option<Frame>> {
+    loop {
+        if let Some((frame, len)) = Frame::decode(buf)? {
+            buf.drain(..len);
+            return Ok(Some(frame));
+        }
+        let mut chunk = [0u8; 4096];
+        match recv.read(&mut chunk).await? {
+            Some(read) => buf.extend_from_slice(&chunk[..read]),
+            None if buf.is_empty() => return Ok(None),
+            None => bail!("stream finished in the middle of a proxy frame"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_roundtrip_and_capabilities_are_intersected() {
+        let forward = Frame::Forward {
+            request_id: 7,
+            request: vec![1, 2, 3],
+        };
+        let mut buf = forward.encode().unwrap();
+        let ack = Frame::Ack {
+            request_id: 7,
+            accepted: true,
+        };
+        buf.extend(ack.encode().unwrap());
+
+        let (decoded, len) = Frame::decode(&buf).unwrap().unwrap();
+        assert_eq!(decoded, forward);
+        assert_eq!(Frame::decode(&buf[len..]).unwrap().unwrap().0, ack);
+        assert!(Frame::decode(&buf[..len - 1]).unwrap().is_none());
+        // v1 requests start with their format version 2500
+        assert!(Frame::decode(&[0xc4, 0x09, 0, 0, 0, 0, 0, 0, 0]).is_err());
+
+        let batch = Frame::ForwardBatch {
+            first_request_id: 8,
+            requests: vec![vec![7; 1000], vec![9; 1000]],
+        };
+        let compressed = batch.encode_compressed().unwrap();
+        assert!(compressed.len() < 2000);
+        assert_eq!(Frame::decode(&compressed).unwrap().unwrap().0, batch);
+
+        let hello = Frame::Hello {
+            max_version: 3,
+            capabilities: Capabilities::ACKS | Capabilities::COMPRESSION,
+        };
+        assert_eq!(
+            negotiate(&hello, Capabilities::ACKS | Capabilities::BATCHING).unwrap(),
+            Frame::HelloAck {
+                version: PROTOCOL_VERSION,
+                capabilities: Capabilities::ACKS,
+            }
+        );
+    }
+}
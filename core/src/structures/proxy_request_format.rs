@@ -21,6 +21,10 @@ impl TxData {
     pub fn new(sig: String, tx_raw: Vec<u8>) -> Self {
         TxData(Signature::from_str(sig.as_str()).unwrap(), tx_raw)
     }
+
+    pub fn transaction_bytes(&self) -> &[u8] {
+        &self.1
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
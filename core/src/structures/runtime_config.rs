@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+/// Settings which can be changed while lite-rpc is running, services read the latest
+/// value from the watch channel every time they use it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    /// number of upcoming leader slots transactions are sent to
+    pub fanout_slots: u64,
+    /// default number of replays of a transaction when not given by the client
+    pub max_retries: usize,
+    /// delay before the first replay, the n-th replay happens after n * retry_after
+    pub retry_after: Duration,
+    /// max number of transactions forwarded to the tpu per second, unlimited when none
+    pub max_send_tps: Option<u64>,
+}
+
+pub type RuntimeConfigReceiver = watch::Receiver<RuntimeConfig>;
+pub type RuntimeConfigSender = watch::Sender<RuntimeConfig>;
+
+impl RuntimeConfig {
+    /// receiver of a config which never changes
+    pub fn fixed(self) -> RuntimeConfigReceiver {
+        watch::channel(self).1
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Spaces out sends to stay at a rate that can change between calls
+pub struct SendPacer {
+    state: Mutex<PacerState>,
+}
+
+struct PacerState {
+    next_send_at: Instant,
+    // nanoseconds owed by the previous intervals, so that `max_tps` sends take exactly a second
+    carried_nanos: u64,
+}
+
+impl Default for SendPacer {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(PacerState {
+                next_send_at: Instant::now(),
+                carried_nanos: 0,
+            }),
+        }
+    }
+}
+
+impl SendPacer {
+    /// waits for the next send at `max_tps` transactions per second
+    pub async fn wait(&self, max_tps: u64) {
+        let send_at = self.reserve(max_tps, Instant::now());
+        tokio::time::sleep_until(send_at).await;
+    }
+
+    /// time of the next send, unused time while idle is not saved up for a burst
+    fn reserve(&self, max_tps: u64, now: Instant) -> Instant {
+        let max_tps = max_tps.max(1);
+        let mut state = self.state.lock().unwrap();
+        if state.next_send_at < now {
+            state.next_send_at = now;
+            state.carried_nanos = 0;
+        }
+        let send_at = state.next_send_at;
+
+        let carried_nanos = state.carried_nanos + NANOS_PER_SEC % max_tps;
+        let interval_nanos = NANOS_PER_SEC / max_tps + carried_nanos / max_tps;
+        state.carried_nanos = carried_nanos % max_tps;
+        state.next_send_at = send_at + Duration::from_nanos(interval_nanos);
+        send_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_pacer_sends_max_tps_per_second() {
+        for max_tps in [1, 3, 25, 30, 41, 7_000] {
+            let send_pacer = SendPacer::default();
+            let start = Instant::now();
+            let sends = (0..=max_tps)
+                .map(|_| send_pacer.reserve(max_tps, start))
+                .collect::<Vec<_>>();
+            assert!(sends[..max_tps as usize]
+                .iter()
+                .all(|send_at| *send_at < start + Duration::from_secs(1)));
+            assert_eq!(sends[max_tps as usize], start + Duration::from_secs(1));
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::future::Future;
+
+use tokio::{sync::watch, time::Instant};
+
+/// Stages of the graceful shutdown in the order they are reached, every service stops
+/// once the stage it belongs to is reached and the next stage starts when it is done
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ShutdownStage {
+    Running,
+    /// json rpc and websocket servers stop accepting requests
+    StopRpc,
+    /// queued transactions are forwarded to the tpu, then the quic connections finish
+    /// the streams in flight, pending replays are dropped
+    DrainSendQueue,
+    /// notifications buffered for postgres are written
+    FlushNotifications,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownState {
+    pub stage: ShutdownStage,
+    /// when the whole shutdown has to be done, set once the shutdown started
+    pub deadline: Option<Instant>,
+}
+
+impl Default for ShutdownState {
+    fn default() -> Self {
+        Self {
+            stage: ShutdownStage::Running,
+            deadline: None,
+        }
+    }
+}
+
+pub type ShutdownReceiver = watch::Receiver<ShutdownState>;
+pub type ShutdownSender = watch::Sender<ShutdownState>;
+
+pub fn shutdown_stage_reached(shutdown: &ShutdownReceiver, stage: ShutdownStage) -> bool {
+    shutdown.borrow().stage >= stage
+}
+
+/// deadline of the shutdown once started, services bound their remaining work by it
+pub fn shutdown_deadline(shutdown: &ShutdownReceiver) -> Option<Instant> {
+    shutdown.borrow().deadline
+}
+
+/// resolves once the shutdown reached `stage`, never resolves if the sender is dropped before
+pub async fn wait_for_shutdown_stage(mut shutdown: ShutdownReceiver, stage: ShutdownStage) {
+    while !shutdown_stage_reached(&shutdown, stage) {
+        if shutdown.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// output of `future` if it completes before `deadline`, waits without bound if there is none
+pub async fn until_deadline<F: Future>(deadline: Option<Instant>, future: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
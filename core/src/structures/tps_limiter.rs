@@ -0,0 +1,67 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// at most `max_tps` transactions per one second window
+pub struct TpsLimiter {
+    max_tps: u64,
+    window: Mutex<(Instant, u64)>,
+}
+
+impl TpsLimiter {
+    pub fn new(max_tps: u64) -> Self {
+        Self {
+            max_tps,
+            window: Mutex::new((Instant::now(), 0)),
+        }
+    }
+
+    /// counts `count` transactions against the window, none of them when it would be exceeded
+    pub fn try_acquire(&self, count: u64) -> bool {
+        let mut window = self.window.lock().unwrap();
+        let (window_start, window_count) = &mut *window;
+        if window_start.elapsed() >= Duration::from_secs(1) {
+            *window_start = Instant::now();
+            *window_count = 0;
+        }
+        if *window_count + count > self.max_tps {
+            return false;
+        }
+        *window_count += count;
+        true
+    }
+
+    /// counts up to `count` transactions against the window, returns how many fit in it
+    pub fn acquire_up_to(&self, count: u64) -> u64 {
+        let mut window = self.window.lock().unwrap();
+        let (window_start, window_count) = &mut *window;
+        if window_start.elapsed() >= Duration::from_secs(1) {
+            *window_start = Instant::now();
+            *window_count = 0;
+        }
+        let acquired = count.min(self.max_tps.saturating_sub(*window_count));
+        *window_count += acquired;
+        acquired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tps_limiter_limits_each_window() {
+        let tps_limiter = TpsLimiter::new(3);
+        assert!(tps_limiter.try_acquire(1));
+        assert!(!tps_limiter.try_acquire(3));
+        assert!(tps_limiter.try_acquire(2));
+        assert!(!tps_limiter.try_acquire(1));
+    }
+
+    #[test]
+    fn tps_limiter_acquires_what_is_left_of_the_window() {
+        let tps_limiter = TpsLimiter::new(300);
+        assert_eq!(tps_limiter.acquire_up_to(256), 256);
+        assert_eq!(tps_limiter.acquire_up_to(256), 44);
+        assert_eq!(tps_limiter.acquire_up_to(1), 0);
+    }
+}
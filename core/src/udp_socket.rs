@@ -0,0 +1,59 @@
+//! UDP sockets of the quic endpoints
+//!
+//! Larger socket buffers avoid packet drops when sending bursts of transactions. The granted
+//! sizes depend on the platform: linux caps the buffers to `net.core.rmem_max` /
+//! `net.core.wmem_max`, macOS and windows have their own limits. When the buffers are smaller
+//! a warning is logged once and the socket is used as is.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    sync::Once,
+};
+
+use anyhow::Context;
+use log::warn;
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+
+/// requested size of the send and receive buffers
+pub const QUIC_SOCKET_BUFFER_SIZE: usize = 8 * 1024 * 1024;
+
+/// local port range of the client sockets
+pub const CLIENT_PORT_RANGE: (u16, u16) = (8000, 10000);
+
+static REDUCED_BUFFERS: Once = Once::new();
+
+/// client socket of a quic endpoint
+pub fn bind_client_socket() -> anyhow::Result<UdpSocket> {
+    let (_, socket) =
+        solana_net_utils::bind_in_range(IpAddr::V4(Ipv4Addr::UNSPECIFIED), CLIENT_PORT_RANGE)
+            .context("Error binding a client socket")?;
+    set_buffer_sizes(SockRef::from(&socket));
+    Ok(socket)
+}
+
+/// listening socket of a quic server
+pub fn bind_server_socket(addr: SocketAddr) -> anyhow::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    set_buffer_sizes(SockRef::from(&socket));
+    socket
+        .bind(&addr.into())
+        .with_context(|| format!("Error binding {addr}"))?;
+    Ok(socket.into())
+}
+
+fn set_buffer_sizes(socket: SockRef) {
+    // errors are ignored, the granted sizes are checked below
+    let _ = socket.set_recv_buffer_size(QUIC_SOCKET_BUFFER_SIZE);
+    let _ = socket.set_send_buffer_size(QUIC_SOCKET_BUFFER_SIZE);
+
+    let recv_buffer_size = socket.recv_buffer_size().unwrap_or_default();
+    let send_buffer_size = socket.send_buffer_size().unwrap_or_default();
+    if recv_buffer_size < QUIC_SOCKET_BUFFER_SIZE || send_buffer_size < QUIC_SOCKET_BUFFER_SIZE {
+        REDUCED_BUFFERS.call_once(|| {
+            warn!(
+                "UDP socket buffers are {recv_buffer_size} bytes (receive) and {send_buffer_size} bytes (send) instead of {QUIC_SOCKET_BUFFER_SIZE}, \
+                 packets may be dropped under load. On linux raise net.core.rmem_max and net.core.wmem_max"
+            )
+        });
+    }
+}
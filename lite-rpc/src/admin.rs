@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use jsonrpsee::{proc_macros::rpc, server::ServerBuilder};
+use log::info;
+use solana_lite_rpc_core::{
+    identity_signer::{IdentitySigner, RemoteSigner},
+    AnyhowJoinHandle,
+};
+use solana_lite_rpc_services::tpu_utils::tpu_service::TpuService;
+use solana_sdk::signature::read_keypair_file;
+
+use crate::{config_reloader::ConfigReloader, rpc::Result};
+
+/// Operations for the operators of the node, served on `[admin] addr`
+#[rpc(server)]
+pub trait AdminRpc {
+    /// Replaces the identity used towards the tpus, `identity` is either the path of a
+    /// keypair file or the http(s) url of a remote signer. New connections use the new
+    /// identity while the current ones drain. Returns the new identity pubkey.
+    #[method(name = "rotateIdentity")]
+    async fn rotate_identity(&self, identity: String) -> Result<String>;
+
+    /// Same as SIGHUP
+    #[method(name = "reloadConfig")]
+    async fn reload_config(&self) -> Result<()>;
+}
+
+pub struct LiteRpcAdmin {
+    tpu_service: TpuService,
+    config_reloader: Arc<ConfigReloader>,
+}
+
+impl LiteRpcAdmin {
+    pub fn new(tpu_service: TpuService, config_reloader: Arc<ConfigReloader>) -> Self {
+        Self {
+            tpu_service,
+            config_reloader,
+        }
+    }
+
+    async fn load_identity(identity: String) -> anyhow::Result<Arc<dyn IdentitySigner>> {
+        if identity.starts_with("http://") || identity.starts_with("https://") {
+            return Ok(Arc::new(RemoteSigner::connect(identity).await?));
+        }
+
+        let keypair = read_keypair_file(&identity)
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| format!("Error reading identity keypair {identity}"))?;
+        Ok(Arc::new(keypair))
+    }
+
+    /// serves until the server stops
+    pub fn start(self, addr: String) -> AnyhowJoinHandle {
+        tokio::spawn(async move {
+            let server_handle = ServerBuilder::default()
+                .http_only()
+                .build(addr.as_str())
+                .await?
+                .start(self.into_rpc())?;
+
+            info!("Admin server started at {addr:?}");
+            server_handle.stopped().await;
+            anyhow::bail!("Admin server stopped")
+        })
+    }
+}
+
+#[jsonrpsee::core::async_trait]
+impl AdminRpcServer for LiteRpcAdmin {
+    async fn rotate_identity(&self, identity: String) -> Result<String> {
+        let rotate = async {
+            let identity = Self::load_identity(identity).await?;
+            let pubkey = identity.pubkey();
+            self.tpu_service.rotate_identity(identity).await?;
+            anyhow::Ok(pubkey)
+        };
+
+        match rotate.await {
+            Ok(pubkey) => Ok(pubkey.to_string()),
+            Err(e) => Err(jsonrpsee::core::Error::Custom(format!("{e:?}"))),
+        }
+    }
+
+    async fn reload_config(&self) -> Result<()> {
+        info!("Reloading config on admin request");
+        self.config_reloader
+            .reload()
+            .map(|_| ())
+            .map_err(|e| jsonrpsee::core::Error::Custom(format!("{e:?}")))
+    }
+}
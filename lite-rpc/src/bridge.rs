@@ -2,6 +2,7 @@ use crate::{
     configs::{IsBlockHashValidConfig, SendTransactionConfig},
     jsonrpsee_subscrption_handler_sink::JsonRpseeSubscriptionHandlerSink,
     rpc::LiteRpcServer,
+    tenants::{TenantLayer, Tenants},
 };
 
 use solana_lite_rpc_services::{
@@ -9,11 +10,17 @@ use solana_lite_rpc_services::{
 };
 
 use anyhow::Context;
-use jsonrpsee::{core::SubscriptionResult, server::ServerBuilder, PendingSubscriptionSink};
+use jsonrpsee::{
+    core::SubscriptionResult,
+    server::ServerBuilder,
+    types::{error::ErrorCode, ErrorObject},
+    PendingSubscriptionSink,
+};
 use log::info;
 use prometheus::{opts, register_int_counter, IntCounter};
 use solana_lite_rpc_core::{
     stores::{block_information_store::BlockInformation, data_cache::DataCache, tx_store::TxProps},
+    structures::shutdown::{wait_for_shutdown_stage, ShutdownReceiver, ShutdownStage},
     AnyhowJoinHandle,
 };
 use solana_lite_rpc_history::history::History;
@@ -54,6 +61,7 @@ pub struct LiteBridge {
     rpc_client: Arc<RpcClient>,
     transaction_service: TransactionService,
     history: History,
+    tenants: Arc<Tenants>,
 }
 
 impl LiteBridge {
@@ -62,46 +70,73 @@ impl LiteBridge {
         data_cache: DataCache,
         transaction_service: TransactionService,
         history: History,
+        tenants: Arc<Tenants>,
     ) -> Self {
         Self {
             rpc_client,
             data_cache,
             transaction_service,
             history,
+            tenants,
         }
     }
 
     /// List for `JsonRpc` requests
+    /// serves until shutdown, then stops accepting requests and returns once the servers are stopped
     pub async fn start<T: ToSocketAddrs + std::fmt::Debug + 'static + Send + Clone>(
         self,
         http_addr: T,
         ws_addr: T,
+        shutdown: ShutdownReceiver,
     ) -> anyhow::Result<()> {
+        let tenant_layer = TenantLayer::new(self.tenants.clone());
+        #[cfg(feature = "websocket")]
+        let ws_tenant_layer = TenantLayer::websocket(self.tenants.clone());
         let rpc = self.into_rpc();
 
-        let ws_server_handle = ServerBuilder::default()
-            .ws_only()
-            .build(ws_addr.clone())
-            .await?
-            .start(rpc.clone())?;
+        #[cfg(feature = "websocket")]
+        let ws_server_handle = Some(
+            ServerBuilder::default()
+                .set_middleware(tower::ServiceBuilder::new().layer(ws_tenant_layer))
+                // a connection refused by jsonrpsee would shift the connection ids the tenant
+                // layer keeps, the subscription quotas of the tenants bound the load instead
+                .max_connections(u32::MAX)
+                .ws_only()
+                .build(ws_addr.clone())
+                .await?
+                .start(rpc.clone())?,
+        );
+        #[cfg(not(feature = "websocket"))]
+        let ws_server_handle: Option<jsonrpsee::server::ServerHandle> = None;
 
         let http_server_handle = ServerBuilder::default()
+            .set_middleware(tower::ServiceBuilder::new().layer(tenant_layer))
             .http_only()
             .build(http_addr.clone())
             .await?
             .start(rpc)?;
 
-        let ws_server: AnyhowJoinHandle = tokio::spawn(async move {
-            log::info!("Websocket Server started at {ws_addr:?}");
-            ws_server_handle.stopped().await;
-            anyhow::bail!("Websocket server stopped");
-        });
+        let ws_server: AnyhowJoinHandle = {
+            let ws_server_handle = ws_server_handle.clone();
+            tokio::spawn(async move {
+                let Some(ws_server_handle) = ws_server_handle else {
+                    log::info!("Built without the websocket feature, {ws_addr:?} is not served");
+                    return std::future::pending().await;
+                };
+                log::info!("Websocket Server started at {ws_addr:?}");
+                ws_server_handle.stopped().await;
+                anyhow::bail!("Websocket server stopped");
+            })
+        };
 
-        let http_server: AnyhowJoinHandle = tokio::spawn(async move {
-            log::info!("HTTP Server started at {http_addr:?}");
-            http_server_handle.stopped().await;
-            anyhow::bail!("HTTP server stopped");
-        });
+        let http_server: AnyhowJoinHandle = {
+            let http_server_handle = http_server_handle.clone();
+            tokio::spawn(async move {
+                log::info!("HTTP Server started at {http_addr:?}");
+                http_server_handle.stopped().await;
+                anyhow::bail!("HTTP server stopped");
+            })
+        };
 
         tokio::select! {
             res = ws_server => {
@@ -110,7 +145,18 @@ impl LiteBridge {
             res = http_server => {
                 anyhow::bail!("HTTP server {res:?}");
             },
+            _ = wait_for_shutdown_stage(shutdown, ShutdownStage::StopRpc) => {}
+        }
+
+        // ignore already stopped errors
+        if let Some(ws_server_handle) = ws_server_handle {
+            let _ = ws_server_handle.stop();
+            ws_server_handle.stopped().await;
         }
+        let _ = http_server_handle.stop();
+        http_server_handle.stopped().await;
+        info!("RPC servers stopped");
+        Ok(())
     }
 }
 
@@ -128,6 +174,14 @@ impl LiteRpcServer for LiteBridge {
             max_retries,
         } = send_transaction_config.unwrap_or_default();
 
+        let tenant = self.tenants.current();
+        if !tenant.try_send_transaction() {
+            return Err(jsonrpsee::core::Error::Custom(format!(
+                "tps quota of tenant {} exceeded",
+                tenant.name
+            )));
+        }
+
         let raw_tx = match encoding.decode(tx) {
             Ok(raw_tx) => raw_tx,
             Err(err) => {
@@ -137,7 +191,7 @@ impl LiteRpcServer for LiteBridge {
 
         match self
             .transaction_service
-            .send_transaction(raw_tx, max_retries)
+            .send_transaction(raw_tx, max_retries, tenant.priority)
             .await
         {
             Ok(sig) => {
@@ -308,9 +362,26 @@ impl LiteRpcServer for LiteBridge {
         commitment_config: CommitmentConfig,
     ) -> SubscriptionResult {
         RPC_SIGNATURE_SUBSCRIBE.inc();
+        let tenant = self
+            .tenants
+            .ws_connection(pending.connection_id())
+            .context("websocket connection too old to be tracked, reconnect");
+        let subscription_permit = match tenant.and_then(|tenant| tenant.try_subscribe()) {
+            Ok(subscription_permit) => subscription_permit,
+            Err(e) => {
+                pending
+                    .reject(ErrorObject::owned(
+                        ErrorCode::ServerIsBusy.code(),
+                        e.to_string(),
+                        None::<()>,
+                    ))
+                    .await;
+                return Ok(());
+            }
+        };
         let sink = pending.accept().await?;
 
-        let jsonrpsee_sink = JsonRpseeSubscriptionHandlerSink::new(sink);
+        let jsonrpsee_sink = JsonRpseeSubscriptionHandlerSink::new(sink, subscription_permit);
         self.data_cache.tx_subs.signature_subscribe(
             signature,
             commitment_config,
@@ -0,0 +1,632 @@
+//! Embedding lite-rpc in another binary
+//!
+//! [`LiteRpcBuilder`] starts the services lite-rpc is made of, with the json rpc servers,
+//! postgres and prometheus being optional, e.g. to only send transactions and track their
+//! confirmation from within another program. The builder lives in this crate rather than in
+//! core since it ties together the cluster endpoints, services and history crates, which all
+//! depend on core.
+//!
+//! An identity held by a remote signer requires the multi threaded tokio runtime, the builder
+//! fails on a current thread runtime.
+
+#[cfg(feature = "quic-proxy")]
+use solana_sdk::pubkey::Pubkey;
+#[cfg(feature = "quic-proxy")]
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    str::FromStr,
+};
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{bail, Context};
+use solana_lite_rpc_cluster_endpoints::{
+    endpoint_stremers::{EndpointSenders, EndpointStreaming},
+    grpc_subscription::spawn_grpc_tasks,
+    json_rpc_leaders_getter::JsonRpcLeaderGetter,
+    json_rpc_subscription::spawn_json_rpc_polling_tasks,
+};
+use solana_lite_rpc_core::{
+    identity_signer::{IdentitySigner, RemoteSigner},
+    keypair_loader::load_identity_keypair,
+    stores::{
+        block_information_store::{BlockInformation, BlockInformationStore},
+        cluster_info_store::ClusterInfo,
+        data_cache::{DataCache, SlotCache},
+        subscription_store::SubscriptionStore,
+        tx_store::TxStore,
+    },
+    structures::{
+        heartbeat::Heartbeat,
+        identity_stakes::IdentityStakes,
+        notifications::NotificationSender,
+        produced_block::ProducedBlock,
+        runtime_config::RuntimeConfigReceiver,
+        shutdown::{
+            wait_for_shutdown_stage, ShutdownReceiver, ShutdownSender, ShutdownStage, ShutdownState,
+        },
+    },
+    types::BlockStream,
+    AnyhowJoinHandle,
+};
+use solana_lite_rpc_history::{
+    block_stores::inmemory_block_store::InmemoryBlockStore, history::History,
+};
+use solana_lite_rpc_services::{
+    clock_skew::ClockSkewMonitor,
+    data_caching_service::DataCachingService,
+    supervisor::{join_group, supervise, RestartPolicy},
+    tpu_utils::{
+        tpu_connection_path::{ProxyBalancePolicy, TpuConnectionPath},
+        tpu_service::{TpuService, TpuServiceConfig},
+    },
+    transaction_replayer::TransactionReplayer,
+    transaction_service::TransactionService,
+    tx_sender::TxSender,
+};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Keypair};
+use tokio::{sync::watch, task::AbortHandle};
+
+use crate::{
+    bridge::LiteBridge,
+    config::{ClockConfig, Config},
+    service_spawner::ServiceSpawner,
+    tenants::Tenants,
+    DEFAULT_MAX_NUMBER_OF_TXS_IN_QUEUE, GRPC_VERSION,
+};
+
+/// json rpc and websocket servers
+pub struct JsonRpcOptions {
+    pub http_addr: String,
+    pub ws_addr: String,
+    pub tenants: Arc<Tenants>,
+    /// number of blocks kept in memory for `getBlock`
+    pub block_store_capacity: usize,
+}
+
+pub struct LiteRpcBuilder {
+    rpc_client: Arc<RpcClient>,
+    identity: Option<Arc<dyn IdentitySigner>>,
+    grpc_addr: Option<String>,
+    tpu_config: TpuServiceConfig,
+    runtime_config: RuntimeConfigReceiver,
+    clock: ClockConfig,
+    notifier: Option<NotificationSender>,
+    enable_postgres: bool,
+    json_rpc: Option<JsonRpcOptions>,
+    prometheus_addr: Option<String>,
+}
+
+impl LiteRpcBuilder {
+    /// sends transactions directly to the tpus with the defaults of the lite-rpc config,
+    /// polling the cluster state from `rpc_client`, without json rpc servers, postgres and prometheus
+    pub fn new(rpc_client: Arc<RpcClient>) -> Self {
+        let Config {
+            tpu, quic, clock, ..
+        } = Config::default();
+        Self {
+            rpc_client,
+            identity: None,
+            grpc_addr: None,
+            tpu_config: TpuServiceConfig {
+                fanout_slots: tpu.fanout_size,
+                maximum_transaction_in_queue: tpu.maximum_transaction_in_queue,
+                quic_connection_params: quic.connection_parameters(),
+                tpu_connection_path: TpuConnectionPath::QuicDirectPath,
+            },
+            runtime_config: Config::default().runtime_config().fixed(),
+            clock,
+            notifier: None,
+            enable_postgres: false,
+            json_rpc: None,
+            prometheus_addr: None,
+        }
+    }
+
+    /// builder with every service enabled in `config`, as started by the lite-rpc binary
+    pub async fn from_config(
+        config: Config,
+        rpc_client: Arc<RpcClient>,
+        runtime_config: RuntimeConfigReceiver,
+    ) -> anyhow::Result<Self> {
+        let Config {
+            source,
+            rpc,
+            tpu,
+            quic,
+            storage,
+            metrics,
+            tenants,
+            clock,
+            ..
+        } = config;
+
+        let identity: Arc<dyn IdentitySigner> = match tpu.identity_signer_url {
+            Some(identity_signer_url) => {
+                Arc::new(RemoteSigner::connect(identity_signer_url).await?)
+            }
+            None => Arc::new(
+                load_identity_keypair(&tpu.identity_keypair)
+                    .await
+                    .unwrap_or_else(Keypair::new),
+            ),
+        };
+
+        Ok(Self {
+            rpc_client,
+            identity: Some(identity),
+            grpc_addr: source.use_grpc.then_some(source.grpc_addr),
+            tpu_config: TpuServiceConfig {
+                fanout_slots: tpu.fanout_size,
+                maximum_transaction_in_queue: tpu.maximum_transaction_in_queue,
+                quic_connection_params: quic.connection_parameters(),
+                tpu_connection_path: configure_tpu_connection_path(
+                    tpu.quic_proxy_addr,
+                    tpu.quic_proxy_identity,
+                    tpu.quic_proxy_policy,
+                    tpu.quic_proxy_leader_fanout,
+                )?,
+            },
+            runtime_config,
+            clock,
+            notifier: None,
+            enable_postgres: storage.enable_postgres,
+            json_rpc: Some(JsonRpcOptions {
+                http_addr: rpc.http_addr,
+                ws_addr: rpc.ws_addr,
+                tenants: Arc::new(Tenants::new(&tenants)),
+                block_store_capacity: storage.block_store_capacity,
+            }),
+            prometheus_addr: Some(metrics.prometheus_addr),
+        })
+    }
+
+    /// identity of the quic connections to the tpus, a new unstaked one when not set
+    pub fn identity(mut self, identity: Arc<dyn IdentitySigner>) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// reads the cluster state from the geyser grpc plugin instead of polling the rpc node
+    pub fn grpc(mut self, grpc_addr: String) -> Self {
+        self.grpc_addr = Some(grpc_addr);
+        self
+    }
+
+    pub fn tpu_config(mut self, tpu_config: TpuServiceConfig) -> Self {
+        self.tpu_config = tpu_config;
+        self
+    }
+
+    /// fanout, retry policy and send rate limit, the services follow the changes sent on the channel
+    pub fn runtime_config(mut self, runtime_config: RuntimeConfigReceiver) -> Self {
+        self.runtime_config = runtime_config;
+        self
+    }
+
+    pub fn clock(mut self, clock: ClockConfig) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// receives the sent transactions, blocks and accounts notifications otherwise written to postgres
+    pub fn notifications(mut self, notifier: NotificationSender) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// writes the notifications to postgres, configured through the `PG_*` env variables
+    pub fn postgres(mut self, enable: bool) -> Self {
+        self.enable_postgres = enable;
+        self
+    }
+
+    pub fn json_rpc(mut self, json_rpc: Option<JsonRpcOptions>) -> Self {
+        self.json_rpc = json_rpc;
+        self
+    }
+
+    pub fn prometheus(mut self, prometheus_addr: Option<String>) -> Self {
+        self.prometheus_addr = prometheus_addr;
+        self
+    }
+
+    pub async fn start(self) -> anyhow::Result<LiteRpc> {
+        let Self {
+            rpc_client,
+            identity,
+            grpc_addr,
+            tpu_config,
+            runtime_config,
+            clock,
+            notifier,
+            enable_postgres,
+            json_rpc,
+            prometheus_addr,
+        } = self;
+        if notifier.is_some() && enable_postgres {
+            bail!("Notifications go either to postgres or to the given channel, not both");
+        }
+        let identity = identity.unwrap_or_else(|| Arc::new(Keypair::new()));
+        let (shutdown_sender, shutdown) = watch::channel(ShutdownState::default());
+        let mut spawned = AbortOnError::default();
+
+        let (endpoint_senders, subscriptions) = EndpointSenders::new();
+        let cluster_endpoints = {
+            let endpoint_senders = endpoint_senders.clone();
+            let rpc_client = rpc_client.clone();
+            supervise("cluster_endpoints", RestartPolicy::default(), move || {
+                let tasks = match &grpc_addr {
+                    Some(grpc_addr) => spawn_grpc_tasks(
+                        rpc_client.clone(),
+                        grpc_addr.clone(),
+                        GRPC_VERSION.to_string(),
+                        &endpoint_senders,
+                    ),
+                    None => {
+                        match spawn_json_rpc_polling_tasks(rpc_client.clone(), &endpoint_senders) {
+                            Ok(tasks) => tasks,
+                            Err(e) => return tokio::spawn(async move { Err(e) }),
+                        }
+                    }
+                };
+                join_group(tasks)
+            })
+        };
+        spawned.track(&cluster_endpoints);
+        let EndpointStreaming {
+            blocks_notifier,
+            cluster_info_notifier,
+            slot_notifier,
+            vote_account_notifier,
+        } = subscriptions;
+        let finalized_block =
+            get_latest_block(blocks_notifier.resubscribe(), CommitmentConfig::finalized()).await;
+
+        let clock_skew_monitor = ClockSkewMonitor::new(Duration::from_secs(clock.max_skew_secs));
+        clock_skew_monitor
+            .check_at_startup(blocks_notifier.resubscribe(), clock.refuse_to_start)
+            .await?;
+        let clock_skew_service = {
+            let endpoint_senders = endpoint_senders.clone();
+            supervise("clock_skew", RestartPolicy::default(), move || {
+                clock_skew_monitor.start(endpoint_senders.blocks_sender.subscribe())
+            })
+        };
+        spawned.track(&clock_skew_service);
+
+        let block_information_store =
+            BlockInformationStore::new(BlockInformation::from_block(&finalized_block));
+        let data_cache = DataCache {
+            block_information_store,
+            cluster_info: ClusterInfo::default(),
+            identity_stakes: IdentityStakes::new(identity.pubkey()),
+            slot_cache: SlotCache::new(finalized_block.slot),
+            tx_subs: SubscriptionStore::default(),
+            txs: TxStore::default(),
+        };
+
+        // to avoid laggin we resubscribe to block notification
+        let mut data_caching_streams = Some(EndpointStreaming {
+            blocks_notifier: blocks_notifier.resubscribe(),
+            slot_notifier: slot_notifier.resubscribe(),
+            cluster_info_notifier,
+            vote_account_notifier,
+        });
+        drop(blocks_notifier);
+        let data_caching_service = {
+            let data_cache = data_cache.clone();
+            let endpoint_senders = endpoint_senders.clone();
+            supervise("data_caching", RestartPolicy::default(), move || {
+                // restarts only see the notifications sent after they subscribed
+                let streams = data_caching_streams
+                    .take()
+                    .unwrap_or_else(|| endpoint_senders.subscribe());
+                let lata_cache_service = DataCachingService {
+                    data_cache: data_cache.clone(),
+                    clean_duration: Duration::from_secs(120),
+                };
+                join_group(lata_cache_service.listen(
+                    streams.blocks_notifier,
+                    streams.slot_notifier,
+                    streams.cluster_info_notifier,
+                    streams.vote_account_notifier,
+                ))
+            })
+        };
+        spawned.track(&data_caching_service);
+
+        let (notifier, postgres) = if enable_postgres {
+            let (postgres_send, postgres) = start_postgres(shutdown.clone()).await?;
+            (Some(postgres_send), postgres)
+        } else {
+            (
+                notifier,
+                wait_for_stage(shutdown.clone(), ShutdownStage::FlushNotifications),
+            )
+        };
+        spawned.track(&postgres);
+
+        let spawner = ServiceSpawner {
+            prometheus_addr: prometheus_addr.clone().unwrap_or_default(),
+            data_cache: data_cache.clone(),
+        };
+        let support_service = match prometheus_addr {
+            Some(_) => spawner.spawn_support_services().await?,
+            None => tokio::spawn(std::future::pending()),
+        };
+        spawned.track(&support_service);
+        let leader_schedule = Arc::new(JsonRpcLeaderGetter::new(rpc_client.clone(), 1024, 128));
+
+        let tpu_service: TpuService = TpuService::new(
+            tpu_config,
+            identity,
+            leader_schedule,
+            data_cache.clone(),
+            runtime_config.clone(),
+        )
+        .await?;
+        let tx_sender = TxSender::new(data_cache.clone(), tpu_service.clone());
+        let heartbeats = vec![tpu_service.heartbeat(), tx_sender.heartbeat()];
+
+        let tx_replayer = TransactionReplayer::new(tpu_service.clone(), data_cache.txs.clone());
+        let (transaction_service, tx_service) = spawner.spawn_tx_service(
+            tx_sender,
+            tx_replayer,
+            tpu_service.clone(),
+            DEFAULT_MAX_NUMBER_OF_TXS_IN_QUEUE,
+            notifier,
+            runtime_config,
+            slot_notifier.resubscribe(),
+            shutdown.clone(),
+        );
+        drop(slot_notifier);
+
+        let bridge = match json_rpc {
+            Some(json_rpc) => {
+                let history = History {
+                    block_storage: Arc::new(InmemoryBlockStore::new(json_rpc.block_store_capacity)),
+                };
+                tokio::spawn(
+                    LiteBridge::new(
+                        rpc_client,
+                        data_cache.clone(),
+                        transaction_service.clone(),
+                        history,
+                        json_rpc.tenants,
+                    )
+                    .start(json_rpc.http_addr, json_rpc.ws_addr, shutdown),
+                )
+            }
+            None => wait_for_stage(shutdown, ShutdownStage::StopRpc),
+        };
+
+        // nothing fails past the tpu service, the services spawned from here on are kept
+        spawned.disarm();
+        Ok(LiteRpc {
+            data_cache,
+            transaction_service,
+            tpu_service,
+            endpoint_senders,
+            heartbeats,
+            shutdown_sender,
+            supervised: vec![
+                ("cluster_endpoints", cluster_endpoints),
+                ("clock_skew", clock_skew_service),
+                ("data_caching", data_caching_service),
+                ("support", support_service),
+            ],
+            bridge,
+            tx_service,
+            postgres,
+        })
+    }
+}
+
+/// Running lite-rpc services
+pub struct LiteRpc {
+    /// blocks, slots, cluster state and the status of the sent transactions
+    pub data_cache: DataCache,
+    /// sends transactions and replays them until confirmed or expired
+    pub transaction_service: TransactionService,
+    pub tpu_service: TpuService,
+    endpoint_senders: EndpointSenders,
+    heartbeats: Vec<Heartbeat>,
+    shutdown_sender: ShutdownSender,
+    supervised: Vec<(&'static str, AnyhowJoinHandle)>,
+    bridge: AnyhowJoinHandle,
+    tx_service: AnyhowJoinHandle,
+    postgres: AnyhowJoinHandle,
+}
+
+impl LiteRpc {
+    /// new receivers of the blocks, slots, vote accounts and cluster nodes notifications
+    pub fn subscribe(&self) -> EndpointStreaming {
+        self.endpoint_senders.subscribe()
+    }
+
+    /// heartbeats of the tpu service and of the transaction sender
+    pub fn heartbeats(&self) -> Vec<Heartbeat> {
+        self.heartbeats.clone()
+    }
+
+    /// resolves with the error of the first service stopping unexpectedly
+    pub async fn stopped(&mut self) -> anyhow::Error {
+        let supervised_names = self
+            .supervised
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+        tokio::select! {
+            res = &mut self.tx_service => {
+                anyhow::anyhow!("Tx Services {res:?}")
+            }
+            res = &mut self.bridge => {
+                anyhow::anyhow!("Server {res:?}")
+            }
+            res = &mut self.postgres => {
+                anyhow::anyhow!("Postgres service {res:?}")
+            }
+            (res, index, _) = futures::future::select_all(
+                self.supervised.iter_mut().map(|(_, service)| service),
+            ) => {
+                anyhow::anyhow!("Supervised service {} {res:?}", supervised_names[index])
+            }
+        }
+    }
+
+    /// stops the json rpc servers, drains the send queue then flushes the notifications,
+    /// fails when this takes longer than `deadline`
+    pub async fn shutdown(self, deadline: Duration) -> anyhow::Result<()> {
+        let stages = [
+            (ShutdownStage::StopRpc, self.bridge),
+            (ShutdownStage::DrainSendQueue, self.tx_service),
+            (ShutdownStage::FlushNotifications, self.postgres),
+        ];
+        let deadline_at = tokio::time::Instant::now() + deadline;
+        let res = tokio::time::timeout_at(
+            deadline_at,
+            shutdown_services(self.shutdown_sender, stages, deadline_at),
+        )
+        .await
+        .with_context(|| format!("Graceful shutdown did not complete within {deadline:?}"))?;
+        self.supervised
+            .iter()
+            .for_each(|(_, service)| service.abort());
+        res
+    }
+}
+
+/// aborts the services spawned by `start` when it returns early with an error, so a failed
+/// start does not leave them running in the background
+#[derive(Default)]
+struct AbortOnError(Vec<AbortHandle>);
+
+impl AbortOnError {
+    fn track(&mut self, service: &AnyhowJoinHandle) {
+        self.0.push(service.abort_handle());
+    }
+
+    fn disarm(mut self) {
+        self.0.clear();
+    }
+}
+
+impl Drop for AbortOnError {
+    fn drop(&mut self) {
+        self.0.iter().for_each(|service| service.abort());
+    }
+}
+
+/// moves through the shutdown stages in order, each stage is done when its service returned
+async fn shutdown_services(
+    shutdown_sender: ShutdownSender,
+    stages: [(ShutdownStage, AnyhowJoinHandle); 3],
+    deadline: tokio::time::Instant,
+) -> anyhow::Result<()> {
+    for (stage, service) in stages {
+        log::info!("Shutdown stage {stage:?}");
+        shutdown_sender.send_replace(ShutdownState {
+            stage,
+            deadline: Some(deadline),
+        });
+        service
+            .await
+            .with_context(|| format!("Service of shutdown stage {stage:?} panicked"))?
+            .with_context(|| format!("Service of shutdown stage {stage:?} failed"))?;
+    }
+    log::info!("Graceful shutdown complete");
+    Ok(())
+}
+
+/// stands for a disabled service in the shutdown sequence
+fn wait_for_stage(shutdown: ShutdownReceiver, stage: ShutdownStage) -> AnyhowJoinHandle {
+    tokio::spawn(async move {
+        wait_for_shutdown_stage(shutdown, stage).await;
+        Ok(())
+    })
+}
+
+async fn get_latest_block(
+    mut block_stream: BlockStream,
+    commitment_config: CommitmentConfig,
+) -> ProducedBlock {
+    while let Ok(block) = block_stream.recv().await {
+        if block.commitment_config == commitment_config {
+            return block;
+        }
+    }
+    panic!("Did  not recv blocks");
+}
+
+#[cfg(feature = "postgres")]
+async fn start_postgres(
+    shutdown: ShutdownReceiver,
+) -> anyhow::Result<(NotificationSender, AnyhowJoinHandle)> {
+    let (postgres_send, postgres_recv) = tokio::sync::mpsc::unbounded_channel();
+
+    let postgres = crate::postgres::Postgres::new()
+        .await?
+        .start(postgres_recv, shutdown);
+
+    Ok((postgres_send, postgres))
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn start_postgres(
+    _shutdown: ShutdownReceiver,
+) -> anyhow::Result<(NotificationSender, AnyhowJoinHandle)> {
+    bail!("Postgres is enabled but lite-rpc was built without the postgres feature")
+}
+
+pub(crate) fn configure_tpu_connection_path(
+    quic_proxy_addr: Option<String>,
+    quic_proxy_identity: Option<String>,
+    quic_proxy_policy: ProxyBalancePolicy,
+    quic_proxy_leader_fanout: bool,
+) -> anyhow::Result<TpuConnectionPath> {
+    match quic_proxy_addr {
+        None => Ok(TpuConnectionPath::QuicDirectPath),
+        #[cfg(feature = "quic-proxy")]
+        Some(proxy_addresses) => Ok(TpuConnectionPath::QuicForwardProxyPath {
+            // e.g. "127.0.0.1:11111" or "localhost:11111,localhost:11112"
+            forward_proxy_addresses: proxy_addresses
+                .split(',')
+                .map(|address| parse_host_port(address.trim()))
+                .collect::<anyhow::Result<_>>()?,
+            proxy_identity: quic_proxy_identity
+                .map(|identity| {
+                    Pubkey::from_str(&identity)
+                        .with_context(|| format!("Invalid tpu.quic_proxy_identity {identity}"))
+                })
+                .transpose()?,
+            balance_policy: quic_proxy_policy,
+            leader_fanout: quic_proxy_leader_fanout,
+        }),
+        #[cfg(not(feature = "quic-proxy"))]
+        Some(_) => {
+            let _ = (
+                quic_proxy_identity,
+                quic_proxy_policy,
+                quic_proxy_leader_fanout,
+            );
+            bail!(
+                "tpu.quic_proxy_addr is set but lite-rpc was built without the quic-proxy feature"
+            )
+        }
+    }
+}
+
+#[cfg(feature = "quic-proxy")]
+fn parse_host_port(host_port: &str) -> anyhow::Result<SocketAddr> {
+    let addrs: Vec<_> = host_port
+        .to_socket_addrs()
+        .with_context(|| format!("Unable to resolve host {host_port}"))?
+        .collect();
+    if addrs.is_empty() {
+        bail!("Unable to resolve host: {host_port}");
+    } else if addrs.len() > 1 {
+        bail!("Multiple addresses resolved for host: {host_port}");
+    }
+    Ok(addrs[0])
+}
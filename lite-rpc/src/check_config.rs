@@ -0,0 +1,130 @@
+//! `--check-config`: validates the configuration and the upstreams lite-rpc depends on
+//! without starting any service
+
+use std::net::ToSocketAddrs;
+
+use anyhow::{bail, Context};
+use log::info;
+use solana_lite_rpc_cluster_endpoints::grpc_subscription::get_grpc_version;
+use solana_lite_rpc_core::identity_signer::{IdentitySigner, RemoteSigner};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::signature::{read_keypair_file, Signer};
+
+use crate::{
+    builder::configure_tpu_connection_path, config::Config, config_reloader::ConfigReloader,
+};
+
+/// checks the entries serde can not and connects to every upstream the config points to
+pub async fn check_config(config: &Config) -> anyhow::Result<()> {
+    if config.tpu.fanout_size == 0 {
+        bail!("tpu.fanout_size should be greater than 0");
+    }
+    if config.quic.max_number_of_connections == 0 {
+        bail!("quic.max_number_of_connections should be greater than 0");
+    }
+    ConfigReloader::log_filter(config).context("Invalid log.filter")?;
+
+    let addrs = [
+        ("rpc.http_addr", &config.rpc.http_addr),
+        ("rpc.ws_addr", &config.rpc.ws_addr),
+        ("metrics.prometheus_addr", &config.metrics.prometheus_addr),
+    ]
+    .into_iter()
+    .chain(config.admin.addr.as_ref().map(|addr| ("admin.addr", addr)));
+    for (name, addr) in addrs {
+        addr.to_socket_addrs()
+            .with_context(|| format!("Invalid {name} {addr}"))?;
+    }
+    configure_tpu_connection_path(
+        config.tpu.quic_proxy_addr.clone(),
+        config.tpu.quic_proxy_identity.clone(),
+        config.tpu.quic_proxy_policy,
+        config.tpu.quic_proxy_leader_fanout,
+    )?;
+
+    let rpc_client = RpcClient::new(config.source.rpc_addr.clone());
+    let version = rpc_client
+        .get_version()
+        .await
+        .with_context(|| format!("Error reaching rpc node {}", config.source.rpc_addr))?;
+    info!(
+        "rpc node {} is reachable, version {}",
+        config.source.rpc_addr, version.solana_core
+    );
+
+    if config.source.use_grpc {
+        let version = get_grpc_version(config.source.grpc_addr.clone())
+            .await
+            .with_context(|| format!("Error reaching grpc {}", config.source.grpc_addr))?;
+        info!(
+            "grpc {} is reachable, version {version}",
+            config.source.grpc_addr
+        );
+    }
+
+    if let Some(identity_signer_url) = &config.tpu.identity_signer_url {
+        let signer = RemoteSigner::connect(identity_signer_url.clone()).await?;
+        info!("remote signer holds identity {}", signer.pubkey());
+    } else if !config.tpu.identity_keypair.is_empty() {
+        let keypair = read_keypair_file(&config.tpu.identity_keypair)
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| {
+                format!(
+                    "Error reading identity keypair {}",
+                    config.tpu.identity_keypair
+                )
+            })?;
+        info!("identity {}", keypair.pubkey());
+    }
+
+    if config.storage.enable_postgres {
+        #[cfg(feature = "postgres")]
+        {
+            crate::postgres::PostgresSession::new()
+                .await
+                .context("Error connecting to postgres")?;
+            info!("postgres is reachable");
+        }
+        #[cfg(not(feature = "postgres"))]
+        bail!("storage.enable_postgres is set but lite-rpc was built without the postgres feature");
+    }
+
+    Ok(())
+}
+
+/// the config after defaults, config file, environment and command line are resolved,
+/// as toml with the api keys redacted
+pub fn effective_config(config: &Config) -> anyhow::Result<String> {
+    let mut config = config.clone();
+    for tenant in &mut config.tenants.keys {
+        tenant.api_key = "<redacted>".to_string();
+    }
+    toml::to_string_pretty(&config).context("Error serializing the effective config")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_config_parses_back_without_api_keys() {
+        let config: Config = toml::from_str(
+            r#"
+            [tpu]
+            fanout_size = 20
+
+            [[tenants.keys]]
+            name = "internal"
+            api_key = "secret"
+            "#,
+        )
+        .unwrap();
+
+        let dumped = effective_config(&config).unwrap();
+        assert!(!dumped.contains("secret"));
+
+        let parsed: Config = toml::from_str(&dumped).unwrap();
+        assert_eq!(parsed.tpu.fanout_size, 20);
+        assert_eq!(parsed.tenants.keys[0].name, "internal");
+    }
+}
@@ -1,40 +1,64 @@
-use crate::{
-    DEFAULT_FANOUT_SIZE, DEFAULT_GRPC_ADDR, DEFAULT_RETRY_TIMEOUT, DEFAULT_RPC_ADDR,
-    DEFAULT_WS_ADDR, MAX_RETRIES,
-};
 use clap::Parser;
 
+/// Every option overrides the matching entry of the config file, options read from the
+/// environment are overridden by the ones passed on the command line
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    #[arg(short, long, default_value_t = String::from(DEFAULT_RPC_ADDR))]
-    pub rpc_addr: String,
-    #[arg(short, long, default_value_t = String::from(DEFAULT_WS_ADDR))]
-    pub ws_addr: String,
-    #[arg(short = 'l', long, default_value_t = String::from("[::]:8890"))]
-    pub lite_rpc_http_addr: String,
-    #[arg(short = 's', long, default_value_t = String::from("[::]:8891"))]
-    pub lite_rpc_ws_addr: String,
+    /// toml or yaml config file, see `config.example.toml`
+    #[arg(short = 'c', long, env = "LITE_RPC_CONFIG")]
+    pub config: Option<String>,
+    #[arg(short, long, env = "RPC_URL")]
+    pub rpc_addr: Option<String>,
+    #[arg(short, long, env = "WS_URL")]
+    pub ws_addr: Option<String>,
+    #[arg(short = 'l', long, env)]
+    pub lite_rpc_http_addr: Option<String>,
+    #[arg(short = 's', long, env)]
+    pub lite_rpc_ws_addr: Option<String>,
     /// tpu fanout
-    #[arg(short = 'f', long, default_value_t = DEFAULT_FANOUT_SIZE) ]
-    pub fanout_size: u64,
+    #[arg(short = 'f', long, env)]
+    pub fanout_size: Option<u64>,
     /// enable logging to postgres
     #[arg(short = 'p', long)]
     pub enable_postgres: bool,
     /// enable metrics to prometheus at addr
-    #[arg(short = 'm', long, default_value_t = String::from("[::]:9091"))]
-    pub prometheus_addr: String,
-    #[arg(short = 'k', long, default_value_t = String::new())]
-    pub identity_keypair: String,
-    #[arg(long, default_value_t = MAX_RETRIES)]
-    pub maximum_retries_per_tx: usize,
-    #[arg(long, default_value_t = DEFAULT_RETRY_TIMEOUT)]
-    pub transaction_retry_after_secs: u64,
-    #[arg(long)]
+    #[arg(short = 'm', long, env)]
+    pub prometheus_addr: Option<String>,
+    #[arg(short = 'k', long)]
+    pub identity_keypair: Option<String>,
+    /// url of a remote signer holding the identity, instead of an identity keypair
+    #[arg(long, env, conflicts_with = "identity_keypair")]
+    pub identity_signer_url: Option<String>,
+    #[arg(long, env)]
+    pub maximum_retries_per_tx: Option<usize>,
+    #[arg(long, env)]
+    pub transaction_retry_after_secs: Option<u64>,
+    /// quic forward proxies, comma separated
+    #[arg(long, env)]
     pub quic_proxy_addr: Option<String>,
+    /// identity pubkey the quic forward proxy has to present
+    #[arg(long, env)]
+    pub quic_proxy_identity: Option<String>,
+    /// max number of transactions forwarded to the tpu per second
+    #[arg(long, env)]
+    pub max_send_tps: Option<u64>,
     #[arg(short = 'g', long)]
     pub use_grpc: bool,
     /// grpc address
-    #[arg(long, default_value_t = String::from(DEFAULT_GRPC_ADDR))]
-    pub grpc_addr: String,
+    #[arg(long, env)]
+    pub grpc_addr: Option<String>,
+    /// max time to stop gracefully on SIGTERM or ctrl+c
+    #[arg(long, env)]
+    pub shutdown_deadline_secs: Option<u64>,
+    /// address of the admin json rpc server, disabled when unset
+    #[arg(long, env)]
+    pub admin_addr: Option<String>,
+    /// validate the config and the connectivity to the upstreams, print the effective
+    /// config and exit without starting the services
+    #[arg(long)]
+    pub check_config: bool,
+    /// max skew of the local clock to the cluster before warning
+    #[arg(long, env)]
+    pub max_clock_skew_secs: Option<u64>,
 }
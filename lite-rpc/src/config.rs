@@ -0,0 +1,457 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use solana_lite_rpc_core::{
+    quic_connection_utils::QuicConnectionParameters,
+    structures::{priority_class::PriorityClass, runtime_config::RuntimeConfig},
+};
+
+use solana_lite_rpc_services::tpu_utils::tpu_connection_path::ProxyBalancePolicy;
+
+use crate::{
+    cli::Args, DEFAULT_FANOUT_SIZE, DEFAULT_GRPC_ADDR, DEFAULT_RETRY_TIMEOUT, DEFAULT_RPC_ADDR,
+    DEFAULT_WS_ADDR, MAX_RETRIES,
+};
+
+/// Configuration of all the services of lite-rpc
+///
+/// Values are resolved with the following precedence, highest first:
+/// command line options, environment variables, config file, defaults.
+/// The fanout, retry policy, send rate limit and log filter are reloaded on SIGHUP.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub source: SourceConfig,
+    pub rpc: RpcConfig,
+    pub tpu: TpuConfig,
+    pub quic: QuicConfig,
+    pub storage: StorageConfig,
+    pub metrics: MetricsConfig,
+    pub log: LogConfig,
+    pub shutdown: ShutdownConfig,
+    pub tenants: TenantsConfig,
+    pub admin: AdminConfig,
+    pub clock: ClockConfig,
+}
+
+/// upstream rpc node the cluster state is read from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourceConfig {
+    pub rpc_addr: String,
+    pub ws_addr: String,
+    pub use_grpc: bool,
+    pub grpc_addr: String,
+}
+
+impl Default for SourceConfig {
+    fn default() -> Self {
+        Self {
+            rpc_addr: DEFAULT_RPC_ADDR.to_string(),
+            ws_addr: DEFAULT_WS_ADDR.to_string(),
+            use_grpc: false,
+            grpc_addr: DEFAULT_GRPC_ADDR.to_string(),
+        }
+    }
+}
+
+/// json rpc and websocket servers of lite-rpc
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RpcConfig {
+    pub http_addr: String,
+    pub ws_addr: String,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            http_addr: "[::]:8890".to_string(),
+            ws_addr: "[::]:8891".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TpuConfig {
+    pub fanout_size: u64,
+    /// path to the identity keypair, the `IDENTITY` env variable takes precedence
+    pub identity_keypair: String,
+    /// url of a remote signer or HSM holding the identity, replaces `identity_keypair`
+    pub identity_signer_url: Option<String>,
+    pub maximum_retries_per_tx: usize,
+    pub transaction_retry_after_secs: u64,
+    pub maximum_transaction_in_queue: usize,
+    /// send through the quic forward proxies at these comma separated addresses instead of
+    /// connecting to the tpus
+    pub quic_proxy_addr: Option<String>,
+    /// how the transactions are spread over several proxies
+    pub quic_proxy_policy: ProxyBalancePolicy,
+    /// send every transaction once and let the proxies fan out to the leaders
+    pub quic_proxy_leader_fanout: bool,
+    /// identity pubkey the quic forward proxy has to present, the proxy is not authenticated
+    /// when unset
+    pub quic_proxy_identity: Option<String>,
+    /// max number of transactions forwarded to the tpu per second, unlimited when unset
+    pub max_send_tps: Option<u64>,
+}
+
+impl Default for TpuConfig {
+    fn default() -> Self {
+        Self {
+            fanout_size: DEFAULT_FANOUT_SIZE,
+            identity_keypair: String::new(),
+            identity_signer_url: None,
+            maximum_retries_per_tx: MAX_RETRIES,
+            transaction_retry_after_secs: DEFAULT_RETRY_TIMEOUT,
+            maximum_transaction_in_queue: 20000,
+            quic_proxy_addr: None,
+            quic_proxy_identity: None,
+            quic_proxy_policy: ProxyBalancePolicy::default(),
+            quic_proxy_leader_fanout: false,
+            max_send_tps: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuicConfig {
+    pub connection_timeout_ms: u64,
+    pub unistream_timeout_ms: u64,
+    pub write_timeout_ms: u64,
+    pub finalize_timeout_ms: u64,
+    pub connection_retry_count: usize,
+    pub max_number_of_connections: usize,
+    pub number_of_transactions_per_unistream: usize,
+}
+
+impl Default for QuicConfig {
+    fn default() -> Self {
+        Self {
+            connection_timeout_ms: 1000,
+            unistream_timeout_ms: 500,
+            write_timeout_ms: 1000,
+            finalize_timeout_ms: 200,
+            connection_retry_count: 10,
+            max_number_of_connections: 8,
+            number_of_transactions_per_unistream: 1,
+        }
+    }
+}
+
+impl QuicConfig {
+    pub fn connection_parameters(&self) -> QuicConnectionParameters {
+        QuicConnectionParameters {
+            connection_timeout: Duration::from_millis(self.connection_timeout_ms),
+            unistream_timeout: Duration::from_millis(self.unistream_timeout_ms),
+            write_timeout: Duration::from_millis(self.write_timeout_ms),
+            finalize_timeout: Duration::from_millis(self.finalize_timeout_ms),
+            connection_retry_count: self.connection_retry_count,
+            max_number_of_connections: self.max_number_of_connections,
+            number_of_transactions_per_unistream: self.number_of_transactions_per_unistream,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// postgres connection is configured through the `PG_*` env variables
+    pub enable_postgres: bool,
+    /// number of blocks kept in memory
+    pub block_store_capacity: usize,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            enable_postgres: false,
+            block_store_capacity: 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub prometheus_addr: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            prometheus_addr: "[::]:9091".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// tracing filter directives (e.g. `info,lite_rpc=debug`), `RUST_LOG` or `info` when unset
+    pub filter: Option<String>,
+}
+
+/// graceful shutdown on SIGTERM or ctrl+c
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// time given to stop the rpc servers, drain the send queue, finish the quic streams in
+    /// flight and flush the postgres notifications before exiting
+    pub deadline_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self { deadline_secs: 10 }
+    }
+}
+
+/// skew of the local clock to the time of the confirmed blocks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClockConfig {
+    /// skew above which a warning is logged
+    pub max_skew_secs: u64,
+    /// fail to start instead of warning when the skew is above `max_skew_secs` at startup
+    pub refuse_to_start: bool,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            max_skew_secs: 10,
+            refuse_to_start: false,
+        }
+    }
+}
+
+/// json rpc server for operators, disabled unless an address is set
+///
+/// It has no authentication so it should only listen on a local or private address.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// e.g. `127.0.0.1:8892`
+    pub addr: Option<String>,
+}
+
+/// clients identified by the api key of their requests, requests without api key are
+/// served as the public tenant unless `require_api_key` is set
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TenantsConfig {
+    pub require_api_key: bool,
+    pub public: TenantLimits,
+    pub keys: Vec<TenantConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TenantLimits {
+    pub priority: PriorityClass,
+    /// max number of transactions sent per second, unlimited when unset
+    pub max_tps: Option<u64>,
+    /// max number of open signature subscriptions, unlimited when unset
+    pub max_subscriptions: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenantConfig {
+    pub name: String,
+    pub api_key: String,
+    #[serde(default)]
+    pub priority: PriorityClass,
+    pub max_tps: Option<u64>,
+    /// max number of open signature subscriptions, unlimited when unset
+    pub max_subscriptions: Option<usize>,
+}
+
+impl Config {
+    /// reads the config file given in `args` if any and overlays the options of `args` on top
+    pub fn load(args: &Args) -> anyhow::Result<Self> {
+        let mut config = match &args.config {
+            Some(config_path) => Self::from_file(Path::new(config_path))?,
+            None => Self::default(),
+        };
+        config.apply_args(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.tpu.identity_signer_url.is_some() && !self.tpu.identity_keypair.is_empty() {
+            bail!("tpu.identity_keypair and tpu.identity_signer_url can not be both set");
+        }
+        let mut api_keys = std::collections::HashSet::new();
+        for tenant in &self.tenants.keys {
+            if !api_keys.insert(&tenant.api_key) {
+                bail!(
+                    "api key of tenant {} is used by another tenant",
+                    tenant.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// the part of the config services follow while running
+    pub fn runtime_config(&self) -> RuntimeConfig {
+        RuntimeConfig {
+            fanout_slots: self.tpu.fanout_size,
+            max_retries: self.tpu.maximum_retries_per_tx,
+            retry_after: Duration::from_secs(self.tpu.transaction_retry_after_secs),
+            max_send_tps: self.tpu.max_send_tps,
+        }
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Error reading config file {path:?}"))?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&contents)
+                .with_context(|| format!("Error parsing config file {path:?}")),
+            Some("yaml" | "yml") => serde_yaml::from_str(&contents)
+                .with_context(|| format!("Error parsing config file {path:?}")),
+            _ => bail!("Config file {path:?} should have a toml, yaml or yml extension"),
+        }
+    }
+
+    fn apply_args(&mut self, args: &Args) {
+        let Args {
+            config: _,
+            rpc_addr,
+            ws_addr,
+            lite_rpc_http_addr,
+            lite_rpc_ws_addr,
+            fanout_size,
+            enable_postgres,
+            prometheus_addr,
+            identity_keypair,
+            identity_signer_url,
+            maximum_retries_per_tx,
+            transaction_retry_after_secs,
+            quic_proxy_addr,
+            quic_proxy_identity,
+            max_send_tps,
+            use_grpc,
+            grpc_addr,
+            shutdown_deadline_secs,
+            admin_addr,
+            check_config: _,
+            max_clock_skew_secs,
+        } = args.clone();
+
+        override_with(&mut self.source.rpc_addr, rpc_addr);
+        override_with(&mut self.source.ws_addr, ws_addr);
+        override_with(&mut self.source.grpc_addr, grpc_addr);
+        // flags can only turn a service on
+        self.source.use_grpc |= use_grpc;
+        override_with(&mut self.rpc.http_addr, lite_rpc_http_addr);
+        override_with(&mut self.rpc.ws_addr, lite_rpc_ws_addr);
+        override_with(&mut self.tpu.fanout_size, fanout_size);
+        // the identity given here replaces the one of the file, whichever kind it is
+        if let Some(identity_keypair) = identity_keypair {
+            self.tpu.identity_keypair = identity_keypair;
+            self.tpu.identity_signer_url = None;
+        }
+        if identity_signer_url.is_some() {
+            self.tpu.identity_signer_url = identity_signer_url;
+            self.tpu.identity_keypair = String::new();
+        }
+        override_with(&mut self.tpu.maximum_retries_per_tx, maximum_retries_per_tx);
+        override_with(
+            &mut self.tpu.transaction_retry_after_secs,
+            transaction_retry_after_secs,
+        );
+        if quic_proxy_addr.is_some() {
+            self.tpu.quic_proxy_addr = quic_proxy_addr;
+        }
+        if quic_proxy_identity.is_some() {
+            self.tpu.quic_proxy_identity = quic_proxy_identity;
+        }
+        if max_send_tps.is_some() {
+            self.tpu.max_send_tps = max_send_tps;
+        }
+        self.storage.enable_postgres |= enable_postgres;
+        override_with(&mut self.metrics.prometheus_addr, prometheus_addr);
+        override_with(&mut self.shutdown.deadline_secs, shutdown_deadline_secs);
+        override_with(&mut self.clock.max_skew_secs, max_clock_skew_secs);
+        if admin_addr.is_some() {
+            self.admin.addr = admin_addr;
+        }
+    }
+}
+
+fn override_with<T>(value: &mut T, overriding: Option<T>) {
+    if let Some(overriding) = overriding {
+        *value = overriding;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn args_override_file_which_overrides_defaults() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [tpu]
+            fanout_size = 20
+            maximum_retries_per_tx = 5
+
+            [quic]
+            max_number_of_connections = 4
+            "#,
+        )
+        .unwrap();
+        let args = Args::parse_from(["lite-rpc", "--fanout-size", "30"]);
+        config.apply_args(&args);
+
+        assert_eq!(config.tpu.fanout_size, 30);
+        assert_eq!(config.tpu.maximum_retries_per_tx, 5);
+        assert_eq!(config.quic.max_number_of_connections, 4);
+        assert_eq!(config.quic.connection_retry_count, 10);
+        assert_eq!(config.metrics.prometheus_addr, "[::]:9091");
+    }
+
+    #[test]
+    fn identity_args_replace_the_identity_of_the_file() {
+        let file_config: Config = toml::from_str(
+            r#"
+            [tpu]
+            identity_signer_url = "http://127.0.0.1:9000"
+            "#,
+        )
+        .unwrap();
+        let mut config = file_config.clone();
+        config.apply_args(&Args::parse_from(["lite-rpc", "-k", "id.json"]));
+        assert_eq!(config.tpu.identity_keypair, "id.json");
+        assert_eq!(config.tpu.identity_signer_url, None);
+        assert!(config.validate().is_ok());
+
+        let mut config = file_config;
+        config.tpu.identity_keypair = "id.json".to_string();
+        config.tpu.identity_signer_url = None;
+        config.apply_args(&Args::parse_from([
+            "lite-rpc",
+            "--identity-signer-url",
+            "http://127.0.0.1:9000",
+        ]));
+        assert!(config.tpu.identity_keypair.is_empty());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn unknown_entries_are_rejected() {
+        assert!(toml::from_str::<Config>("[tpu]\nfanout = 20\n").is_err());
+    }
+}
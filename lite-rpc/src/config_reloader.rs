@@ -0,0 +1,79 @@
+use log::{error, info};
+use solana_lite_rpc_core::structures::runtime_config::RuntimeConfigSender;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::{cli::Args, config::Config};
+
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// Reloads the reloadable part of the config: fanout, retry policy, send rate limit and log filter
+///
+/// The config file is read again and the options lite-rpc was started with are applied on top,
+/// so values given on the command line or in the environment are kept.
+pub struct ConfigReloader {
+    args: Args,
+    runtime_config_sender: RuntimeConfigSender,
+    log_filter_handle: LogFilterHandle,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        args: Args,
+        runtime_config_sender: RuntimeConfigSender,
+        log_filter_handle: LogFilterHandle,
+    ) -> Self {
+        Self {
+            args,
+            runtime_config_sender,
+            log_filter_handle,
+        }
+    }
+
+    /// log filter of the config, falling back to `RUST_LOG` then `info`
+    pub fn log_filter(config: &Config) -> anyhow::Result<EnvFilter> {
+        Ok(match &config.log.filter {
+            Some(filter) => EnvFilter::try_new(filter)?,
+            None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        })
+    }
+
+    pub fn apply_log_filter(&self, config: &Config) -> anyhow::Result<()> {
+        self.log_filter_handle
+            .reload(Self::log_filter(config)?)
+            .map_err(anyhow::Error::from)
+    }
+
+    /// returns the reloaded config
+    pub fn reload(&self) -> anyhow::Result<Config> {
+        let config = Config::load(&self.args)?;
+        let runtime_config = config.runtime_config();
+
+        self.apply_log_filter(&config)?;
+        self.runtime_config_sender.send_if_modified(|current| {
+            if *current == runtime_config {
+                return false;
+            }
+            info!("runtime config changed from {current:?} to {runtime_config:?}");
+            *current = runtime_config;
+            true
+        });
+        Ok(config)
+    }
+
+    /// reloads the config every time the process receives SIGHUP
+    #[cfg(unix)]
+    pub fn reload_on_sighup(self: std::sync::Arc<Self>) -> solana_lite_rpc_core::AnyhowJoinHandle {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        tokio::spawn(async move {
+            let mut sighup = signal(SignalKind::hangup())?;
+            while sighup.recv().await.is_some() {
+                info!("Received SIGHUP, reloading config");
+                if let Err(e) = self.reload() {
+                    error!("Error reloading config, keeping the current one {e:?}");
+                }
+            }
+            anyhow::bail!("SIGHUP stream closed")
+        })
+    }
+}
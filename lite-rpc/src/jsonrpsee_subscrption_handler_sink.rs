@@ -1,14 +1,23 @@
 use async_trait::async_trait;
 use jsonrpsee::{SubscriptionMessage, SubscriptionSink};
 use solana_rpc_client_api::response::{Response as RpcResponse, RpcResponseContext};
+use tokio::sync::OwnedSemaphorePermit;
 
 pub struct JsonRpseeSubscriptionHandlerSink {
     jsonrpsee_sink: SubscriptionSink,
+    /// counts the subscription against the quota of its tenant until the sink is dropped
+    _subscription_permit: Option<OwnedSemaphorePermit>,
 }
 
 impl JsonRpseeSubscriptionHandlerSink {
-    pub fn new(jsonrpsee_sink: SubscriptionSink) -> Self {
-        Self { jsonrpsee_sink }
+    pub fn new(
+        jsonrpsee_sink: SubscriptionSink,
+        subscription_permit: Option<OwnedSemaphorePermit>,
+    ) -> Self {
+        Self {
+            jsonrpsee_sink,
+            _subscription_permit: subscription_permit,
+        }
     }
 }
 
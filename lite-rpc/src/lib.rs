@@ -1,15 +1,23 @@
 use const_env::from_env;
 use solana_transaction_status::TransactionConfirmationStatus;
 
+pub mod admin;
 pub mod bridge;
+pub mod builder;
+pub mod check_config;
 pub mod cli;
+pub mod config;
+pub mod config_reloader;
 pub mod configs;
 pub mod encoding;
 pub mod errors;
 pub mod jsonrpsee_subscrption_handler_sink;
+#[cfg(feature = "postgres")]
 pub mod postgres;
 pub mod rpc;
 pub mod service_spawner;
+pub mod systemd;
+pub mod tenants;
 
 #[from_env]
 pub const DEFAULT_RPC_ADDR: &str = "http://0.0.0.0:8899";
@@ -13,9 +13,12 @@ use native_tls::{Certificate, Identity, TlsConnector};
 
 use crate::encoding::BinaryEncoding;
 use solana_lite_rpc_core::{
-    structures::notifications::{
-        BlockNotification, NotificationMsg, NotificationReciever, TransactionNotification,
-        TransactionUpdateNotification,
+    structures::{
+        notifications::{
+            BlockNotification, NotificationMsg, NotificationReciever, TransactionNotification,
+            TransactionUpdateNotification,
+        },
+        shutdown::{shutdown_stage_reached, ShutdownReceiver, ShutdownStage},
     },
     AnyhowJoinHandle,
 };
@@ -410,7 +413,12 @@ impl Postgres {
         Ok(self.session.read().await)
     }
 
-    pub fn start(mut self, mut recv: NotificationReciever) -> AnyhowJoinHandle {
+    /// writes the notifications until shutdown, the ones buffered are written before returning
+    pub fn start(
+        mut self,
+        mut recv: NotificationReciever,
+        shutdown: ShutdownReceiver,
+    ) -> AnyhowJoinHandle {
         tokio::spawn(async move {
             info!("start postgres worker");
 
@@ -461,6 +469,10 @@ impl Postgres {
                         }
                         Err(tokio::sync::mpsc::error::TryRecvError::Empty) => break,
                         Err(tokio::sync::mpsc::error::TryRecvError::Disconnected) => {
+                            // the services sending notifications are stopped first on shutdown
+                            if shutdown_stage_reached(&shutdown, ShutdownStage::StopRpc) {
+                                break;
+                            }
                             log::error!("Postgres channel broke");
                             bail!("Postgres channel broke")
                         }
@@ -469,6 +481,10 @@ impl Postgres {
 
                 // if there's nothing to do, yield for a brief time
                 if tx_batch.is_empty() && block_batch.is_empty() && update_batch.is_empty() {
+                    if shutdown_stage_reached(&shutdown, ShutdownStage::FlushNotifications) {
+                        info!("Postgres notifications flushed");
+                        return Ok(());
+                    }
                     tokio::time::sleep(Duration::from_millis(10)).await;
                     continue;
                 }
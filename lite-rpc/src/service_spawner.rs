@@ -1,6 +1,10 @@
+use anyhow::Context;
 use solana_lite_rpc_core::{
     stores::data_cache::DataCache,
-    structures::notifications::NotificationSender,
+    structures::{
+        notifications::NotificationSender, runtime_config::RuntimeConfigReceiver,
+        shutdown::ShutdownReceiver,
+    },
     types::{BlockStream, ClusterInfoStream, SlotStream, VoteAccountStream},
     AnyhowJoinHandle,
 };
@@ -8,34 +12,39 @@ use solana_lite_rpc_services::{
     data_caching_service::DataCachingService,
     metrics_capture::MetricsCapture,
     prometheus_sync::PrometheusSync,
+    supervisor::{join_group, supervise, RestartPolicy},
     tpu_utils::tpu_service::TpuService,
     transaction_replayer::TransactionReplayer,
     transaction_service::{TransactionService, TransactionServiceBuilder},
     tx_sender::TxSender,
 };
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
+use tokio::net::TcpListener;
 pub struct ServiceSpawner {
     pub prometheus_addr: String,
     pub data_cache: DataCache,
 }
 
 impl ServiceSpawner {
-    /// spawn services that support the whole system
-    pub async fn spawn_support_services(&self) -> anyhow::Result<()> {
+    /// spawn services that support the whole system, each one is restarted when it fails,
+    /// fails right away when the prometheus address can not be bound
+    pub async fn spawn_support_services(&self) -> anyhow::Result<AnyhowJoinHandle> {
         // spawn prometheus
-        let prometheus = PrometheusSync::sync(self.prometheus_addr.clone());
+        let listener = TcpListener::bind(&self.prometheus_addr)
+            .await
+            .with_context(|| format!("Failed to bind prometheus to {}", self.prometheus_addr))?;
+        let listener = Arc::new(listener);
+        let prometheus = supervise("prometheus", RestartPolicy::default(), move || {
+            PrometheusSync::serve(listener.clone())
+        });
 
         // spawn metrics capture
-        let metrics = MetricsCapture::new(self.data_cache.txs.clone()).capture();
+        let data_cache = self.data_cache.clone();
+        let metrics = supervise("metrics_capture", RestartPolicy::default(), move || {
+            MetricsCapture::new(data_cache.clone()).capture()
+        });
 
-        tokio::select! {
-            prometheus_res = prometheus => {
-                anyhow::bail!("Prometheus exited unexpectedly: {prometheus_res:?}");
-            }
-            metrics_res = metrics => {
-                anyhow::bail!("Metrics capture exited unexpectedly: {metrics_res:?}");
-            }
-        }
+        Ok(join_group(vec![prometheus, metrics]))
     }
 
     pub async fn spawn_data_caching_service(
@@ -66,8 +75,9 @@ impl ServiceSpawner {
         tpu_service: TpuService,
         max_nb_txs_in_queue: usize,
         notifier: Option<NotificationSender>,
-        max_retries: usize,
+        runtime_config: RuntimeConfigReceiver,
         slot_notifications: SlotStream,
+        shutdown: ShutdownReceiver,
     ) -> (TransactionService, AnyhowJoinHandle) {
         let service_builder = TransactionServiceBuilder::new(
             tx_sender,
@@ -78,8 +88,9 @@ impl ServiceSpawner {
         service_builder.start(
             notifier,
             self.data_cache.block_information_store.clone(),
-            max_retries,
+            runtime_config,
             slot_notifications,
+            shutdown,
         )
     }
 }
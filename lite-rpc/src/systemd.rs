@@ -0,0 +1,68 @@
+//! systemd notify and watchdog integration, every call is a no-op when lite-rpc is not
+//! started by systemd with `Type=notify`
+
+use std::time::Duration;
+
+use log::{info, warn};
+use solana_lite_rpc_core::structures::heartbeat::Heartbeat;
+
+#[cfg(unix)]
+fn notify(state: sd_notify::NotifyState) {
+    if let Err(e) = sd_notify::notify(false, &[state]) {
+        warn!("Error notifying systemd {e:?}");
+    }
+}
+
+/// `READY=1`, once the leader connections are set up
+pub fn notify_ready() {
+    info!("lite-rpc ready");
+    #[cfg(unix)]
+    notify(sd_notify::NotifyState::Ready);
+}
+
+/// `STOPPING=1`, when the graceful shutdown starts
+pub fn notify_stopping() {
+    #[cfg(unix)]
+    notify(sd_notify::NotifyState::Stopping);
+}
+
+#[cfg(unix)]
+fn watchdog_interval() -> Option<Duration> {
+    let mut usec = 0;
+    sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec))
+}
+
+#[cfg(not(unix))]
+fn watchdog_interval() -> Option<Duration> {
+    None
+}
+
+/// Pings the watchdog twice per `WatchdogSec` as long as every heartbeat is more recent
+/// than `WatchdogSec`, so systemd restarts lite-rpc when a core service stops making progress
+pub async fn run_watchdog(heartbeats: Vec<Heartbeat>) -> anyhow::Result<()> {
+    let Some(watchdog_interval) = watchdog_interval() else {
+        std::future::pending::<()>().await;
+        unreachable!()
+    };
+    info!("systemd watchdog enabled every {watchdog_interval:?}");
+
+    let mut ping_interval = tokio::time::interval(watchdog_interval / 2);
+    loop {
+        ping_interval.tick().await;
+
+        let stalled = heartbeats
+            .iter()
+            .find(|heartbeat| heartbeat.elapsed() > watchdog_interval);
+        match stalled {
+            Some(heartbeat) => warn!(
+                "{} made no progress for {:?}, not pinging the systemd watchdog",
+                heartbeat.name(),
+                heartbeat.elapsed()
+            ),
+            #[cfg(unix)]
+            None => notify(sd_notify::NotifyState::Watchdog),
+            #[cfg(not(unix))]
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Per tenant quotas and priority classes
+//!
+//! A tower layer in front of the rpc servers resolves the tenant of every http request from
+//! its `x-api-key` header or `api-key` query parameter. Requests with an unknown api key are
+//! rejected. The websocket server handles the messages outside of the upgrade request, so the
+//! tenant resolved for the upgrade is kept by connection id for the subscriptions made on it.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use hyper::{Body, Request, Response, StatusCode};
+use prometheus::{opts, register_int_counter, IntCounter};
+use solana_lite_rpc_core::structures::{priority_class::PriorityClass, tps_limiter::TpsLimiter};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tower::{Layer, Service};
+
+use crate::config::{TenantConfig, TenantLimits, TenantsConfig};
+
+lazy_static::lazy_static! {
+    static ref TENANT_TPS_QUOTA_EXCEEDED: IntCounter =
+    register_int_counter!(opts!("literpc_tenant_tps_quota_exceeded", "Transactions rejected because of the tps quota of their tenant")).unwrap();
+    static ref TENANT_SUBSCRIPTION_QUOTA_EXCEEDED: IntCounter =
+    register_int_counter!(opts!("literpc_tenant_subscription_quota_exceeded", "Subscriptions rejected because of the quota of their tenant")).unwrap();
+    static ref TENANT_UNAUTHORIZED: IntCounter =
+    register_int_counter!(opts!("literpc_tenant_unauthorized", "Requests rejected because of a missing or unknown api key")).unwrap();
+}
+
+const API_KEY_HEADER: &str = "x-api-key";
+const API_KEY_QUERY_PARAM: &str = "api-key";
+/// tenants of the most recent websocket connections kept, subscriptions on older connections
+/// are rejected until the client reconnects
+const MAX_WS_CONNECTIONS_TRACKED: usize = 65_536;
+
+tokio::task_local! {
+    static CURRENT_TENANT: Arc<Tenant>;
+}
+
+pub struct Tenant {
+    pub name: String,
+    pub priority: PriorityClass,
+    tps_limiter: Option<TpsLimiter>,
+    subscriptions: Option<Arc<Semaphore>>,
+}
+
+impl Tenant {
+    fn new(name: String, limits: &TenantLimits) -> Self {
+        Self {
+            name,
+            priority: limits.priority,
+            tps_limiter: limits.max_tps.map(TpsLimiter::new),
+            subscriptions: limits
+                .max_subscriptions
+                .map(|max_subscriptions| Arc::new(Semaphore::new(max_subscriptions))),
+        }
+    }
+
+    /// counts a transaction against the tps quota, false when exceeded
+    pub fn try_send_transaction(&self) -> bool {
+        let allowed = self
+            .tps_limiter
+            .as_ref()
+            .map_or(true, |tps_limiter| tps_limiter.try_acquire(1));
+        if !allowed {
+            TENANT_TPS_QUOTA_EXCEEDED.inc();
+        }
+        allowed
+    }
+
+    /// `Ok(None)` when subscriptions are unlimited, the subscription is counted until the
+    /// permit is dropped
+    pub fn try_subscribe(&self) -> anyhow::Result<Option<OwnedSemaphorePermit>> {
+        let Some(subscriptions) = &self.subscriptions else {
+            return Ok(None);
+        };
+        match subscriptions.clone().try_acquire_owned() {
+            Ok(permit) => Ok(Some(permit)),
+            Err(_) => {
+                TENANT_SUBSCRIPTION_QUOTA_EXCEEDED.inc();
+                anyhow::bail!("subscription quota of tenant {} exceeded", self.name)
+            }
+        }
+    }
+}
+
+pub struct Tenants {
+    require_api_key: bool,
+    public: Arc<Tenant>,
+    by_api_key: HashMap<String, Arc<Tenant>>,
+    ws_connections: Mutex<BTreeMap<usize, Arc<Tenant>>>,
+}
+
+impl Tenants {
+    pub fn new(config: &TenantsConfig) -> Self {
+        let by_api_key = config
+            .keys
+            .iter()
+            .map(
+                |TenantConfig {
+                     name,
+                     api_key,
+                     priority,
+                     max_tps,
+                     max_subscriptions,
+                 }| {
+                    let limits = TenantLimits {
+                        priority: *priority,
+                        max_tps: *max_tps,
+                        max_subscriptions: *max_subscriptions,
+                    };
+                    (
+                        api_key.clone(),
+                        Arc::new(Tenant::new(name.clone(), &limits)),
+                    )
+                },
+            )
+            .collect();
+
+        Self {
+            require_api_key: config.require_api_key,
+            public: Arc::new(Tenant::new("public".to_string(), &config.public)),
+            by_api_key,
+            ws_connections: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// none when the request is not allowed
+    fn resolve(&self, api_key: Option<&str>) -> Option<Arc<Tenant>> {
+        match api_key {
+            Some(api_key) => self.by_api_key.get(api_key).cloned(),
+            None if self.require_api_key => None,
+            None => Some(self.public.clone()),
+        }
+    }
+
+    /// tenant of the request being handled, the public one outside of an http request
+    pub fn current(&self) -> Arc<Tenant> {
+        CURRENT_TENANT
+            .try_with(|tenant| tenant.clone())
+            .unwrap_or_else(|_| self.public.clone())
+    }
+
+    fn open_ws_connection(&self, connection_id: usize, tenant: Arc<Tenant>) {
+        let mut ws_connections = self.ws_connections.lock().unwrap();
+        ws_connections.insert(connection_id, tenant);
+        while ws_connections.len() > MAX_WS_CONNECTIONS_TRACKED {
+            ws_connections.pop_first();
+        }
+    }
+
+    /// tenant resolved for the upgrade request of the websocket connection, none when the
+    /// connection is too old to be tracked
+    pub fn ws_connection(&self, connection_id: usize) -> Option<Arc<Tenant>> {
+        self.ws_connections
+            .lock()
+            .unwrap()
+            .get(&connection_id)
+            .cloned()
+    }
+}
+
+fn api_key<B>(request: &Request<B>) -> Option<Cow<str>> {
+    if let Some(api_key) = request.headers().get(API_KEY_HEADER) {
+        return api_key.to_str().ok().map(Cow::Borrowed);
+    }
+    request.uri().query().and_then(|query| {
+        query.split('&').find_map(|param| {
+            param
+                .strip_prefix(API_KEY_QUERY_PARAM)
+                .and_then(|value| value.strip_prefix('='))
+                .map(percent_decode)
+        })
+    })
+}
+
+/// decodes the `%XX` escapes of a query value, malformed escapes are kept as is
+fn percent_decode(value: &str) -> Cow<str> {
+    if !value.contains('%') {
+        return Cow::Borrowed(value);
+    }
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| bytes.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    Cow::Owned(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+#[derive(Clone)]
+pub struct TenantLayer {
+    tenants: Arc<Tenants>,
+    // numbers the websocket connections like jsonrpsee does
+    ws_connections: Option<Arc<AtomicUsize>>,
+}
+
+impl TenantLayer {
+    pub fn new(tenants: Arc<Tenants>) -> Self {
+        Self {
+            tenants,
+            ws_connections: None,
+        }
+    }
+
+    /// for the websocket server, jsonrpsee builds the middleware once per accepted connection
+    /// in the order of the connection ids it gives them, starting at 0, so the server must not
+    /// refuse connections before building it
+    pub fn websocket(tenants: Arc<Tenants>) -> Self {
+        Self {
+            tenants,
+            ws_connections: Some(Arc::new(AtomicUsize::new(0))),
+        }
+    }
+}
+
+impl<S> Layer<S> for TenantLayer {
+    type Service = TenantService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TenantService {
+            inner,
+            tenants: self.tenants.clone(),
+            ws_connection_id: self
+                .ws_connections
+                .as_ref()
+                .map(|ws_connections| ws_connections.fetch_add(1, Ordering::Relaxed)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TenantService<S> {
+    inner: S,
+    tenants: Arc<Tenants>,
+    ws_connection_id: Option<usize>,
+}
+
+impl<S> Service<Request<Body>> for TenantService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let Some(tenant) = self.tenants.resolve(api_key(&request).as_deref()) else {
+            TENANT_UNAUTHORIZED.inc();
+            return Box::pin(async {
+                Ok(Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .body(Body::from("missing or unknown api key"))
+                    .expect("valid unauthorized response"))
+            });
+        };
+        if let Some(connection_id) = self.ws_connection_id {
+            self.tenants
+                .open_ws_connection(connection_id, tenant.clone());
+        }
+        Box::pin(CURRENT_TENANT.scope(tenant, self.inner.call(request)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_key_from_header_or_query() {
+        let request = Request::builder()
+            .uri("/?foo=bar&api-key=secret")
+            .body(())
+            .unwrap();
+        assert_eq!(api_key(&request).as_deref(), Some("secret"));
+
+        let request = Request::builder()
+            .uri("/?api-key=a%2Bb%3Dc%zz")
+            .body(())
+            .unwrap();
+        assert_eq!(api_key(&request).as_deref(), Some("a+b=c%zz"));
+
+        let request = Request::builder()
+            .uri("/?api-key=ignored")
+            .header(API_KEY_HEADER, "header-secret")
+            .body(())
+            .unwrap();
+        assert_eq!(api_key(&request).as_deref(), Some("header-secret"));
+
+        let request = Request::builder().uri("/").body(()).unwrap();
+        assert_eq!(api_key(&request), None);
+    }
+
+    #[test]
+    fn subscriptions_count_against_the_tenant_of_their_ws_connection() {
+        let tenants = Tenants::new(&TenantsConfig {
+            require_api_key: false,
+            public: TenantLimits::default(),
+            keys: vec![TenantConfig {
+                name: "internal".to_string(),
+                api_key: "secret".to_string(),
+                priority: PriorityClass::default(),
+                max_tps: None,
+                max_subscriptions: Some(1),
+            }],
+        });
+        let internal = tenants.resolve(Some("secret")).unwrap();
+        tenants.open_ws_connection(0, tenants.resolve(None).unwrap());
+        tenants.open_ws_connection(1, internal);
+
+        let public = tenants.ws_connection(0).unwrap();
+        assert!(public.try_subscribe().unwrap().is_none());
+
+        let internal = tenants.ws_connection(1).unwrap();
+        let permit = internal.try_subscribe().unwrap();
+        assert!(permit.is_some());
+        assert!(internal.try_subscribe().is_err());
+        drop(permit);
+        assert!(internal.try_subscribe().unwrap().is_some());
+
+        assert!(tenants.ws_connection(2).is_none());
+    }
+}
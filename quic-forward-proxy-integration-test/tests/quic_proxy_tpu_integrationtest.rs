@@ -1,14 +1,19 @@
+use async_trait::async_trait;
 use countmap::CountMap;
 use crossbeam_channel::Sender;
 
 use log::{debug, error, info, trace, warn};
 
+use solana_lite_rpc_core::identity_signer::QuicClientIdentity;
 use solana_lite_rpc_core::quic_connection_utils::QuicConnectionParameters;
 use solana_lite_rpc_core::solana_utils::SerializableTransaction;
 use solana_lite_rpc_core::stores::data_cache::DataCache;
 use solana_lite_rpc_core::structures::identity_stakes::IdentityStakesData;
+use solana_lite_rpc_core::structures::leader_data::LeaderData;
 use solana_lite_rpc_core::structures::transaction_sent_info::SentTransactionInfo;
+use solana_lite_rpc_core::traits::leaders_fetcher_interface::LeaderFetcherInterface;
 use solana_lite_rpc_services::tpu_utils::tpu_connection_manager::TpuConnectionManager;
+use solana_sdk::clock::Slot;
 use solana_sdk::hash::Hash;
 use solana_sdk::instruction::Instruction;
 use solana_sdk::message::Message;
@@ -20,9 +25,8 @@ use solana_streamer::nonblocking::quic::ConnectionPeerType;
 use solana_streamer::packet::PacketBatch;
 use solana_streamer::quic::StreamStats;
 use solana_streamer::streamer::StakedNodes;
-use solana_streamer::tls_certificates::new_self_signed_tls_certificate;
 use std::collections::{HashMap, HashSet};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::net::{SocketAddr, UdpSocket};
 
 use itertools::Itertools;
 use std::str::FromStr;
@@ -36,10 +40,15 @@ use tokio::task::{yield_now, JoinHandle};
 use tokio::time::sleep;
 use tracing_subscriber::EnvFilter;
 
+use solana_lite_rpc_quic_forward_proxy::client_allowlist::ClientAllowlist;
+use solana_lite_rpc_quic_forward_proxy::outbound::leader_fanout::LeaderFanout;
+use solana_lite_rpc_quic_forward_proxy::outbound::retry_buffer::RetryPolicy;
 use solana_lite_rpc_quic_forward_proxy::proxy::QuicForwardProxy;
+use solana_lite_rpc_quic_forward_proxy::shared::ProxyState;
 use solana_lite_rpc_quic_forward_proxy::tls_self_signed_pair_generator::SelfSignedTlsConfigProvider;
 use solana_lite_rpc_quic_forward_proxy::validator_identity::ValidatorIdentity;
 use solana_lite_rpc_services::tpu_utils::quic_proxy_connection_manager::QuicProxyConnectionManager;
+use solana_lite_rpc_services::tpu_utils::tpu_connection_path::ProxyBalancePolicy;
 use tracing_subscriber::fmt::format::FmtSpan;
 
 #[derive(Copy, Clone, Debug)]
@@ -47,6 +56,22 @@ struct TestCaseParams {
     sample_tx_count: u32,
     stake_connection: bool,
     proxy_mode: bool,
+    /// lite-rpc sends to an unreachable proxy first
+    proxy_failover: bool,
+    /// lite-rpc only sends the slots, the proxy resolves the leaders
+    leader_fanout: bool,
+    proxy_auth: ProxyAuth,
+}
+
+/// how the quic proxy and lite-rpc authenticate each other
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ProxyAuth {
+    /// lite-rpc is in the allowlist of the proxy and pins the identity of the proxy
+    Allowed,
+    /// lite-rpc is not in the allowlist of the proxy
+    ClientRejected,
+    /// lite-rpc pins another identity than the one of the proxy
+    WrongProxyIdentity,
 }
 
 const MAXIMUM_TRANSACTIONS_IN_QUEUE: usize = 16_384;
@@ -62,6 +87,9 @@ const QUIC_CONNECTION_PARAMS: QuicConnectionParameters = QuicConnectionParameter
     number_of_transactions_per_unistream: 10,
 };
 
+// the streamer is the leader of all slots
+const LEADER_FANOUT_SLOTS: (Slot, Slot) = (1000, 1004);
+
 #[test]
 pub fn small_tx_batch_staked_direct() {
     configure_logging(true);
@@ -70,6 +98,9 @@ pub fn small_tx_batch_staked_direct() {
         sample_tx_count: 20,
         stake_connection: true,
         proxy_mode: false,
+        proxy_failover: false,
+        leader_fanout: false,
+        proxy_auth: ProxyAuth::Allowed,
     });
 }
 
@@ -81,6 +112,65 @@ pub fn small_tx_batch_staked_proxy() {
         sample_tx_count: 20,
         stake_connection: true,
         proxy_mode: true,
+        proxy_failover: false,
+        leader_fanout: false,
+        proxy_auth: ProxyAuth::Allowed,
+    });
+}
+
+#[test]
+pub fn small_tx_batch_staked_proxy_failover() {
+    configure_logging(true);
+
+    wireup_and_send_txs_via_channel(TestCaseParams {
+        sample_tx_count: 20,
+        stake_connection: true,
+        proxy_mode: true,
+        proxy_failover: true,
+        leader_fanout: false,
+        proxy_auth: ProxyAuth::Allowed,
+    });
+}
+
+#[test]
+pub fn small_tx_batch_staked_proxy_leader_fanout() {
+    configure_logging(true);
+
+    wireup_and_send_txs_via_channel(TestCaseParams {
+        sample_tx_count: 20,
+        stake_connection: true,
+        proxy_mode: true,
+        proxy_failover: false,
+        leader_fanout: true,
+        proxy_auth: ProxyAuth::Allowed,
+    });
+}
+
+#[test]
+pub fn small_tx_batch_proxy_rejects_client_not_in_allowlist() {
+    configure_logging(true);
+
+    wireup_and_send_txs_via_channel(TestCaseParams {
+        sample_tx_count: 20,
+        stake_connection: true,
+        proxy_mode: true,
+        proxy_failover: false,
+        leader_fanout: false,
+        proxy_auth: ProxyAuth::ClientRejected,
+    });
+}
+
+#[test]
+pub fn small_tx_batch_proxy_with_wrong_pinned_identity() {
+    configure_logging(true);
+
+    wireup_and_send_txs_via_channel(TestCaseParams {
+        sample_tx_count: 20,
+        stake_connection: true,
+        proxy_mode: true,
+        proxy_failover: false,
+        leader_fanout: false,
+        proxy_auth: ProxyAuth::WrongProxyIdentity,
     });
 }
 
@@ -92,6 +182,9 @@ pub fn small_tx_batch_unstake_direct() {
         sample_tx_count: 20,
         stake_connection: false,
         proxy_mode: false,
+        proxy_failover: false,
+        leader_fanout: false,
+        proxy_auth: ProxyAuth::Allowed,
     });
 }
 
@@ -104,6 +197,9 @@ pub fn with_100_transactions_direct() {
         sample_tx_count: 100,
         stake_connection: true,
         proxy_mode: false,
+        proxy_failover: false,
+        leader_fanout: false,
+        proxy_auth: ProxyAuth::Allowed,
     });
 }
 
@@ -116,6 +212,9 @@ pub fn with_1000_transactions_direct() {
         sample_tx_count: 1000,
         stake_connection: true,
         proxy_mode: false,
+        proxy_failover: false,
+        leader_fanout: false,
+        proxy_auth: ProxyAuth::Allowed,
     });
 }
 
@@ -131,6 +230,9 @@ pub fn bench_proxy() {
         sample_tx_count: 1000,
         stake_connection: true,
         proxy_mode: true,
+        proxy_failover: false,
+        leader_fanout: false,
+        proxy_auth: ProxyAuth::Allowed,
     });
 }
 
@@ -144,6 +246,9 @@ pub fn with_10000_transactions_direct() {
         sample_tx_count: 10000,
         stake_connection: true,
         proxy_mode: false,
+        proxy_failover: false,
+        leader_fanout: false,
+        proxy_auth: ProxyAuth::Allowed,
     });
 }
 
@@ -157,6 +262,9 @@ pub fn with_10000_transactions_proxy() {
         sample_tx_count: 10000,
         stake_connection: true,
         proxy_mode: true,
+        proxy_failover: false,
+        leader_fanout: false,
+        proxy_auth: ProxyAuth::Allowed,
     });
 }
 
@@ -170,6 +278,9 @@ pub fn many_transactions_proxy() {
         sample_tx_count: 100000,
         stake_connection: false,
         proxy_mode: true,
+        proxy_failover: false,
+        leader_fanout: false,
+        proxy_auth: ProxyAuth::Allowed,
     });
 }
 
@@ -200,6 +311,15 @@ fn wireup_and_send_txs_via_channel(test_case_params: TestCaseParams) {
         .expect("failed to build tokio runtime for quic-forward-proxy");
 
     let literpc_validator_identity = Arc::new(Keypair::new());
+    let proxy_identity = Keypair::new();
+    let allowed_client = match test_case_params.proxy_auth {
+        ProxyAuth::ClientRejected => Keypair::new().pubkey(),
+        ProxyAuth::Allowed | ProxyAuth::WrongProxyIdentity => literpc_validator_identity.pubkey(),
+    };
+    let pinned_proxy_identity = match test_case_params.proxy_auth {
+        ProxyAuth::WrongProxyIdentity => Keypair::new().pubkey(),
+        ProxyAuth::Allowed | ProxyAuth::ClientRejected => proxy_identity.pubkey(),
+    };
     let udp_listen_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
     let listen_addr = udp_listen_socket.local_addr().unwrap();
 
@@ -207,6 +327,16 @@ fn wireup_and_send_txs_via_channel(test_case_params: TestCaseParams) {
         .unwrap()
         .local_addr()
         .unwrap();
+    // bound but never answering, kept open so the port is not reused
+    let unreachable_proxy_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let proxy_addrs = if test_case_params.proxy_failover {
+        vec![
+            unreachable_proxy_socket.local_addr().unwrap(),
+            proxy_listen_addr,
+        ]
+    } else {
+        vec![proxy_listen_addr]
+    };
 
     let (inbound_packets_sender, inbound_packets_receiver) = crossbeam_channel::unbounded();
 
@@ -229,8 +359,20 @@ fn wireup_and_send_txs_via_channel(test_case_params: TestCaseParams) {
         );
     });
 
+    let leader_fanout = test_case_params.leader_fanout.then(|| {
+        LeaderFanout::new_static(
+            Arc::new(StaticLeaderSchedule(literpc_validator_identity.pubkey())),
+            HashMap::from([(literpc_validator_identity.pubkey(), listen_addr)]),
+        )
+    });
+
     runtime_quic_proxy.block_on(async {
-        tokio::spawn(start_quic_proxy(proxy_listen_addr));
+        tokio::spawn(start_quic_proxy(
+            proxy_listen_addr,
+            leader_fanout,
+            proxy_identity,
+            allowed_client,
+        ));
     });
 
     runtime_literpc.block_on(async {
@@ -239,7 +381,8 @@ fn wireup_and_send_txs_via_channel(test_case_params: TestCaseParams) {
                 test_case_params,
                 listen_addr,
                 literpc_validator_identity,
-                proxy_listen_addr,
+                proxy_addrs,
+                pinned_proxy_identity,
             ));
         } else {
             tokio::spawn(start_literpc_client_direct_mode(
@@ -264,9 +407,16 @@ fn wireup_and_send_txs_via_channel(test_case_params: TestCaseParams) {
         let mut contents: HashSet<String> =
             HashSet::with_capacity(test_case_params.sample_tx_count as usize);
         let warmup_tx_count: u32 = test_case_params.sample_tx_count / 2;
+        let expect_delivery =
+            !test_case_params.proxy_mode || test_case_params.proxy_auth == ProxyAuth::Allowed;
+        let idle_timeout = if expect_delivery {
+            Duration::from_secs(50)
+        } else {
+            Duration::from_secs(10)
+        };
 
         while (count_map.len() as u32) < test_case_params.sample_tx_count {
-            if latest_tx.elapsed() > Duration::from_secs(50) {
+            if latest_tx.elapsed() > idle_timeout {
                 warn!("abort after timeout waiting for packet from quic streamer");
                 break;
             }
@@ -316,6 +466,16 @@ fn wireup_and_send_txs_via_channel(test_case_params: TestCaseParams) {
             }
         } // -- while not all packets received - by count
 
+        if !expect_delivery {
+            assert!(
+                count_map.is_empty(),
+                "no transaction may go through a proxy failing the mutual authentication ({:?})",
+                test_case_params.proxy_auth
+            );
+            runtime_literpc.shutdown_timeout(Duration::from_millis(1000));
+            return;
+        }
+
         let total_duration = timer.elapsed();
         let half_duration = timer2
             .map(|t| t.elapsed())
@@ -367,6 +527,7 @@ fn wireup_and_send_txs_via_channel(test_case_params: TestCaseParams) {
     });
 
     packet_consumer_jh.join().unwrap();
+    drop(unreachable_proxy_socket);
 }
 
 fn configure_panic_hook() {
@@ -463,14 +624,13 @@ async fn start_literpc_client_direct_mode(
     // (String, Vec<u8>) (signature, transaction)
     let (sender, _) = tokio::sync::broadcast::channel(MAXIMUM_TRANSACTIONS_IN_QUEUE);
     let broadcast_sender = Arc::new(sender);
-    let (certificate, key) = new_self_signed_tls_certificate(
-        literpc_validator_identity.as_ref(),
-        IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
-    )
-    .expect("Failed to initialize QUIC connection certificates");
+    let client_identity = Arc::new(
+        QuicClientIdentity::new(literpc_validator_identity.clone())
+            .expect("Failed to initialize QUIC connection certificates"),
+    );
 
     let tpu_connection_manager =
-        TpuConnectionManager::new(certificate, key, fanout_slots as usize).await;
+        TpuConnectionManager::new(client_identity, fanout_slots as usize).await;
 
     // this effectively controls how many connections we will have
     let mut connections_to_keep: HashMap<Pubkey, SocketAddr> = HashMap::new();
@@ -553,24 +713,31 @@ async fn start_literpc_client_proxy_mode(
     test_case_params: TestCaseParams,
     streamer_listen_addrs: SocketAddr,
     validator_identity: Arc<Keypair>,
-    forward_proxy_address: SocketAddr,
+    forward_proxy_addresses: Vec<SocketAddr>,
+    proxy_identity: Pubkey,
 ) -> anyhow::Result<()> {
     info!(
-        "Start lite-rpc test client using quic proxy at {} ...",
-        forward_proxy_address
+        "Start lite-rpc test client using quic proxies at {:?} ...",
+        forward_proxy_addresses
     );
 
     // (String, Vec<u8>) (signature, transaction)
     let (sender, _) = tokio::sync::broadcast::channel(MAXIMUM_TRANSACTIONS_IN_QUEUE);
     let broadcast_sender = Arc::new(sender);
-    let (certificate, key) = new_self_signed_tls_certificate(
-        validator_identity.as_ref(),
-        IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
-    )
-    .expect("Failed to initialize QUIC connection certificates");
+    let client_identity = Arc::new(
+        QuicClientIdentity::new(validator_identity.clone())
+            .expect("Failed to initialize QUIC connection certificates"),
+    );
 
-    let quic_proxy_connection_manager =
-        QuicProxyConnectionManager::new(certificate, key, forward_proxy_address).await;
+    // the first proxy is tried first, the others only once it fails
+    let quic_proxy_connection_manager = QuicProxyConnectionManager::new(
+        client_identity,
+        forward_proxy_addresses,
+        Some(proxy_identity),
+        ProxyBalancePolicy::Failover,
+        test_case_params.leader_fanout,
+    )
+    .await;
 
     // this effectively controls how many connections we will have
     let mut connections_to_keep: HashMap<Pubkey, SocketAddr> = HashMap::new();
@@ -592,8 +759,11 @@ async fn start_literpc_client_proxy_mode(
         addr2,
     );
 
-    // this is the real streamer
-    connections_to_keep.insert(validator_identity.pubkey(), streamer_listen_addrs);
+    // with leader fanout the proxy has to find the real streamer by itself
+    if !test_case_params.leader_fanout {
+        // this is the real streamer
+        connections_to_keep.insert(validator_identity.pubkey(), streamer_listen_addrs);
+    }
 
     // get information about the optional validator identity stake
     // populated from get_stakes_for_identity()
@@ -627,6 +797,7 @@ async fn start_literpc_client_proxy_mode(
         .update_connection(
             transaction_receiver,
             connections_to_keep,
+            LEADER_FANOUT_SLOTS,
             QUIC_CONNECTION_PARAMS,
         )
         .await;
@@ -663,15 +834,33 @@ async fn start_literpc_client_proxy_mode(
     Ok(())
 }
 
-async fn start_quic_proxy(proxy_listen_addr: SocketAddr) -> anyhow::Result<()> {
-    let _tls_configuration = SelfSignedTlsConfigProvider::new_singleton_self_signed_localhost();
+async fn start_quic_proxy(
+    proxy_listen_addr: SocketAddr,
+    leader_fanout: Option<Arc<LeaderFanout>>,
+    proxy_identity: Keypair,
+    allowed_client: Pubkey,
+) -> anyhow::Result<()> {
     let random_unstaked_validator_identity = ValidatorIdentity::new(None);
 
-    let tls_config = Arc::new(SelfSignedTlsConfigProvider::new_singleton_self_signed_localhost());
+    // mutual tls like a deployed proxy: the proxy presents `proxy_identity` and only
+    // accepts `allowed_client`
+    let client_allowlist =
+        ClientAllowlist::new(Some(ClientAllowlist::parse(&allowed_client.to_string())?));
+    let tls_config = Arc::new(SelfSignedTlsConfigProvider::new_for_identity(
+        &proxy_identity,
+        client_allowlist.clone(),
+    )?);
     let proxy_service = QuicForwardProxy::new(
         proxy_listen_addr,
         tls_config,
-        random_unstaked_validator_identity,
+        client_allowlist,
+        leader_fanout,
+        // proxy defaults
+        RetryPolicy {
+            window: Duration::from_millis(2000),
+            capacity: 1000,
+        },
+        ProxyState::new(random_unstaked_validator_identity),
     )
     .await?
     .start_services();
@@ -683,6 +872,21 @@ async fn start_quic_proxy(proxy_listen_addr: SocketAddr) -> anyhow::Result<()> {
     }
 }
 
+/// leader schedule with the same leader for every slot
+struct StaticLeaderSchedule(Pubkey);
+
+#[async_trait]
+impl LeaderFetcherInterface for StaticLeaderSchedule {
+    async fn get_slot_leaders(&self, from: Slot, to: Slot) -> anyhow::Result<Vec<LeaderData>> {
+        Ok((from..=to)
+            .map(|leader_slot| LeaderData {
+                leader_slot,
+                pubkey: self.0,
+            })
+            .collect())
+    }
+}
+
 #[allow(dead_code)]
 struct SolanaQuicStreamer {
     sock: UdpSocket,
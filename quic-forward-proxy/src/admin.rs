@@ -0,0 +1,229 @@
+//! Admin interface of the proxy
+//!
+//! Served on `--admin-addr`, every request needs the `--admin-token` as bearer token in its
+//! `authorization` header.
+
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use anyhow::{bail, Context as _};
+use futures::future::BoxFuture;
+use hyper::header::AUTHORIZATION;
+use hyper::{Body, Request, Response, StatusCode};
+use jsonrpsee::core::RpcResult;
+use jsonrpsee::{proc_macros::rpc, server::ServerBuilder};
+use log::{info, warn};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::read_keypair_file;
+use tower::{Layer, Service};
+
+use crate::shared::{ConnectionState, ProxyState};
+use crate::util::AnyhowJoinHandle;
+use crate::validator_identity::ValidatorIdentity;
+
+/// Operations for the operators of the proxy
+#[rpc(server)]
+pub trait ProxyAdminRpc {
+    /// Stops accepting new clients, the connected ones are closed once their pending requests
+    /// are handed to the forwarder so they fail over to another proxy. The proxy keeps
+    /// draining until it is restarted.
+    #[method(name = "drain")]
+    async fn drain(&self) -> RpcResult<()>;
+
+    /// Reloads the identity used towards the tpus from the keypair file at `path`, the
+    /// `--identity-keypair` file if none. New connections use the new identity while the
+    /// current ones send what they have queued. Returns the new identity pubkey.
+    ///
+    /// The certificate presented to the clients is not rebuilt, it keeps the identity the
+    /// proxy was started with, which is the one clients pin until the next restart.
+    #[method(name = "reloadIdentity")]
+    async fn reload_identity(&self, path: Option<String>) -> RpcResult<String>;
+
+    /// Connected clients and warm tpu connections
+    #[method(name = "connectionState")]
+    async fn connection_state(&self) -> RpcResult<ConnectionState>;
+}
+
+pub struct ProxyAdmin {
+    proxy_state: Arc<ProxyState>,
+    // presented to the clients, fixed at startup
+    tls_identity: Pubkey,
+    // empty if the identity was not given as file
+    identity_keypair: String,
+}
+
+impl ProxyAdmin {
+    pub fn new(proxy_state: Arc<ProxyState>, identity_keypair: String) -> Self {
+        Self {
+            tls_identity: proxy_state.validator_identity().get_pubkey(),
+            proxy_state,
+            identity_keypair,
+        }
+    }
+
+    /// serves until the server stops
+    pub fn start(self, addr: String, token: String) -> AnyhowJoinHandle {
+        tokio::spawn(async move {
+            let server_handle = ServerBuilder::default()
+                .http_only()
+                .set_middleware(tower::ServiceBuilder::new().layer(AdminAuthLayer::new(token)))
+                .build(addr.as_str())
+                .await?
+                .start(self.into_rpc())?;
+
+            info!("Admin server started at {addr:?}");
+            server_handle.stopped().await;
+            bail!("Admin server stopped")
+        })
+    }
+
+    fn reload(&self, path: Option<String>) -> anyhow::Result<String> {
+        let path = match path {
+            Some(path) => path,
+            None if !self.identity_keypair.is_empty() => self.identity_keypair.clone(),
+            None => bail!("no identity keypair file configured, pass the path"),
+        };
+        let keypair = read_keypair_file(&path)
+            .map_err(|e| anyhow::anyhow!("{e}"))
+            .with_context(|| format!("Error reading identity keypair {path}"))?;
+
+        let validator_identity = ValidatorIdentity::new(Some(keypair));
+        let pubkey = validator_identity.get_pubkey();
+        info!("Switching the TPU connections to identity {pubkey} from {path}");
+        self.proxy_state.set_validator_identity(validator_identity);
+        if pubkey != self.tls_identity {
+            warn!(
+                "Clients still see identity {} until the restart, keep pinning it",
+                self.tls_identity
+            );
+        }
+        Ok(pubkey.to_string())
+    }
+}
+
+#[jsonrpsee::core::async_trait]
+impl ProxyAdminRpcServer for ProxyAdmin {
+    async fn drain(&self) -> RpcResult<()> {
+        warn!("Draining on admin request");
+        self.proxy_state.drain();
+        Ok(())
+    }
+
+    async fn reload_identity(&self, path: Option<String>) -> RpcResult<String> {
+        self.reload(path)
+            .map_err(|e| jsonrpsee::core::Error::Custom(format!("{e:?}")))
+    }
+
+    async fn connection_state(&self) -> RpcResult<ConnectionState> {
+        Ok(self.proxy_state.connection_state())
+    }
+}
+
+fn is_authorized<B>(request: &Request<B>, token: &str) -> bool {
+    request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map_or(false, |bearer| {
+            constant_time_eq(bearer.as_bytes(), token.as_bytes())
+        })
+}
+
+// compares every byte so the time taken does not tell how much of the token was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[derive(Clone)]
+struct AdminAuthLayer {
+    token: Arc<String>,
+}
+
+impl AdminAuthLayer {
+    fn new(token: String) -> Self {
+        Self {
+            token: Arc::new(token),
+        }
+    }
+}
+
+impl<S> Layer<S> for AdminAuthLayer {
+    type Service = AdminAuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AdminAuthService {
+            inner,
+            token: self.token.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct AdminAuthService<S> {
+    inner: S,
+    token: Arc<String>,
+}
+
+impl<S> Service<Request<Body>> for AdminAuthService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if !is_authorized(&request, &self.token) {
+            warn!("Rejected admin request without a valid token");
+            return Box::pin(async {
+                Ok(Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .body(Body::from("missing or invalid admin token"))
+                    .expect("valid unauthorized response"))
+            });
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admin_token_from_bearer_header() {
+        let request = Request::builder()
+            .header(AUTHORIZATION, "Bearer secret")
+            .body(())
+            .unwrap();
+        assert!(is_authorized(&request, "secret"));
+        assert!(!is_authorized(&request, "other"));
+
+        let request = Request::builder()
+            .header(AUTHORIZATION, "secret")
+            .body(())
+            .unwrap();
+        assert!(!is_authorized(&request, "secret"));
+
+        let request = Request::builder().body(()).unwrap();
+        assert!(!is_authorized(&request, "secret"));
+    }
+
+    #[test]
+    fn constant_time_eq_compares_whole_tokens() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+}
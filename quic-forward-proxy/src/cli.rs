@@ -8,4 +8,31 @@ pub struct Args {
     // e.g. 0.0.0.0:11111 or "localhost:11111"
     #[arg(short = 'l', long, env)]
     pub proxy_listen_addr: String,
+    /// file with the identity pubkeys or source networks of the lite-rpc instances allowed to
+    /// send through the proxy, each optionally with a tps quota, one per line
+    #[arg(long, env)]
+    pub client_allowlist: Option<String>,
+    /// accept any client when no allowlist is given, anyone reaching the proxy can then send
+    /// through its identity
+    #[arg(long)]
+    pub allow_any_client: bool,
+    /// rpc node to get the leader schedule from, enables the leader fanout for the clients
+    /// asking for it
+    #[arg(long, env)]
+    pub rpc_url: Option<String>,
+    /// how long transactions for an unreachable tpu are retried, 0 disables the retries
+    #[arg(long, env, default_value_t = 2000)]
+    pub retry_window_ms: u64,
+    /// max number of transactions kept for retries per tpu
+    #[arg(long, env, default_value_t = 1000)]
+    pub retry_buffer_size: usize,
+    /// serves the prometheus metrics on e.g. 0.0.0.0:9092, disabled if unset
+    #[arg(long, env)]
+    pub prometheus_addr: Option<String>,
+    /// serves the admin interface on e.g. 127.0.0.1:9093, disabled if unset
+    #[arg(long, env)]
+    pub admin_addr: Option<String>,
+    /// bearer token required by the admin interface
+    #[arg(long, env, hide_env_values = true)]
+    pub admin_token: Option<String>,
 }
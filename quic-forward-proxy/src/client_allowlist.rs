@@ -0,0 +1,266 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::{bail, Context};
+use log::warn;
+use prometheus::{opts, register_int_counter, register_int_counter_vec, IntCounter, IntCounterVec};
+use rustls::server::{ClientCertVerified, ClientCertVerifier};
+use rustls::{Certificate, DistinguishedNames};
+use solana_lite_rpc_core::structures::tps_limiter::TpsLimiter;
+use solana_sdk::pubkey::Pubkey;
+use solana_streamer::tls_certificates::get_pubkey_from_tls_certificate;
+
+lazy_static::lazy_static! {
+    static ref REJECTED_CLIENTS: IntCounter =
+        register_int_counter!(opts!("quic_forward_proxy_rejected_clients", "Connections rejected because the client is not in the allowlist")).unwrap();
+    static ref QUOTA_EXCEEDED: IntCounterVec =
+        register_int_counter_vec!(opts!("quic_forward_proxy_quota_exceeded", "Transactions rejected because of the quota of their allowlist entry"), &["client"]).unwrap();
+}
+
+/// Requires the clients to present the self signed certificate of their identity, like lite-rpc
+/// does, and only accepts the identities and source networks of the allowlist
+pub struct ClientAllowlist {
+    // any client is accepted if none
+    rules: Option<Vec<Arc<ClientRule>>>,
+}
+
+/// Entry of the allowlist, the quota is shared by all connections matching it
+pub struct ClientRule {
+    // the entry as written in the allowlist
+    name: String,
+    client: ClientMatcher,
+    tps_limiter: Option<TpsLimiter>,
+}
+
+enum ClientMatcher {
+    Identity(Pubkey),
+    Network(Cidr),
+    Any,
+}
+
+impl ClientRule {
+    fn parse(line: &str) -> anyhow::Result<Self> {
+        let mut fields = line.split_whitespace();
+        let name = fields.next().context("empty entry")?.to_string();
+        let client = if name.contains('/') || name.parse::<IpAddr>().is_ok() {
+            ClientMatcher::Network(Cidr::parse(&name)?)
+        } else {
+            ClientMatcher::Identity(Pubkey::from_str(&name).context("invalid pubkey")?)
+        };
+        let tps_limiter = fields
+            .next()
+            .map(|max_tps| max_tps.parse().context("invalid max tps"))
+            .transpose()?
+            .map(TpsLimiter::new);
+        if fields.next().is_some() {
+            bail!("unexpected fields after the max tps");
+        }
+        Ok(Self {
+            name,
+            client,
+            tps_limiter,
+        })
+    }
+
+    /// counts the transactions of a request against the quota, returns how many of them are
+    /// forwarded, the excess over what is left of the quota is rejected
+    pub fn admit_transactions(&self, transaction_count: usize) -> usize {
+        let admitted = self
+            .tps_limiter
+            .as_ref()
+            .map_or(transaction_count, |tps_limiter| {
+                tps_limiter.acquire_up_to(transaction_count as u64) as usize
+            });
+        if admitted < transaction_count {
+            QUOTA_EXCEEDED
+                .with_label_values(&[&self.name])
+                .inc_by((transaction_count - admitted) as u64);
+        }
+        admitted
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl ClientAllowlist {
+    pub fn new(rules: Option<Vec<ClientRule>>) -> Arc<Self> {
+        Arc::new(Self {
+            rules: rules.map(|rules| rules.into_iter().map(Arc::new).collect()),
+        })
+    }
+
+    /// one entry per line: a base58 pubkey or a source network like `10.0.0.0/8`, optionally
+    /// followed by the max transactions per second; empty lines and lines starting with `#`
+    /// are skipped
+    pub fn load(path: &str) -> anyhow::Result<Arc<Self>> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Error reading client allowlist {path}"))?;
+        let rules = Self::parse(&contents)
+            .with_context(|| format!("Error parsing client allowlist {path}"))?;
+        Ok(Self::new(Some(rules)))
+    }
+
+    /// entries in the format of `load`
+    pub fn parse(contents: &str) -> anyhow::Result<Vec<ClientRule>> {
+        contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| ClientRule::parse(line).with_context(|| format!("Invalid entry {line}")))
+            .collect()
+    }
+
+    pub fn allowed_count(&self) -> Option<usize> {
+        self.rules.as_ref().map(Vec::len)
+    }
+
+    /// rule of a connected client, identities take precedence over networks; none when the
+    /// client is not allowed
+    pub fn admit(&self, identity: Option<Pubkey>, address: IpAddr) -> Option<Arc<ClientRule>> {
+        let Some(rules) = &self.rules else {
+            return Some(Arc::new(ClientRule {
+                name: "any".to_string(),
+                client: ClientMatcher::Any,
+                tps_limiter: None,
+            }));
+        };
+        let by_identity = rules.iter().find(|rule| match &rule.client {
+            ClientMatcher::Identity(allowed) => identity.as_ref() == Some(allowed),
+            _ => false,
+        });
+        let rule = by_identity.or_else(|| {
+            rules.iter().find(|rule| match &rule.client {
+                ClientMatcher::Network(network) => network.contains(address),
+                _ => false,
+            })
+        });
+        if rule.is_none() {
+            REJECTED_CLIENTS.inc();
+        }
+        rule.cloned()
+    }
+
+    // the networks can only be checked once the connection is established
+    fn may_be_allowed(&self, identity: &Pubkey) -> bool {
+        self.rules.as_ref().map_or(true, |rules| {
+            rules.iter().any(|rule| match &rule.client {
+                ClientMatcher::Identity(allowed) => allowed == identity,
+                ClientMatcher::Network(_) | ClientMatcher::Any => true,
+            })
+        })
+    }
+}
+
+impl ClientCertVerifier for ClientAllowlist {
+    fn client_auth_root_subjects(&self) -> Option<DistinguishedNames> {
+        Some(DistinguishedNames::new())
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        let Some(identity) = get_pubkey_from_tls_certificate(end_entity) else {
+            return Err(rustls::Error::InvalidCertificateData(
+                "no ed25519 identity in the client certificate".to_string(),
+            ));
+        };
+        if !self.may_be_allowed(&identity) {
+            warn!("Rejected client with identity {identity}, not in the allowlist");
+            REJECTED_CLIENTS.inc();
+            return Err(rustls::Error::General(format!(
+                "client identity {identity} is not allowed"
+            )));
+        }
+        Ok(ClientCertVerified::assertion())
+    }
+}
+
+/// Source network of an allowlist entry, a single address is a network of its own
+struct Cidr {
+    network: IpAddr,
+    prefix_len: u32,
+}
+
+impl Cidr {
+    fn parse(cidr: &str) -> anyhow::Result<Self> {
+        let (network, prefix_len) = match cidr.split_once('/') {
+            Some((network, prefix_len)) => (
+                network
+                    .parse::<IpAddr>()
+                    .context("invalid network address")?,
+                Some(prefix_len.parse::<u32>().context("invalid prefix length")?),
+            ),
+            None => (cidr.parse::<IpAddr>().context("invalid address")?, None),
+        };
+        let max_prefix_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = prefix_len.unwrap_or(max_prefix_len);
+        if prefix_len > max_prefix_len {
+            bail!("prefix length {prefix_len} is too long");
+        }
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+
+    fn contains(&self, address: IpAddr) -> bool {
+        // clients of a dual stack listener show up as ipv4 mapped addresses
+        let address = match address {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(address, IpAddr::V4),
+            IpAddr::V4(_) => address,
+        };
+        match (self.network, address) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_len).unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_len).unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowlist_matches_identities_and_networks() {
+        let allowed = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let contents = format!("# lite-rpc instances\n\n  {allowed}  \n10.1.0.0/16 2\n");
+        let allowlist = ClientAllowlist::new(Some(ClientAllowlist::parse(&contents).unwrap()));
+        let outside: IpAddr = "192.168.0.1".parse().unwrap();
+        let inside: IpAddr = "::ffff:10.1.2.3".parse().unwrap();
+
+        assert_eq!(
+            allowlist.admit(Some(allowed), inside).unwrap().name(),
+            allowed.to_string()
+        );
+        assert!(allowlist.admit(Some(other), outside).is_none());
+        let network = allowlist.admit(Some(other), inside).unwrap();
+        assert_eq!(network.name(), "10.1.0.0/16");
+        assert_eq!(network.admit_transactions(3), 2);
+        assert_eq!(network.admit_transactions(1), 0);
+
+        assert_eq!(
+            ClientAllowlist::new(None)
+                .admit(None, outside)
+                .unwrap()
+                .admit_transactions(1000),
+            1000
+        );
+        assert!(ClientAllowlist::parse("not a pubkey").is_err());
+        assert!(ClientAllowlist::parse("10.0.0.0/33").is_err());
+    }
+}
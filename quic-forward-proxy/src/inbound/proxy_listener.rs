@@ -1,24 +1,63 @@
+use crate::client_allowlist::{ClientAllowlist, ClientRule};
+use crate::outbound::leader_fanout::LeaderFanout;
 use crate::proxy_request_format::TpuForwardingRequest;
 use crate::quic_util::connection_stats;
-use crate::shared::ForwardPacket;
+use crate::shared::{ForwardPacket, ProxyState};
 use crate::tls_config_provider_server::ProxyTlsConfigProvider;
 use crate::tls_self_signed_pair_generator::SelfSignedTlsConfigProvider;
-use crate::util::FALLBACK_TIMEOUT;
+use crate::util::{timeout_fallback, FALLBACK_TIMEOUT};
 use anyhow::{anyhow, bail, Context};
+use itertools::Itertools;
 use log::{debug, error, info, trace, warn};
-use quinn::{Connecting, Endpoint, ServerConfig, VarInt};
+use prometheus::{opts, register_int_counter, register_int_gauge, IntCounter, IntGauge};
+use quinn::{Connecting, Connection, Endpoint, EndpointConfig, ServerConfig, TokioRuntime, VarInt};
+use solana_lite_rpc_core::structures::proxy_protocol::{
+    negotiate, read_frame, Capabilities, Frame, ALPN_TPU_FORWARDPROXY_PROTOCOL_ID_V2, MAX_FRAME_LEN,
+};
+use solana_lite_rpc_core::udp_socket::bind_server_socket;
 use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::slot_history::Slot;
+use solana_streamer::tls_certificates::get_pubkey_from_tls_certificate;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::{self, Sender, UnboundedReceiver};
+use tokio::sync::watch;
 
 // note: setting this to "1" did not make a difference!
 // solana server sets this to 256
 const MAX_CONCURRENT_UNI_STREAMS: u32 = 24;
+/// capabilities of the proxy protocol implemented by this proxy
+const SERVER_CAPABILITIES: Capabilities = Capabilities::ACKS
+    .union(Capabilities::BATCHING)
+    .union(Capabilities::COMPRESSION);
+/// offered on top of `SERVER_CAPABILITIES` when the proxy knows the leader schedule
+const LEADER_FANOUT_CAPABILITIES: Capabilities =
+    SERVER_CAPABILITIES.union(Capabilities::LEADER_FANOUT);
+// a batch of requests is sent on one stream, a request alone fits into one packet
+const STREAM_RECEIVE_WINDOW: u32 = 512 * 1024;
+/// application error code when closing the connection of a client not in the allowlist
+const CLIENT_NOT_ALLOWED: u32 = 1;
+/// application error code when closing the connection of a client because the proxy drains
+const PROXY_DRAINING: u32 = 2;
+
+lazy_static::lazy_static! {
+    static ref CONNECTED_CLIENTS: IntGauge =
+        register_int_gauge!(opts!("quic_forward_proxy_connected_clients", "Number of clients connected to the proxy")).unwrap();
+    static ref RECEIVED_TRANSACTIONS: IntCounter =
+        register_int_counter!(opts!("quic_forward_proxy_received_transactions", "Number of transactions received from the clients")).unwrap();
+    static ref FAILED_REQUESTS: IntCounter =
+        register_int_counter!(opts!("quic_forward_proxy_failed_requests", "Number of client requests that were invalid, over quota or could not be enqueued")).unwrap();
+    static ref FORWARD_QUEUE_DEPTH: IntGauge =
+        register_int_gauge!(opts!("quic_forward_proxy_forward_queue_depth", "Number of requests waiting for the forwarder")).unwrap();
+}
 
 pub struct ProxyListener {
     tls_config: Arc<SelfSignedTlsConfigProvider>,
+    client_allowlist: Arc<ClientAllowlist>,
+    leader_fanout: Option<Arc<LeaderFanout>>,
+    proxy_state: Arc<ProxyState>,
     proxy_listener_addr: SocketAddr,
 }
 
@@ -26,10 +65,16 @@ impl ProxyListener {
     pub fn new(
         proxy_listener_addr: SocketAddr,
         tls_config: Arc<SelfSignedTlsConfigProvider>,
+        client_allowlist: Arc<ClientAllowlist>,
+        leader_fanout: Option<Arc<LeaderFanout>>,
+        proxy_state: Arc<ProxyState>,
     ) -> Self {
         Self {
             proxy_listener_addr,
             tls_config,
+            client_allowlist,
+            leader_fanout,
+            proxy_state,
         }
     }
 
@@ -43,10 +88,29 @@ impl ProxyListener {
             Self::new_proxy_listen_server_endpoint(&self.tls_config, self.proxy_listener_addr)
                 .await;
 
+        let draining_endpoint = endpoint.clone();
+        let mut draining = self.proxy_state.subscribe_draining();
+        tokio::spawn(async move {
+            wait_for_drain(&mut draining).await;
+            info!("Draining - refusing new clients");
+            draining_endpoint.set_server_config(None);
+        });
+
         while let Some(connecting) = endpoint.accept().await {
             let forwarder_channel_copy = forwarder_channel.clone();
+            let client_allowlist = self.client_allowlist.clone();
+            let leader_fanout = self.leader_fanout.clone();
+            let proxy_state = self.proxy_state.clone();
             tokio::spawn(async move {
-                match Self::handle_client_connection(connecting, forwarder_channel_copy).await {
+                match Self::handle_client_connection(
+                    connecting,
+                    client_allowlist,
+                    leader_fanout,
+                    proxy_state,
+                    forwarder_channel_copy,
+                )
+                .await
+                {
                     Ok(()) => {
                         debug!("connection handled correctly");
                     }
@@ -73,79 +137,341 @@ impl ProxyListener {
         // note: this config must be aligned with lite-rpc's client config
         let transport_config = Arc::get_mut(&mut quinn_server_config.transport).unwrap();
         transport_config.max_concurrent_uni_streams(VarInt::from_u32(MAX_CONCURRENT_UNI_STREAMS));
-        // no bidi streams used
-        transport_config.max_concurrent_bidi_streams(VarInt::from_u32(0));
+        // the control stream of protocol v2
+        transport_config.max_concurrent_bidi_streams(VarInt::from_u32(1));
         let timeout = Duration::from_secs(10).try_into().unwrap();
         transport_config.max_idle_timeout(Some(timeout));
         transport_config.keep_alive_interval(Some(Duration::from_millis(500)));
-        transport_config.stream_receive_window((PACKET_DATA_SIZE as u32).into());
-        transport_config
-            .receive_window((PACKET_DATA_SIZE as u32 * MAX_CONCURRENT_UNI_STREAMS).into());
+        transport_config.stream_receive_window(STREAM_RECEIVE_WINDOW.into());
+        transport_config.receive_window(
+            (PACKET_DATA_SIZE as u32 * MAX_CONCURRENT_UNI_STREAMS + STREAM_RECEIVE_WINDOW).into(),
+        );
 
-        Endpoint::server(quinn_server_config, proxy_listener_addr).unwrap()
+        let socket = bind_server_socket(proxy_listener_addr).unwrap();
+        Endpoint::new(
+            EndpointConfig::default(),
+            Some(quinn_server_config),
+            socket,
+            TokioRuntime,
+        )
+        .unwrap()
+    }
+
+    /// v2 handshake on the bidi stream opened by the client, then the acks
+    async fn serve_control_stream(
+        client_connection: Connection,
+        supported: Capabilities,
+        mut ack_receiver: UnboundedReceiver<Frame>,
+    ) -> anyhow::Result<()> {
+        let (mut send, mut recv) = client_connection.accept_bi().await?;
+        let mut buf = Vec::new();
+        let hello = read_frame(&mut recv, &mut buf)
+            .await?
+            .context("control stream closed before hello")?;
+        let hello_ack = negotiate(&hello, supported)?;
+        send.write_all(&hello_ack.encode()?).await?;
+        debug!(
+            "negotiated {:?} with client {}",
+            hello_ack,
+            client_connection.remote_address()
+        );
+
+        let Frame::HelloAck { capabilities, .. } = hello_ack else {
+            unreachable!("negotiate answers with a hello ack");
+        };
+        while let Some(ack) = ack_receiver.recv().await {
+            if capabilities.contains(Capabilities::ACKS) {
+                send.write_all(&ack.encode()?).await?;
+            }
+        }
+        send.finish().await?;
+        Ok(())
+    }
+
+    async fn forward_request(
+        raw_request: &[u8],
+        client_rule: &ClientRule,
+        forwarder_channel: &Sender<ForwardPacket>,
+    ) -> anyhow::Result<()> {
+        let proxy_request = TpuForwardingRequest::try_deserialize_from_wire_format(raw_request)?;
+
+        if proxy_request.get_tpu_nodes().is_empty() {
+            warn!("no tpu nodes in request - skip");
+            return Ok(());
+        }
+
+        trace!("proxy request details: {}", proxy_request);
+        let tpu_nodes = proxy_request
+            .get_tpu_nodes()
+            .iter()
+            .map(|tpu_node| (tpu_node.identity_tpunode, tpu_node.tpu_socket_addr))
+            .collect_vec();
+        Self::enqueue_transactions(
+            proxy_request.get_transaction_bytes(),
+            &tpu_nodes,
+            client_rule,
+            forwarder_channel,
+        )
+        .await
+    }
+
+    async fn forward_to_leaders(
+        transactions: Vec<Vec<u8>>,
+        (first_slot, last_slot): (Slot, Slot),
+        leader_fanout: Option<&LeaderFanout>,
+        client_rule: &ClientRule,
+        forwarder_channel: &Sender<ForwardPacket>,
+    ) -> anyhow::Result<()> {
+        let Some(leader_fanout) = leader_fanout else {
+            bail!("leader fanout is not enabled on this proxy");
+        };
+        if transactions.is_empty() {
+            warn!("no transactions in request - skip");
+            return Ok(());
+        }
+        let tpu_nodes = leader_fanout.tpu_nodes(first_slot, last_slot).await?;
+        if tpu_nodes.is_empty() {
+            bail!("no tpu address for the leaders of slots {first_slot} to {last_slot}");
+        }
+        Self::enqueue_transactions(transactions, &tpu_nodes, client_rule, forwarder_channel).await
+    }
+
+    async fn enqueue_transactions(
+        mut txs: Vec<Vec<u8>>,
+        tpu_nodes: &[(Pubkey, SocketAddr)],
+        client_rule: &ClientRule,
+        forwarder_channel: &Sender<ForwardPacket>,
+    ) -> anyhow::Result<()> {
+        RECEIVED_TRANSACTIONS.inc_by(txs.len() as u64);
+        let admitted = client_rule.admit_transactions(txs.len());
+        if admitted == 0 {
+            bail!("quota of client {} exceeded", client_rule.name());
+        }
+        if admitted < txs.len() {
+            warn!(
+                "quota of client {} exceeded - forwarding {} of {} transactions",
+                client_rule.name(),
+                admitted,
+                txs.len()
+            );
+            txs.truncate(admitted);
+        }
+
+        debug!(
+            "enqueue transaction batch of size {} to {} tpu nodes",
+            txs.len(),
+            tpu_nodes.len(),
+        );
+        let queue_depth = forwarder_channel.max_capacity() - forwarder_channel.capacity();
+        FORWARD_QUEUE_DEPTH.set(queue_depth as i64);
+        if queue_depth > 0 {
+            debug!(
+                "forward channel buffered: capacity {} of {}",
+                forwarder_channel.capacity(),
+                forwarder_channel.max_capacity()
+            );
+        }
+
+        for (tpu_identity, tpu_address) in tpu_nodes {
+            forwarder_channel
+                .send_timeout(
+                    ForwardPacket::new(txs.clone(), *tpu_address, *tpu_identity),
+                    FALLBACK_TIMEOUT,
+                )
+                .await
+                .context("sending internal packet from proxy to forwarder")?;
+        }
+        Ok(())
     }
 
     #[tracing::instrument(skip_all, level = "debug")]
     async fn handle_client_connection(
         client_conn_handshake: Connecting,
+        client_allowlist: Arc<ClientAllowlist>,
+        leader_fanout: Option<Arc<LeaderFanout>>,
+        proxy_state: Arc<ProxyState>,
         forwarder_channel: Sender<ForwardPacket>,
     ) -> anyhow::Result<()> {
         let client_connection = client_conn_handshake.await.context("handshake")?;
 
+        // verified by the ClientAllowlist during the handshake
+        let client_identity = client_connection
+            .peer_identity()
+            .and_then(|certs| certs.downcast::<Vec<rustls::Certificate>>().ok())
+            .and_then(|certs| certs.first().and_then(get_pubkey_from_tls_certificate));
         debug!(
-            "inbound connection established, client {}",
-            client_connection.remote_address()
+            "inbound connection established, client {} with identity {:?}",
+            client_connection.remote_address(),
+            client_identity
+        );
+
+        // the identities are verified during the handshake, the networks only now
+        let Some(client_rule) =
+            client_allowlist.admit(client_identity, client_connection.remote_address().ip())
+        else {
+            warn!(
+                "Rejected client {} with identity {:?}, not in the allowlist",
+                client_connection.remote_address(),
+                client_identity
+            );
+            client_connection.close(VarInt::from_u32(CLIENT_NOT_ALLOWED), b"client not allowed");
+            return Ok(());
+        };
+
+        CONNECTED_CLIENTS.inc();
+        proxy_state.add_client(
+            client_connection.clone(),
+            client_identity,
+            client_rule.name(),
         );
+        let closed_connection = client_connection.clone();
+        let closed_state = proxy_state.clone();
+        tokio::spawn(async move {
+            closed_connection.closed().await;
+            closed_state.remove_client(&closed_connection);
+            CONNECTED_CLIENTS.dec();
+        });
 
+        let protocol = client_connection
+            .handshake_data()
+            .and_then(|data| data.downcast::<quinn::crypto::rustls::HandshakeData>().ok())
+            .and_then(|data| data.protocol);
+        let framed = protocol.as_deref() == Some(ALPN_TPU_FORWARDPROXY_PROTOCOL_ID_V2);
+        let (ack_sender, ack_receiver) = mpsc::unbounded_channel();
+        let control_stream = framed.then(|| {
+            let client_connection = client_connection.clone();
+            let supported = match leader_fanout {
+                Some(_) => LEADER_FANOUT_CAPABILITIES,
+                None => SERVER_CAPABILITIES,
+            };
+            tokio::spawn(async move {
+                if let Err(e) =
+                    Self::serve_control_stream(client_connection, supported, ack_receiver).await
+                {
+                    warn!("proxy control stream failed: {e:?}");
+                }
+            })
+        });
+
+        let mut draining = proxy_state.subscribe_draining();
+        // every stream task holds a sender until its requests are handed to the forwarder
+        let (in_flight, mut in_flight_done) = mpsc::channel::<()>(1);
         loop {
-            let maybe_stream = client_connection.accept_uni().await;
+            let maybe_stream = tokio::select! {
+                maybe_stream = client_connection.accept_uni() => maybe_stream,
+                _ = wait_for_drain(&mut draining) => {
+                    drop(in_flight);
+                    let _ = in_flight_done.recv().await;
+                    // the control stream ends once the acks of the last requests are sent
+                    drop(ack_sender);
+                    if let Some(control_stream) = control_stream {
+                        let _ = timeout_fallback(control_stream).await;
+                    }
+                    info!(
+                        "Draining - closing connection to client {}",
+                        client_connection.remote_address()
+                    );
+                    client_connection.close(VarInt::from_u32(PROXY_DRAINING), b"proxy draining");
+                    return Ok(());
+                }
+            };
             match maybe_stream {
                 Ok(recv_stream) => {
+                    let in_flight = in_flight.clone();
                     let forwarder_channel_copy = forwarder_channel.clone();
+                    let ack_sender = ack_sender.clone();
+                    let client_rule = client_rule.clone();
+                    let leader_fanout = leader_fanout.clone();
                     tokio::spawn(async move {
-                        let raw_request = recv_stream.read_to_end(10_000_000).await.unwrap();
-
-                        let proxy_request =
-                            TpuForwardingRequest::try_deserialize_from_wire_format(&raw_request)
-                                .unwrap();
-
-                        if proxy_request.get_tpu_nodes().is_empty() {
-                            warn!("no tpu nodes in request - skip");
-                            return;
-                        }
-
-                        trace!("proxy request details: {}", proxy_request);
-                        let txs = proxy_request.get_transaction_bytes();
+                        let result = async {
+                            let raw_request = recv_stream.read_to_end(MAX_FRAME_LEN).await?;
+                            if !framed {
+                                return Self::forward_request(
+                                    &raw_request,
+                                    &client_rule,
+                                    &forwarder_channel_copy,
+                                )
+                                .await;
+                            }
 
-                        debug!(
-                            "enqueue transaction batch of size {} to {} tpu nodes",
-                            txs.len(),
-                            proxy_request.get_tpu_nodes().len(),
-                        );
-                        if forwarder_channel_copy.capacity() < forwarder_channel_copy.max_capacity()
-                        {
-                            debug!(
-                                "forward channel buffered: capacity {} of {}",
-                                forwarder_channel_copy.capacity(),
-                                forwarder_channel_copy.max_capacity()
-                            );
-                        }
+                            let forwarded = match Frame::decode(&raw_request)? {
+                                Some((
+                                    Frame::Forward {
+                                        request_id,
+                                        request,
+                                    },
+                                    _,
+                                )) => {
+                                    let result = Self::forward_request(
+                                        &request,
+                                        &client_rule,
+                                        &forwarder_channel_copy,
+                                    )
+                                    .await;
+                                    vec![(request_id, result)]
+                                }
+                                Some((
+                                    Frame::ForwardBatch {
+                                        first_request_id,
+                                        requests,
+                                    },
+                                    _,
+                                )) => {
+                                    let mut forwarded = Vec::with_capacity(requests.len());
+                                    for (request_id, request) in (first_request_id..).zip(requests)
+                                    {
+                                        let result = Self::forward_request(
+                                            &request,
+                                            &client_rule,
+                                            &forwarder_channel_copy,
+                                        )
+                                        .await;
+                                        forwarded.push((request_id, result));
+                                    }
+                                    forwarded
+                                }
+                                Some((
+                                    Frame::ForwardToLeaders {
+                                        request_id,
+                                        first_slot,
+                                        last_slot,
+                                        transactions,
+                                    },
+                                    _,
+                                )) => {
+                                    let result = Self::forward_to_leaders(
+                                        transactions,
+                                        (first_slot, last_slot),
+                                        leader_fanout.as_deref(),
+                                        &client_rule,
+                                        &forwarder_channel_copy,
+                                    )
+                                    .await;
+                                    vec![(request_id, result)]
+                                }
+                                _ => bail!("expected a forward frame"),
+                            };
 
-                        for tpu_node in proxy_request.get_tpu_nodes() {
-                            let tpu_address = tpu_node.tpu_socket_addr;
-                            forwarder_channel_copy
-                                .send_timeout(
-                                    ForwardPacket::new(
-                                        txs.clone(),
-                                        tpu_address,
-                                        proxy_request.get_hash(),
-                                    ),
-                                    FALLBACK_TIMEOUT,
-                                )
-                                .await
-                                .context("sending internal packet from proxy to forwarder")
-                                .unwrap();
+                            for (request_id, result) in forwarded {
+                                // the control stream drops it unless acks were negotiated
+                                let _ = ack_sender.send(Frame::Ack {
+                                    request_id,
+                                    accepted: result.is_ok(),
+                                });
+                                if let Err(e) = result {
+                                    FAILED_REQUESTS.inc();
+                                    warn!(
+                                        "failed handling proxy request {request_id}: {e:?} - skip"
+                                    );
+                                }
+                            }
+                            Ok(())
+                        };
+                        if let Err(e) = result.await {
+                            FAILED_REQUESTS.inc();
+                            warn!("failed handling proxy request: {e:?} - skip");
                         }
+                        drop(in_flight);
                     });
 
                     debug!(
@@ -172,3 +498,12 @@ impl ProxyListener {
         } // -- loop
     }
 }
+
+/// resolves once the proxy drains, never if the admin interface is gone
+async fn wait_for_drain(draining: &mut watch::Receiver<bool>) {
+    while !*draining.borrow_and_update() {
+        if draining.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
@@ -1,13 +1,14 @@
 // lib definition is only required for 'quic-forward-proxy-integration-test' to work
 
+pub mod admin;
 mod cli;
+pub mod client_allowlist;
 mod inbound;
-mod outbound;
+pub mod outbound;
 pub mod proxy;
 pub mod proxy_request_format;
 mod quic_util;
-mod quinn_auto_reconnect;
-mod shared;
+pub mod shared;
 pub mod tls_config_provider_client;
 pub mod tls_config_provider_server;
 pub mod tls_self_signed_pair_generator;
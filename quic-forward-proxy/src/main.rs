@@ -1,22 +1,30 @@
+use crate::admin::ProxyAdmin;
 use crate::cli::Args;
+use crate::client_allowlist::ClientAllowlist;
+use crate::outbound::leader_fanout::LeaderFanout;
+use crate::outbound::retry_buffer::RetryPolicy;
 use crate::proxy::QuicForwardProxy;
+use crate::shared::ProxyState;
 use crate::tls_self_signed_pair_generator::SelfSignedTlsConfigProvider;
 use anyhow::bail;
 use clap::Parser;
 use dotenv::dotenv;
-use log::info;
+use log::{info, warn};
 use solana_lite_rpc_core::keypair_loader::load_identity_keypair;
+use solana_lite_rpc_core::prometheus_sync::PrometheusSync;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::validator_identity::ValidatorIdentity;
 
+pub mod admin;
 pub mod cli;
+mod client_allowlist;
 mod inbound;
 mod outbound;
 pub mod proxy;
 pub mod proxy_request_format;
 pub mod quic_util;
-mod quinn_auto_reconnect;
 mod shared;
 pub mod tls_config_provider_client;
 pub mod tls_config_provider_server;
@@ -31,16 +39,88 @@ pub async fn main() -> anyhow::Result<()> {
     let Args {
         identity_keypair,
         proxy_listen_addr,
+        client_allowlist,
+        allow_any_client,
+        prometheus_addr,
+        rpc_url,
+        retry_window_ms,
+        retry_buffer_size,
+        admin_addr,
+        admin_token,
     } = Args::parse();
     dotenv().ok();
 
+    let admin_addr = match (admin_addr, admin_token) {
+        (Some(admin_addr), Some(admin_token)) => Some((admin_addr, admin_token)),
+        (Some(_), None) => bail!("--admin-addr requires an --admin-token"),
+        (None, _) => None,
+    };
+
     let proxy_listener_addr = proxy_listen_addr.parse().unwrap();
     let validator_identity = ValidatorIdentity::new(load_identity_keypair(&identity_keypair).await);
 
-    let tls_config = Arc::new(SelfSignedTlsConfigProvider::new_singleton_self_signed_localhost());
-    let main_services = QuicForwardProxy::new(proxy_listener_addr, tls_config, validator_identity)
-        .await?
-        .start_services();
+    let client_allowlist = match client_allowlist {
+        Some(path) => {
+            let client_allowlist = ClientAllowlist::load(&path)?;
+            info!(
+                "Accepting the clients of {} allowlist entries from {path}",
+                client_allowlist.allowed_count().unwrap_or_default()
+            );
+            client_allowlist
+        }
+        None if allow_any_client => {
+            warn!("No client allowlist configured, any client can send through the proxy");
+            ClientAllowlist::new(None)
+        }
+        None => bail!("No --client-allowlist given, pass --allow-any-client to accept any client"),
+    };
+    let tls_config = Arc::new(SelfSignedTlsConfigProvider::new_for_identity(
+        &validator_identity.get_keypair_for_tls(),
+        client_allowlist.clone(),
+    )?);
+    info!(
+        "Proxy presents identity {} to its clients",
+        validator_identity.get_pubkey()
+    );
+    let leader_fanout = rpc_url.map(|rpc_url| {
+        info!("Clients may leave the leader fanout to the proxy, leaders from {rpc_url}");
+        LeaderFanout::new(rpc_url)
+    });
+    let proxy_state = ProxyState::new(validator_identity);
+    let main_services = QuicForwardProxy::new(
+        proxy_listener_addr,
+        tls_config,
+        client_allowlist,
+        leader_fanout,
+        RetryPolicy {
+            window: Duration::from_millis(retry_window_ms),
+            capacity: retry_buffer_size,
+        },
+        proxy_state.clone(),
+    )
+    .await?
+    .start_services();
+
+    let admin = async {
+        match admin_addr {
+            Some((admin_addr, admin_token)) => {
+                ProxyAdmin::new(proxy_state, identity_keypair)
+                    .start(admin_addr, admin_token)
+                    .await
+            }
+            None => std::future::pending().await,
+        }
+    };
+
+    let prometheus = async {
+        match prometheus_addr {
+            Some(prometheus_addr) => {
+                info!("Serving prometheus metrics on {prometheus_addr}");
+                PrometheusSync::sync(prometheus_addr).await
+            }
+            None => std::future::pending().await,
+        }
+    };
 
     let ctrl_c_signal = tokio::signal::ctrl_c();
 
@@ -48,6 +128,12 @@ pub async fn main() -> anyhow::Result<()> {
         res = main_services => {
             bail!("Services quit unexpectedly {res:?}");
         },
+        res = prometheus => {
+            bail!("Prometheus service quit unexpectedly {res:?}");
+        },
+        res = admin => {
+            bail!("Admin service quit unexpectedly {res:?}");
+        },
         // res = test_client => {
         //     bail!("Test Client quit unexpectedly {res:?}");
         // },
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use itertools::Itertools;
+use log::{info, warn};
+use solana_lite_rpc_cluster_endpoints::json_rpc_leaders_getter::JsonRpcLeaderGetter;
+use solana_lite_rpc_core::traits::leaders_fetcher_interface::LeaderFetcherInterface;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::quic::QUIC_PORT_OFFSET;
+use solana_sdk::slot_history::Slot;
+use tokio::sync::RwLock;
+
+// same leader cache as lite-rpc
+const LEADERS_TO_CACHE: u64 = 1024;
+const OLD_LEADERS_TO_CACHE: u64 = 128;
+/// longest slot range a client may ask for
+const MAX_FANOUT_SLOTS: u64 = 100;
+const CLUSTER_NODES_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Resolves the tpus of the leaders of a slot range for clients sending with leader fanout
+pub struct LeaderFanout {
+    // none if the tpu addresses are fixed
+    rpc_client: Option<Arc<RpcClient>>,
+    leader_schedule: Arc<dyn LeaderFetcherInterface>,
+    tpu_addresses: RwLock<HashMap<Pubkey, SocketAddr>>,
+}
+
+impl LeaderFanout {
+    pub fn new(rpc_url: String) -> Arc<Self> {
+        let rpc_client = Arc::new(RpcClient::new(rpc_url));
+        Arc::new(Self {
+            leader_schedule: Arc::new(JsonRpcLeaderGetter::new(
+                rpc_client.clone(),
+                LEADERS_TO_CACHE,
+                OLD_LEADERS_TO_CACHE,
+            )),
+            rpc_client: Some(rpc_client),
+            tpu_addresses: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// fixed tpu addresses, e.g. for a local test cluster
+    pub fn new_static(
+        leader_schedule: Arc<dyn LeaderFetcherInterface>,
+        tpu_addresses: HashMap<Pubkey, SocketAddr>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            rpc_client: None,
+            leader_schedule,
+            tpu_addresses: RwLock::new(tpu_addresses),
+        })
+    }
+
+    /// keeps the tpu addresses of the cluster nodes up to date
+    pub async fn refresh_cluster_nodes(&self, exit_signal: Arc<AtomicBool>) -> anyhow::Result<()> {
+        let Some(rpc_client) = &self.rpc_client else {
+            return std::future::pending().await;
+        };
+        loop {
+            if exit_signal.load(Ordering::Relaxed) {
+                bail!("exit signal received");
+            }
+            match rpc_client.get_cluster_nodes().await {
+                Ok(cluster_nodes) => {
+                    let tpu_addresses: HashMap<_, _> = cluster_nodes
+                        .into_iter()
+                        .filter_map(|node| {
+                            let identity = Pubkey::from_str(&node.pubkey).ok()?;
+                            let mut tpu_address = node.tpu?;
+                            // add quic port offset
+                            tpu_address.set_port(tpu_address.port() + QUIC_PORT_OFFSET);
+                            Some((identity, tpu_address))
+                        })
+                        .collect();
+                    info!("Leader fanout knows {} tpu addresses", tpu_addresses.len());
+                    *self.tpu_addresses.write().await = tpu_addresses;
+                }
+                Err(e) => warn!("Failed to get the cluster nodes: {e:?}"),
+            }
+            tokio::time::sleep(CLUSTER_NODES_REFRESH_INTERVAL).await;
+        }
+    }
+
+    /// tpus of the leaders from `first_slot` to `last_slot`, at most `MAX_FANOUT_SLOTS`
+    pub async fn tpu_nodes(
+        &self,
+        first_slot: Slot,
+        last_slot: Slot,
+    ) -> anyhow::Result<Vec<(Pubkey, SocketAddr)>> {
+        if last_slot < first_slot {
+            bail!("invalid slot range {first_slot}..{last_slot}");
+        }
+        // slots come from the client, don't trust them to stay in range
+        let last_slot = last_slot.clamp(
+            first_slot.saturating_add(1),
+            first_slot.saturating_add(MAX_FANOUT_SLOTS),
+        );
+        let leaders = self
+            .leader_schedule
+            .get_slot_leaders(first_slot, last_slot)
+            .await
+            .context("leader schedule")?;
+
+        let tpu_addresses = self.tpu_addresses.read().await;
+        Ok(leaders
+            .into_iter()
+            .map(|leader| leader.pubkey)
+            .unique()
+            .filter_map(|leader| {
+                tpu_addresses
+                    .get(&leader)
+                    .map(|tpu_address| (leader, *tpu_address))
+            })
+            .collect())
+    }
+}
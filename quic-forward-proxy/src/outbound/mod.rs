@@ -1,3 +1,4 @@
 mod debouncer;
-mod sharder;
+pub mod leader_fanout;
+pub mod retry_buffer;
 pub mod tx_forward;
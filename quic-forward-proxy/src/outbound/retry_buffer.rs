@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How long and how many transactions are kept per tpu while it is unreachable
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// counted from the first attempt, no retries if zero
+    pub window: Duration,
+    pub capacity: usize,
+}
+
+/// Why a transaction left the buffer without being sent
+#[derive(Debug, PartialEq, Eq)]
+pub enum Discarded {
+    /// the retry window passed
+    Expired,
+    /// the buffer was full
+    Dropped,
+}
+
+/// Transactions waiting for another attempt, oldest first
+pub struct RetryBuffer {
+    policy: RetryPolicy,
+    queue: VecDeque<(Instant, Vec<u8>)>,
+}
+
+impl RetryBuffer {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            policy,
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// keeps a transaction that failed, returns what had to be discarded for it; a full buffer
+    /// makes room by dropping the oldest transaction
+    pub fn push(&mut self, first_attempt: Instant, transaction: Vec<u8>) -> Option<Discarded> {
+        if first_attempt.elapsed() >= self.policy.window {
+            return Some(Discarded::Expired);
+        }
+        if self.policy.capacity == 0 {
+            return Some(Discarded::Dropped);
+        }
+        let dropped = if self.queue.len() >= self.policy.capacity {
+            self.queue.pop_front();
+            Some(Discarded::Dropped)
+        } else {
+            None
+        };
+        self.queue.push_back((first_attempt, transaction));
+        dropped
+    }
+
+    /// empties the buffer, returns the transactions to retry and the number of expired ones
+    pub fn take_retries(&mut self) -> (Vec<(Instant, Vec<u8>)>, usize) {
+        let window = self.policy.window;
+        let (retries, expired): (Vec<_>, Vec<_>) = self
+            .queue
+            .drain(..)
+            .partition(|(first_attempt, _)| first_attempt.elapsed() < window);
+        (retries, expired.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_buffer_drops_oldest_and_expires() {
+        let mut buffer = RetryBuffer::new(RetryPolicy {
+            window: Duration::from_secs(60),
+            capacity: 2,
+        });
+        let now = Instant::now();
+        assert_eq!(buffer.push(now, vec![1]), None);
+        assert_eq!(buffer.push(now, vec![2]), None);
+        assert_eq!(buffer.push(now, vec![3]), Some(Discarded::Dropped));
+        let mut no_retries = RetryBuffer::new(RetryPolicy {
+            window: Duration::ZERO,
+            capacity: 2,
+        });
+        assert_eq!(no_retries.push(now, vec![4]), Some(Discarded::Expired));
+
+        let (retries, expired) = buffer.take_retries();
+        assert_eq!(
+            retries.into_iter().map(|(_, tx)| tx).collect::<Vec<_>>(),
+            vec![vec![2], vec![3]]
+        );
+        assert_eq!(expired, 0);
+        assert!(buffer.is_empty());
+    }
+}
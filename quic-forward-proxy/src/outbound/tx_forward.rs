@@ -1,330 +1,367 @@
 use crate::outbound::debouncer::Debouncer;
-use crate::outbound::sharder::Sharder;
-use crate::quic_util::SkipServerVerification;
-use crate::quinn_auto_reconnect::AutoReconnect;
-use crate::shared::ForwardPacket;
-use crate::util::timeout_fallback;
+use crate::outbound::retry_buffer::{Discarded, RetryBuffer, RetryPolicy};
+use crate::shared::{ForwardPacket, ProxyState, TpuPoolEntry};
 use crate::validator_identity::ValidatorIdentity;
 use anyhow::{bail, Context};
-use futures::future::join_all;
-use log::{debug, info, trace, warn};
-use quinn::{
-    ClientConfig, Endpoint, EndpointConfig, IdleTimeout, TokioRuntime, TransportConfig, VarInt,
+use log::{debug, info, warn};
+use prometheus::{
+    opts, register_int_counter, register_int_counter_vec, register_int_gauge, IntCounter,
+    IntCounterVec, IntGauge,
 };
-use solana_sdk::quic::QUIC_MAX_TIMEOUT;
-use solana_streamer::nonblocking::quic::ALPN_TPU_PROTOCOL_ID;
-use solana_streamer::tls_certificates::new_self_signed_tls_certificate;
+use quinn::Endpoint;
+use solana_lite_rpc_core::identity_signer::QuicClientIdentity;
+use solana_lite_rpc_core::quic_connection::QuicConnectionPool;
+use solana_lite_rpc_core::quic_connection_utils::{QuicConnectionParameters, QuicConnectionUtils};
+use solana_lite_rpc_core::structures::rotating_queue::RotatingQueue;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::quic::QUIC_MAX_UNSTAKED_CONCURRENT_STREAMS;
 use std::collections::HashMap;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::mpsc::Receiver;
-use tokio::sync::RwLock;
+use tokio::sync::mpsc::{self, Receiver, Sender, UnboundedSender};
 
-const MAX_PARALLEL_STREAMS: usize = 6;
 pub const PARALLEL_TPU_CONNECTION_COUNT: usize = 4;
-const AGENT_SHUTDOWN_IDLE: Duration = Duration::from_millis(2500); // ms; should be 4x400ms+buffer
-
-struct AgentHandle {
-    pub tpu_address: SocketAddr,
-    pub agent_exit_signal: Arc<AtomicBool>,
-    pub last_used_at: Arc<RwLock<Instant>>,
-}
-
-impl AgentHandle {
-    pub async fn touch(&self) {
-        let mut timestamp = self.last_used_at.write().await;
-        *timestamp = Instant::now();
-    }
+// the stake of the proxy identity is unknown, stay within the limit of unstaked peers
+const MAX_UNI_STREAMS_PER_CONNECTION: usize = QUIC_MAX_UNSTAKED_CONCURRENT_STREAMS;
+// a tpu is part of the fanout of every request for the slots before and while it leads
+const POOL_SHUTDOWN_IDLE: Duration = Duration::from_secs(5);
+const POOL_QUEUE_SIZE: usize = 10_000;
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+lazy_static::lazy_static! {
+    static ref FORWARDED_TRANSACTIONS: IntCounter =
+        register_int_counter!(opts!("quic_forward_proxy_forwarded_transactions", "Number of transactions sent to the TPUs")).unwrap();
+    static ref TPU_TRANSACTIONS: IntCounterVec =
+        register_int_counter_vec!(opts!("quic_forward_proxy_tpu_transactions", "Outcome of the transactions per TPU identity: sent, expired or dropped"), &["tpu", "outcome"]).unwrap();
+    static ref RETRIED_TRANSACTIONS: IntCounter =
+        register_int_counter!(opts!("quic_forward_proxy_retried_transactions", "Number of attempts to resend a transaction from the retry buffer")).unwrap();
+    static ref RETRY_BUFFER_DEPTH: IntGauge =
+        register_int_gauge!(opts!("quic_forward_proxy_retry_buffer_depth", "Number of transactions waiting for their TPU to become reachable")).unwrap();
+    static ref WARM_POOLS: IntGauge =
+        register_int_gauge!(opts!("quic_forward_proxy_warm_pools", "Number of TPUs the proxy keeps connections to")).unwrap();
+    static ref TPU_QUEUE_DEPTH: IntGauge =
+        register_int_gauge!(opts!("quic_forward_proxy_tpu_queue_depth", "Number of transactions waiting for a connection to their TPU")).unwrap();
 }
 
-// takes transactions from upstream clients and forwards them to the TPU
-pub async fn tx_forwarder(
-    validator_identity: ValidatorIdentity,
-    mut transaction_channel: Receiver<ForwardPacket>,
-    exit_signal: Arc<AtomicBool>,
-) -> anyhow::Result<()> {
-    info!("TPU Quic forwarder started");
-
-    let endpoint = new_endpoint_with_validator_identity(validator_identity).await;
-
-    let (broadcast_in, _) = tokio::sync::broadcast::channel::<Arc<ForwardPacket>>(1024);
-
-    let mut agents: HashMap<SocketAddr, AgentHandle> = HashMap::new();
-    let agent_shutdown_debouncer = Debouncer::new(Duration::from_millis(200));
+// a single short attempt per send, the retry buffer retries an unreachable tpu within the
+// retry window
+const CONNECTION_PARAMETERS: QuicConnectionParameters = QuicConnectionParameters {
+    connection_timeout: Duration::from_millis(250),
+    unistream_timeout: Duration::from_millis(500),
+    write_timeout: Duration::from_millis(1000),
+    finalize_timeout: Duration::from_millis(200),
+    connection_retry_count: 1,
+    max_number_of_connections: PARALLEL_TPU_CONNECTION_COUNT,
+    number_of_transactions_per_unistream: 1,
+};
 
-    loop {
-        if exit_signal.load(Ordering::Relaxed) {
-            bail!("exit signal received");
-        }
+/// Connections to one tpu, warmed up when the tpu first shows up in a request
+struct WarmPool {
+    // dropped on eviction, the connections close once the queued transactions are sent
+    sender: Sender<(Instant, Vec<u8>)>,
+    tpu_identity: Pubkey,
+    last_requested_at: Instant,
+}
 
-        let forward_packet = Arc::new(
-            transaction_channel
-                .recv()
-                .await
-                .expect("channel closed unexpectedly"),
+impl WarmPool {
+    fn start(
+        tpu_identity: Pubkey,
+        tpu_address: SocketAddr,
+        endpoints: RotatingQueue<Endpoint>,
+        retry_policy: RetryPolicy,
+        exit_signal: Arc<AtomicBool>,
+    ) -> Self {
+        let pool = QuicConnectionPool::new(
+            tpu_identity,
+            endpoints,
+            tpu_address,
+            CONNECTION_PARAMETERS,
+            exit_signal.clone(),
+            PARALLEL_TPU_CONNECTION_COUNT,
+            MAX_UNI_STREAMS_PER_CONNECTION,
         );
-        let tpu_address = forward_packet.tpu_address;
+        let (sender, mut receiver) = mpsc::channel::<(Instant, Vec<u8>)>(POOL_QUEUE_SIZE);
 
-        agents.entry(tpu_address).or_insert_with(|| {
-            let agent_exit_signal = Arc::new(AtomicBool::new(false));
-
-            for connection_idx in 0..PARALLEL_TPU_CONNECTION_COUNT {
-                let sharder =
-                    Sharder::new(connection_idx as u32, PARALLEL_TPU_CONNECTION_COUNT as u32);
-                let global_exit_signal = exit_signal.clone();
-                let endpoint_copy = endpoint.clone();
-                let agent_exit_signal_copy = agent_exit_signal.clone();
-                let mut per_connection_receiver = broadcast_in.subscribe();
-                tokio::spawn(async move {
-                    debug!(
-                        "Start Quic forwarder agent #{} for TPU {}",
-                        connection_idx, tpu_address
-                    );
-                    // get a copy of the packet from broadcast channel
-                    let auto_connection = AutoReconnect::new(endpoint_copy, tpu_address);
-
-                    // TODO check exit signal (using select! or maybe replace with oneshot)
-                    let _exit_signal_copy = global_exit_signal.clone();
-                    'tx_channel_loop: loop {
-                        let timeout_result = timeout_fallback(per_connection_receiver.recv()).await;
+        // in the background so an unreachable tpu does not hold up its queue
+        let warm_up_pool = pool.clone();
+        tokio::spawn(async move {
+            debug!("Warming up connections to TPU {tpu_identity} at {tpu_address}");
+            warm_up_pool.warm_up().await;
+        });
 
-                        let maybe_packet = match timeout_result {
-                            Ok(recv) => recv,
-                            Err(_elapsed) => continue 'tx_channel_loop,
+        tokio::spawn(async move {
+            let tpu_label = tpu_identity.to_string();
+            let (retry_sender, mut retry_receiver) = mpsc::unbounded_channel();
+            let mut retry_buffer = RetryBuffer::new(retry_policy);
+            let mut retry_interval = tokio::time::interval(RETRY_INTERVAL);
+
+            // ends once the pool is evicted and the queued transactions are sent
+            loop {
+                if exit_signal.load(Ordering::Relaxed) {
+                    break;
+                }
+                tokio::select! {
+                    transaction = receiver.recv() => {
+                        let Some((first_attempt, transaction)) = transaction else {
+                            break;
                         };
-
-                        if global_exit_signal.load(Ordering::Relaxed) {
-                            warn!("Caught global exit signal, {} remaining - stopping agent thread",
-                                per_connection_receiver.len());
-                            break 'tx_channel_loop;
-                        }
-                        if agent_exit_signal_copy.load(Ordering::Relaxed) {
-                            if per_connection_receiver.is_empty() {
-                                debug!("Caught exit signal for this agent ({} #{}) - stopping agent thread",
-                                    tpu_address, connection_idx);
-                                break 'tx_channel_loop;
-                            } else if auto_connection.is_permanent_dead().await {
-                                info!("Caught exit signal for this agent ({} #{}), {} remaining but connection is dead - stopping",
-                                tpu_address, connection_idx,
-                                per_connection_receiver.len());
-                                break 'tx_channel_loop;
-                            } else {
-                                trace!("Caught exit signal for this agent ({} #{}), {} remaining - continue",
-                                tpu_address, connection_idx,
-                                per_connection_receiver.len());
+                        Self::send(&pool, &tpu_label, transaction, first_attempt, &retry_sender)
+                            .await;
+                    }
+                    Some((first_attempt, transaction)) = retry_receiver.recv() => {
+                        let size_before = retry_buffer.len();
+                        match retry_buffer.push(first_attempt, transaction) {
+                            Some(Discarded::Expired) => {
+                                Self::count_outcome(&tpu_label, "expired", 1)
                             }
-
+                            Some(Discarded::Dropped) => {
+                                Self::count_outcome(&tpu_label, "dropped", 1)
+                            }
+                            None => {}
                         }
-
-                        if let Err(_recv_error) = maybe_packet {
-                            break 'tx_channel_loop;
+                        RETRY_BUFFER_DEPTH.add(retry_buffer.len() as i64 - size_before as i64);
+                    }
+                    _ = retry_interval.tick(), if !retry_buffer.is_empty() => {
+                        RETRY_BUFFER_DEPTH.sub(retry_buffer.len() as i64);
+                        let (retries, expired) = retry_buffer.take_retries();
+                        Self::count_outcome(&tpu_label, "expired", expired as u64);
+                        RETRIED_TRANSACTIONS.inc_by(retries.len() as u64);
+                        for (first_attempt, transaction) in retries {
+                            Self::send(
+                                &pool,
+                                &tpu_label,
+                                transaction,
+                                first_attempt,
+                                &retry_sender,
+                            )
+                            .await;
                         }
+                    }
+                }
+            }
 
-                        let packet = maybe_packet.unwrap();
+            // nobody retries them once the pool is gone
+            RETRY_BUFFER_DEPTH.sub(retry_buffer.len() as i64);
+            Self::count_outcome(&tpu_label, "dropped", retry_buffer.len() as u64);
+            debug!("Connections to TPU {tpu_identity} at {tpu_address} shut down");
+        });
 
-                        if packet.tpu_address != tpu_address {
-                            continue 'tx_channel_loop;
-                        }
-                        if !sharder.matching(packet.shard_hash) {
-                            continue 'tx_channel_loop;
-                        }
+        Self {
+            sender,
+            tpu_identity,
+            last_requested_at: Instant::now(),
+        }
+    }
 
-                        if auto_connection.is_permanent_dead().await {
-                            warn!("Agent ({} #{}) connection permanently dead, {} remaining - stopping",
-                                tpu_address, connection_idx,
-                                per_connection_receiver.len());
-                            break 'tx_channel_loop;
-                        }
+    /// a transaction that could not be sent goes back to the pool task for the retry buffer
+    async fn send(
+        pool: &QuicConnectionPool,
+        tpu_label: &str,
+        transaction: Vec<u8>,
+        first_attempt: Instant,
+        retry_sender: &UnboundedSender<(Instant, Vec<u8>)>,
+    ) {
+        let pooled = match pool.get_pooled_connection().await {
+            Ok(pooled) => pooled,
+            Err(e) => {
+                debug!("No connection to TPU {tpu_label}: {e:?}");
+                let _ = retry_sender.send((first_attempt, transaction));
+                return;
+            }
+        };
+        let tpu_label = tpu_label.to_string();
+        let retry_sender = retry_sender.clone();
+        tokio::spawn(async move {
+            if pooled
+                .connection
+                .send_transaction(transaction.clone())
+                .await
+            {
+                FORWARDED_TRANSACTIONS.inc();
+                Self::count_outcome(&tpu_label, "sent", 1);
+            } else {
+                let _ = retry_sender.send((first_attempt, transaction));
+            }
+            drop(pooled.permit);
+        });
+    }
 
-                        let mut transactions_batch: Vec<Vec<u8>> = packet.transactions.clone();
+    fn count_outcome(tpu_label: &str, outcome: &str, count: u64) {
+        if count > 0 {
+            TPU_TRANSACTIONS
+                .with_label_values(&[tpu_label, outcome])
+                .inc_by(count);
+        }
+    }
+}
 
-                        'more: while let Ok(more) = per_connection_receiver.try_recv() {
-                            if more.tpu_address != tpu_address {
-                                continue 'more;
-                            }
-                            if !sharder.matching(more.shard_hash) {
-                                continue 'more;
-                            }
-                            transactions_batch.extend(more.transactions.clone());
-                        }
+// takes transactions from upstream clients and forwards them to the TPU
+pub async fn tx_forwarder(
+    proxy_state: Arc<ProxyState>,
+    mut transaction_channel: Receiver<ForwardPacket>,
+    retry_policy: RetryPolicy,
+    exit_signal: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    info!("TPU Quic forwarder started");
 
-                        debug!(
-                            "forwarding transaction batch of size {} to address {}",
-                            transactions_batch.len(),
-                            packet.tpu_address
-                        );
+    let mut validator_identity = proxy_state.subscribe_validator_identity();
+    let mut endpoints =
+        new_endpoints_with_validator_identity(validator_identity.borrow_and_update().clone())?;
 
-                        let result = timeout_fallback(send_tx_batch_to_tpu(
-                            &auto_connection,
-                            &transactions_batch,
-                        ))
-                        .await
-                        .context(format!(
-                            "send txs to tpu node {}",
-                            auto_connection.target_address
-                        ));
+    let mut pools: HashMap<SocketAddr, WarmPool> = HashMap::new();
+    let pool_shutdown_debouncer = Debouncer::new(Duration::from_millis(200));
 
-                        match result {
-                            Ok(()) => {
-                                debug!("send_txs_to_tpu_static sent {}", transactions_batch.len());
-                                debug!(
-                                    "Outbound connection stats: {}",
-                                    &auto_connection.connection_stats().await
-                                );
-                            }
-                            Err(err) => {
-                                warn!("got send_txs_to_tpu_static error {} - loop over errors", err);
-                            }
-                        }
-                    } // -- while all packtes from channel
+    loop {
+        if exit_signal.load(Ordering::Relaxed) {
+            bail!("exit signal received");
+        }
 
+        let forward_packet = transaction_channel
+            .recv()
+            .await
+            .context("channel closed unexpectedly")?;
+
+        // reloaded by the admin interface
+        if validator_identity.has_changed().unwrap_or(false) {
+            match new_endpoints_with_validator_identity(
+                validator_identity.borrow_and_update().clone(),
+            ) {
+                Ok(new_endpoints) => {
+                    endpoints = new_endpoints;
+                    // the evicted pools send what they have queued with the previous identity
+                    pools.clear();
+                }
+                Err(e) => warn!("Keeping the previous validator identity: {e:?}"),
+            }
+        }
 
-                    auto_connection.force_shutdown().await;
-                    warn!(
-                        "Quic forwarder agent #{} for TPU {} exited; shut down connection",
-                        connection_idx, tpu_address
-                    );
-                }); // -- spawned thread for one connection to one TPU
-            } // -- for parallel connections to one TPU
+        let tpu_address = forward_packet.tpu_address;
 
-            let now = Instant::now();
-            AgentHandle {
+        let pool = pools.entry(tpu_address).or_insert_with(|| {
+            WarmPool::start(
+                forward_packet.tpu_identity,
                 tpu_address,
-                agent_exit_signal,
-                last_used_at: Arc::new(RwLock::new(now))
-            }
-        }); // -- new agent
-
-        let agent = agents.get(&tpu_address).unwrap();
-        agent.touch().await;
+                endpoints.clone(),
+                retry_policy,
+                exit_signal.clone(),
+            )
+        });
+        pool.last_requested_at = Instant::now();
 
-        if agent_shutdown_debouncer.can_fire() {
-            cleanup_agents(&mut agents, &tpu_address).await;
+        debug!(
+            "forwarding transaction batch of size {} to address {}",
+            forward_packet.transactions.len(),
+            tpu_address
+        );
+        for transaction in forward_packet.transactions {
+            if pool
+                .sender
+                .try_send((forward_packet.received_at, transaction))
+                .is_err()
+            {
+                warn!("Queue to TPU {tpu_address} is full - dropping transaction");
+                WarmPool::count_outcome(&forward_packet.tpu_identity.to_string(), "dropped", 1);
+            }
         }
 
-        if broadcast_in.len() > 5 {
-            debug!("tx-forward queue len: {}", broadcast_in.len())
+        if pool_shutdown_debouncer.can_fire() {
+            shutdown_idle_pools(&mut pools);
+            WARM_POOLS.set(pools.len() as i64);
+            TPU_QUEUE_DEPTH.set(
+                pools
+                    .values()
+                    .map(|pool| POOL_QUEUE_SIZE - pool.sender.capacity())
+                    .sum::<usize>() as i64,
+            );
+            proxy_state.set_tpu_pools(
+                pools
+                    .iter()
+                    .map(|(tpu_address, pool)| TpuPoolEntry {
+                        tpu_identity: pool.tpu_identity,
+                        tpu_address: *tpu_address,
+                        last_requested_at: pool.last_requested_at,
+                        queued: POOL_QUEUE_SIZE - pool.sender.capacity(),
+                    })
+                    .collect(),
+            );
         }
-
-        broadcast_in
-            .send(forward_packet)
-            .expect("send must succeed");
     } // -- loop over transactions from upstream channels
-
-    // not reachable
 }
 
-async fn cleanup_agents(
-    agents: &mut HashMap<SocketAddr, AgentHandle>,
-    current_tpu_address: &SocketAddr,
-) {
-    let now = Instant::now();
-    let mut to_shutdown = Vec::new();
-    for (tpu_address, handle) in &*agents {
-        if tpu_address == current_tpu_address {
-            continue;
+fn shutdown_idle_pools(pools: &mut HashMap<SocketAddr, WarmPool>) {
+    pools.retain(|tpu_address, pool| {
+        let unused_period = pool.last_requested_at.elapsed();
+        if unused_period <= POOL_SHUTDOWN_IDLE {
+            return true;
         }
-
-        let unused_period = {
-            let last_used_at = handle.last_used_at.read().await;
-            now - *last_used_at
-        };
-
-        if unused_period > AGENT_SHUTDOWN_IDLE {
-            to_shutdown.push(tpu_address.to_owned())
-        }
-    }
-
-    for tpu_address in to_shutdown.iter() {
-        if let Some(removed_agent) = agents.remove(tpu_address) {
-            let was_signaled = removed_agent
-                .agent_exit_signal
-                .compare_exchange(false, true, Ordering::SeqCst, Ordering::Relaxed)
-                .is_ok();
-            if was_signaled {
-                let unused_period = {
-                    let last_used_ts = removed_agent.last_used_at.read().await;
-                    Instant::now() - *last_used_ts
-                };
-                debug!(
-                    "Idle Agent for tpu node {} idle for {}ms - sending exit signal",
-                    removed_agent.tpu_address,
-                    unused_period.as_millis()
-                );
-            }
-        }
-    }
+        debug!(
+            "Connections to tpu node {} idle for {}ms - shutting down",
+            tpu_address,
+            unused_period.as_millis()
+        );
+        false
+    });
 }
 
-/// takes a validator identity and creates a new QUIC client; appears as staked peer to TPU
+/// endpoints presenting the validator identity; the proxy appears as staked peer to the TPU
 // note: ATM the provided identity might or might not be a valid validator keypair
-async fn new_endpoint_with_validator_identity(validator_identity: ValidatorIdentity) -> Endpoint {
+fn new_endpoints_with_validator_identity(
+    validator_identity: ValidatorIdentity,
+) -> anyhow::Result<RotatingQueue<Endpoint>> {
     info!(
         "Setup TPU Quic stable connection with validator identity {} ...",
         validator_identity
     );
     // the counterpart of this function is get_remote_pubkey+get_pubkey_from_tls_certificate
-    let (certificate, key) = new_self_signed_tls_certificate(
-        &validator_identity.get_keypair_for_tls(),
-        IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
-    )
-    .expect("Failed to initialize QUIC connection certificates");
-
-    create_tpu_client_endpoint(certificate, key)
-}
-
-fn create_tpu_client_endpoint(
-    certificate: rustls::Certificate,
-    key: rustls::PrivateKey,
-) -> Endpoint {
-    let mut endpoint = {
-        let client_socket =
-            solana_net_utils::bind_in_range(IpAddr::V4(Ipv4Addr::UNSPECIFIED), (8000, 10000))
-                .expect("create_endpoint bind_in_range")
-                .1;
-        let config = EndpointConfig::default();
-        quinn::Endpoint::new(config, None, client_socket, TokioRuntime)
-            .expect("create_endpoint quinn::Endpoint::new")
-    };
-
-    let mut crypto = rustls::ClientConfig::builder()
-        .with_safe_defaults()
-        .with_custom_certificate_verifier(SkipServerVerification::new())
-        .with_single_cert(vec![certificate], key)
-        .expect("Failed to set QUIC client certificates");
-
-    crypto.enable_early_data = true;
-
-    crypto.alpn_protocols = vec![ALPN_TPU_PROTOCOL_ID.to_vec()];
-
-    let mut config = ClientConfig::new(Arc::new(crypto));
-
-    // note: this should be aligned with solana quic server's endpoint config
-    let mut transport_config = TransportConfig::default();
-    // no remotely-initiated streams required
-    transport_config.max_concurrent_uni_streams(VarInt::from_u32(0));
-    transport_config.max_concurrent_bidi_streams(VarInt::from_u32(0));
-    let timeout = IdleTimeout::try_from(QUIC_MAX_TIMEOUT).unwrap();
-    transport_config.max_idle_timeout(Some(timeout));
-    transport_config.keep_alive_interval(Some(Duration::from_millis(500)));
-
-    config.transport_config(Arc::new(transport_config));
-
-    endpoint.set_default_client_config(config);
+    let client_identity = Arc::new(
+        QuicClientIdentity::new(validator_identity.get_keypair_for_tls())
+            .context("Failed to initialize QUIC connection certificates")?,
+    );
 
-    endpoint
+    Ok(RotatingQueue::new(PARALLEL_TPU_CONNECTION_COUNT, || {
+        QuicConnectionUtils::create_endpoint(client_identity.clone())
+    }))
 }
 
-// send potentially large amount of transactions to a single TPU
-#[tracing::instrument(skip_all, level = "debug")]
-async fn send_tx_batch_to_tpu(auto_connection: &AutoReconnect, txs: &[Vec<u8>]) {
-    for chunk in txs.chunks(MAX_PARALLEL_STREAMS) {
-        let all_send_fns = chunk.iter().map(|tx_raw| {
-            auto_connection.send_uni(tx_raw) // ignores error
-        });
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+
+    #[tokio::test]
+    async fn unreachable_tpu_is_retried_within_the_window() {
+        // bound but never answering
+        let tpu_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let retry_policy = RetryPolicy {
+            window: Duration::from_secs(2),
+            capacity: 10,
+        };
+        let exit_signal = Arc::new(AtomicBool::new(false));
+        let pool = WarmPool::start(
+            Pubkey::new_unique(),
+            tpu_socket.local_addr().unwrap(),
+            new_endpoints_with_validator_identity(ValidatorIdentity::new(None)).unwrap(),
+            retry_policy,
+            exit_signal.clone(),
+        );
 
-        join_all(all_send_fns).await;
+        let retried_before = RETRIED_TRANSACTIONS.get();
+        let first_attempt = Instant::now();
+        pool.sender
+            .send((first_attempt, vec![1, 2, 3]))
+            .await
+            .unwrap();
+        while RETRIED_TRANSACTIONS.get() == retried_before
+            && first_attempt.elapsed() < retry_policy.window
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(
+            RETRIED_TRANSACTIONS.get() > retried_before,
+            "no retry within the retry window"
+        );
+        exit_signal.store(true, Ordering::Relaxed);
     }
 }
@@ -4,17 +4,23 @@ use anyhow::bail;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
+use crate::client_allowlist::ClientAllowlist;
 use crate::inbound::proxy_listener;
+use crate::outbound::leader_fanout::LeaderFanout;
+use crate::outbound::retry_buffer::RetryPolicy;
 use crate::outbound::tx_forward::tx_forwarder;
+use crate::shared::ProxyState;
 use crate::tls_self_signed_pair_generator::SelfSignedTlsConfigProvider;
 use crate::util::AnyhowJoinHandle;
-use crate::validator_identity::ValidatorIdentity;
 use log::info;
 
 pub struct QuicForwardProxy {
     // endpoint: Endpoint,
-    validator_identity: ValidatorIdentity,
+    proxy_state: Arc<ProxyState>,
     tls_config: Arc<SelfSignedTlsConfigProvider>,
+    client_allowlist: Arc<ClientAllowlist>,
+    leader_fanout: Option<Arc<LeaderFanout>>,
+    retry_policy: RetryPolicy,
     pub proxy_listener_addr: SocketAddr,
 }
 
@@ -22,14 +28,23 @@ impl QuicForwardProxy {
     pub async fn new(
         proxy_listener_addr: SocketAddr,
         tls_config: Arc<SelfSignedTlsConfigProvider>,
-        validator_identity: ValidatorIdentity,
+        client_allowlist: Arc<ClientAllowlist>,
+        leader_fanout: Option<Arc<LeaderFanout>>,
+        retry_policy: RetryPolicy,
+        proxy_state: Arc<ProxyState>,
     ) -> anyhow::Result<Self> {
-        info!("Quic proxy uses validator identity {}", validator_identity);
+        info!(
+            "Quic proxy uses validator identity {}",
+            proxy_state.validator_identity()
+        );
 
         Ok(Self {
             proxy_listener_addr,
-            validator_identity,
+            proxy_state,
             tls_config,
+            client_allowlist,
+            leader_fanout,
+            retry_policy,
         })
     }
 
@@ -38,8 +53,13 @@ impl QuicForwardProxy {
 
         let (forwarder_channel, forward_receiver) = tokio::sync::mpsc::channel(1000);
 
-        let proxy_listener =
-            proxy_listener::ProxyListener::new(self.proxy_listener_addr, self.tls_config);
+        let proxy_listener = proxy_listener::ProxyListener::new(
+            self.proxy_listener_addr,
+            self.tls_config,
+            self.client_allowlist,
+            self.leader_fanout.clone(),
+            self.proxy_state.clone(),
+        );
 
         let quic_proxy = tokio::spawn(async move {
             proxy_listener
@@ -48,14 +68,23 @@ impl QuicForwardProxy {
                 .expect("proxy listen service");
         });
 
-        let validator_identity = self.validator_identity.clone();
         let exit_signal_clone = exit_signal.clone();
         let forwarder: AnyhowJoinHandle = tokio::spawn(tx_forwarder(
-            validator_identity,
+            self.proxy_state,
             forward_receiver,
+            self.retry_policy,
             exit_signal_clone,
         ));
 
+        let leader_fanout = self.leader_fanout;
+        let exit_signal_clone = exit_signal.clone();
+        let cluster_nodes: AnyhowJoinHandle = tokio::spawn(async move {
+            match leader_fanout {
+                Some(leader_fanout) => leader_fanout.refresh_cluster_nodes(exit_signal_clone).await,
+                None => std::future::pending().await,
+            }
+        });
+
         tokio::select! {
             res = quic_proxy => {
                 bail!("TPU Quic Proxy server exited unexpectedly {res:?}");
@@ -63,6 +92,9 @@ impl QuicForwardProxy {
             res = forwarder => {
                 bail!("TPU Quic Tx forwarder exited unexpectedly {res:?}");
             },
+            res = cluster_nodes => {
+                bail!("Cluster nodes refresh exited unexpectedly {res:?}");
+            },
         }
     }
 }
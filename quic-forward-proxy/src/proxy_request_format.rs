@@ -22,7 +22,7 @@ pub struct TxData(Signature, Vec<u8>);
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TpuNode {
     pub tpu_socket_addr: SocketAddr,
-    pub identity_tpunode: Pubkey, // note: this is only used for debugging
+    pub identity_tpunode: Pubkey, // note: only labels the connections to the tpu
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
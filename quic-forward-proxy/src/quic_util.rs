@@ -1,7 +1,9 @@
 use quinn::Connection;
 use std::sync::Arc;
 
-pub const ALPN_TPU_FORWARDPROXY_PROTOCOL_ID: &[u8] = b"solana-tpu-forward-proxy";
+pub use solana_lite_rpc_core::structures::proxy_protocol::{
+    ALPN_TPU_FORWARDPROXY_PROTOCOL_ID, ALPN_TPU_FORWARDPROXY_PROTOCOL_ID_V2,
+};
 
 pub struct SkipServerVerification;
 
@@ -1,20 +1,171 @@
+use crate::validator_identity::ValidatorIdentity;
+use dashmap::DashMap;
+use quinn::Connection;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
 use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::sync::watch;
 
 /// internal structure with transactions and target TPU
 #[derive(Debug)]
 pub struct ForwardPacket {
     pub transactions: Vec<Vec<u8>>,
     pub tpu_address: SocketAddr,
-    pub shard_hash: u64,
+    pub tpu_identity: Pubkey,
+    /// when the proxy received the transactions, the retry window starts here
+    pub received_at: Instant,
 }
 
 impl ForwardPacket {
-    pub fn new(transactions: Vec<Vec<u8>>, tpu_address: SocketAddr, hash: u64) -> Self {
+    pub fn new(transactions: Vec<Vec<u8>>, tpu_address: SocketAddr, tpu_identity: Pubkey) -> Self {
         assert!(!transactions.is_empty(), "no transactions");
         Self {
             transactions,
             tpu_address,
-            shard_hash: hash,
+            tpu_identity,
+            received_at: Instant::now(),
+        }
+    }
+}
+
+/// State read and controlled by the admin interface, shared with the listener and the forwarder
+pub struct ProxyState {
+    draining: watch::Sender<bool>,
+    validator_identity: watch::Sender<ValidatorIdentity>,
+    // by stable id of the connection
+    clients: DashMap<usize, ClientEntry>,
+    // snapshot taken by the forwarder
+    tpu_pools: RwLock<Vec<TpuPoolEntry>>,
+}
+
+struct ClientEntry {
+    connection: Connection,
+    identity: Option<Pubkey>,
+    allowlist_entry: String,
+}
+
+pub struct TpuPoolEntry {
+    pub tpu_identity: Pubkey,
+    pub tpu_address: SocketAddr,
+    pub last_requested_at: Instant,
+    pub queued: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientState {
+    pub remote_address: SocketAddr,
+    pub identity: Option<String>,
+    pub allowlist_entry: String,
+    pub rtt_ms: u128,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TpuPoolState {
+    pub tpu_identity: String,
+    pub tpu_address: SocketAddr,
+    pub idle_ms: u128,
+    pub queued: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionState {
+    pub draining: bool,
+    pub identity: String,
+    pub clients: Vec<ClientState>,
+    pub tpu_pools: Vec<TpuPoolState>,
+}
+
+impl ProxyState {
+    pub fn new(validator_identity: ValidatorIdentity) -> Arc<Self> {
+        Arc::new(Self {
+            draining: watch::channel(false).0,
+            validator_identity: watch::channel(validator_identity).0,
+            clients: DashMap::new(),
+            tpu_pools: RwLock::new(Vec::new()),
+        })
+    }
+
+    /// stops accepting clients, the connected ones are closed once their requests are
+    /// forwarded
+    pub fn drain(&self) {
+        self.draining.send_replace(true);
+    }
+
+    pub fn subscribe_draining(&self) -> watch::Receiver<bool> {
+        self.draining.subscribe()
+    }
+
+    pub fn validator_identity(&self) -> ValidatorIdentity {
+        self.validator_identity.borrow().clone()
+    }
+
+    /// new connections to the tpus use `validator_identity`
+    pub fn set_validator_identity(&self, validator_identity: ValidatorIdentity) {
+        self.validator_identity.send_replace(validator_identity);
+    }
+
+    pub fn subscribe_validator_identity(&self) -> watch::Receiver<ValidatorIdentity> {
+        self.validator_identity.subscribe()
+    }
+
+    pub fn add_client(
+        &self,
+        connection: Connection,
+        identity: Option<Pubkey>,
+        allowlist_entry: &str,
+    ) {
+        self.clients.insert(
+            connection.stable_id(),
+            ClientEntry {
+                connection,
+                identity,
+                allowlist_entry: allowlist_entry.to_string(),
+            },
+        );
+    }
+
+    pub fn remove_client(&self, connection: &Connection) {
+        self.clients.remove(&connection.stable_id());
+    }
+
+    pub fn set_tpu_pools(&self, tpu_pools: Vec<TpuPoolEntry>) {
+        *self.tpu_pools.write().unwrap() = tpu_pools;
+    }
+
+    pub fn connection_state(&self) -> ConnectionState {
+        let clients = self
+            .clients
+            .iter()
+            .map(|client| ClientState {
+                remote_address: client.connection.remote_address(),
+                identity: client.identity.map(|identity| identity.to_string()),
+                allowlist_entry: client.allowlist_entry.clone(),
+                rtt_ms: client.connection.rtt().as_millis(),
+            })
+            .collect();
+        let tpu_pools = self
+            .tpu_pools
+            .read()
+            .unwrap()
+            .iter()
+            .map(|pool| TpuPoolState {
+                tpu_identity: pool.tpu_identity.to_string(),
+                tpu_address: pool.tpu_address,
+                idle_ms: pool.last_requested_at.elapsed().as_millis(),
+                queued: pool.queued,
+            })
+            .collect();
+
+        ConnectionState {
+            draining: *self.draining.borrow(),
+            identity: self.validator_identity().to_string(),
+            clients,
+            tpu_pools,
         }
     }
 }
@@ -1,8 +1,16 @@
-use crate::quic_util::{SkipServerVerification, ALPN_TPU_FORWARDPROXY_PROTOCOL_ID};
+use crate::quic_util::{
+    SkipServerVerification, ALPN_TPU_FORWARDPROXY_PROTOCOL_ID, ALPN_TPU_FORWARDPROXY_PROTOCOL_ID_V2,
+};
 use crate::tls_config_provider_client::TpuClientTlsConfigProvider;
 use crate::tls_config_provider_server::ProxyTlsConfigProvider;
+use anyhow::Context;
 use rcgen::generate_simple_self_signed;
+use rustls::server::{ClientCertVerifier, NoClientAuth};
 use rustls::{Certificate, ClientConfig, PrivateKey, ServerConfig};
+use solana_sdk::signature::Keypair;
+use solana_streamer::tls_certificates::new_self_signed_tls_certificate;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
 
 impl ProxyTlsConfigProvider for SelfSignedTlsConfigProvider {
     fn get_server_tls_crypto_config(&self) -> ServerConfig {
@@ -26,13 +34,29 @@ impl SelfSignedTlsConfigProvider {
         // note: this check could be relaxed when you know what you are doing!
         let hostnames = vec!["localhost".to_string()];
         let (certificate, private_key) = Self::gen_tls_certificate_and_key(hostnames);
-        let server_crypto = Self::build_server_crypto(certificate, private_key);
+        let server_crypto =
+            Self::build_server_crypto(certificate, private_key, NoClientAuth::boxed());
         Self {
             client_crypto: Self::build_client_crypto_insecure(),
             server_crypto,
         }
     }
 
+    /// presents the self signed certificate of `identity`, so clients can pin the proxy
+    /// identity, and authenticates the clients with `client_verifier`
+    pub fn new_for_identity(
+        identity: &Keypair,
+        client_verifier: Arc<dyn ClientCertVerifier>,
+    ) -> anyhow::Result<Self> {
+        let (certificate, private_key) =
+            new_self_signed_tls_certificate(identity, IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+                .context("Failed to create the proxy certificate")?;
+        Ok(Self {
+            client_crypto: Self::build_client_crypto_insecure(),
+            server_crypto: Self::build_server_crypto(certificate, private_key, client_verifier),
+        })
+    }
+
     fn gen_tls_certificate_and_key(hostnames: Vec<String>) -> (Certificate, PrivateKey) {
         let cert = generate_simple_self_signed(hostnames).unwrap();
         let key = cert.serialize_private_key_der();
@@ -50,16 +74,21 @@ impl SelfSignedTlsConfigProvider {
         client_crypto
     }
 
-    fn build_server_crypto(server_cert: Certificate, server_key: PrivateKey) -> ServerConfig {
-        // let (server_cert, server_key) = gen_tls_certificate_and_key();
-
+    fn build_server_crypto(
+        server_cert: Certificate,
+        server_key: PrivateKey,
+        client_verifier: Arc<dyn ClientCertVerifier>,
+    ) -> ServerConfig {
         let mut server_crypto = rustls::ServerConfig::builder()
-            // FIXME we want client auth
             .with_safe_defaults()
-            .with_no_client_auth()
+            .with_client_cert_verifier(client_verifier)
             .with_single_cert(vec![server_cert], server_key)
             .unwrap();
-        server_crypto.alpn_protocols = vec![ALPN_TPU_FORWARDPROXY_PROTOCOL_ID.to_vec()];
+        // preferred first, the protocol version follows from the one chosen
+        server_crypto.alpn_protocols = vec![
+            ALPN_TPU_FORWARDPROXY_PROTOCOL_ID_V2.to_vec(),
+            ALPN_TPU_FORWARDPROXY_PROTOCOL_ID.to_vec(),
+        ];
         server_crypto
     }
 
@@ -0,0 +1,134 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context};
+use log::{info, warn};
+use prometheus::{core::GenericGauge, opts, register_int_gauge};
+use solana_lite_rpc_core::{
+    structures::produced_block::ProducedBlock, types::BlockStream, AnyhowJoinHandle,
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+use tokio::sync::broadcast::error::RecvError;
+
+lazy_static::lazy_static! {
+    static ref CLOCK_SKEW_MS: GenericGauge<prometheus::core::AtomicI64> =
+    register_int_gauge!(opts!("literpc_clock_skew_ms", "Local clock minus the time of the last confirmed block")).unwrap();
+}
+
+/// Compares the local clock to the time of the confirmed blocks
+///
+/// The block time is the stake weighted time voted by the validators, a confirmed block is
+/// around a second old when received and its time has a resolution of a second, so a skew of
+/// a couple of seconds is expected. A larger skew breaks the blockhash expiry estimation and
+/// the latency metrics.
+#[derive(Clone, Copy)]
+pub struct ClockSkewMonitor {
+    max_skew: Duration,
+}
+
+impl ClockSkewMonitor {
+    pub fn new(max_skew: Duration) -> Self {
+        Self { max_skew }
+    }
+
+    /// local clock minus the block time in milliseconds, none for blocks without time
+    pub fn clock_skew_ms(block: &ProducedBlock, now: SystemTime) -> Option<i64> {
+        if block.block_time == 0 {
+            return None;
+        }
+        let now_ms = now.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64;
+        Some(now_ms - block.block_time as i64 * 1000)
+    }
+
+    fn exceeds_max_skew(&self, skew_ms: i64) -> bool {
+        skew_ms.unsigned_abs() > self.max_skew.as_millis() as u64
+    }
+
+    async fn next_skew_ms(block_stream: &mut BlockStream) -> anyhow::Result<i64> {
+        loop {
+            let block = match block_stream.recv().await {
+                Ok(block) => block,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(e) => return Err(e).context("Error receiving blocks"),
+            };
+            if block.commitment_config != CommitmentConfig::confirmed() {
+                continue;
+            }
+            if let Some(skew_ms) = Self::clock_skew_ms(&block, SystemTime::now()) {
+                CLOCK_SKEW_MS.set(skew_ms);
+                return Ok(skew_ms);
+            }
+        }
+    }
+
+    /// measures the skew on the next confirmed block, fails if it is too large and `refuse_to_start`
+    pub async fn check_at_startup(
+        &self,
+        mut block_stream: BlockStream,
+        refuse_to_start: bool,
+    ) -> anyhow::Result<()> {
+        let skew_ms = Self::next_skew_ms(&mut block_stream).await?;
+        if !self.exceeds_max_skew(skew_ms) {
+            info!("Clock skew to the cluster is {skew_ms}ms");
+            return Ok(());
+        }
+
+        if refuse_to_start {
+            bail!(
+                "Clock skew to the cluster is {skew_ms}ms, more than {:?}, fix the system clock",
+                self.max_skew
+            );
+        }
+        warn!(
+            "Clock skew to the cluster is {skew_ms}ms, more than {:?}, blockhash expiry and latency metrics will be off",
+            self.max_skew
+        );
+        Ok(())
+    }
+
+    /// keeps measuring the skew, warns when it goes above the max and when it is back under
+    pub fn start(self, mut block_stream: BlockStream) -> AnyhowJoinHandle {
+        tokio::spawn(async move {
+            let mut skewed = false;
+            loop {
+                let skew_ms = Self::next_skew_ms(&mut block_stream).await?;
+                match (skewed, self.exceeds_max_skew(skew_ms)) {
+                    (false, true) => warn!(
+                        "Clock skew to the cluster is {skew_ms}ms, more than {:?}",
+                        self.max_skew
+                    ),
+                    (true, false) => info!("Clock skew to the cluster is back to {skew_ms}ms"),
+                    _ => {}
+                }
+                skewed = self.exceeds_max_skew(skew_ms);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skew_is_local_clock_minus_block_time() {
+        let block = ProducedBlock {
+            block_time: 1_000,
+            ..Default::default()
+        };
+        let now = UNIX_EPOCH + Duration::from_millis(1_002_500);
+        assert_eq!(ClockSkewMonitor::clock_skew_ms(&block, now), Some(2_500));
+
+        let behind = UNIX_EPOCH + Duration::from_millis(990_000);
+        assert_eq!(
+            ClockSkewMonitor::clock_skew_ms(&block, behind),
+            Some(-10_000)
+        );
+
+        let without_time = ProducedBlock::default();
+        assert_eq!(ClockSkewMonitor::clock_skew_ms(&without_time, now), None);
+
+        let monitor = ClockSkewMonitor::new(Duration::from_secs(5));
+        assert!(!monitor.exceeds_max_skew(2_500));
+        assert!(monitor.exceeds_max_skew(-10_000));
+    }
+}
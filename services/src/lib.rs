@@ -1,6 +1,8 @@
+pub mod clock_skew;
 pub mod data_caching_service;
 pub mod metrics_capture;
-pub mod prometheus_sync;
+pub use solana_lite_rpc_core::prometheus_sync;
+pub mod supervisor;
 pub mod tpu_utils;
 pub mod transaction_replayer;
 pub mod transaction_service;
@@ -3,12 +3,17 @@ use std::sync::Arc;
 use log::info;
 use prometheus::{core::GenericGauge, opts, register_int_gauge};
 use serde::{Deserialize, Serialize};
-use solana_lite_rpc_core::stores::tx_store::TxStore;
+use solana_lite_rpc_core::stores::data_cache::DataCache;
 use solana_transaction_status::TransactionConfirmationStatus;
 use tokio::{sync::RwLock, task::JoinHandle};
 
 lazy_static::lazy_static! {
     static ref TXS_IN_STORE: GenericGauge<prometheus::core::AtomicI64> = register_int_gauge!(opts!("literpc_txs_in_store", "Transactions in store")).unwrap();
+    static ref TXS_STORE_BYTES: GenericGauge<prometheus::core::AtomicI64> = register_int_gauge!(opts!("literpc_txs_store_estimated_bytes", "Estimated memory used by transaction store in bytes")).unwrap();
+    static ref BLOCKS_IN_STORE: GenericGauge<prometheus::core::AtomicI64> = register_int_gauge!(opts!("literpc_blocks_in_store", "Block informations in store")).unwrap();
+    static ref BLOCKS_STORE_BYTES: GenericGauge<prometheus::core::AtomicI64> = register_int_gauge!(opts!("literpc_blocks_store_estimated_bytes", "Estimated memory used by block information store in bytes")).unwrap();
+    static ref SIGNATURE_SUBSCRIPTIONS: GenericGauge<prometheus::core::AtomicI64> = register_int_gauge!(opts!("literpc_signature_subscriptions", "Signature subscriptions in store")).unwrap();
+    static ref SIGNATURE_SUBSCRIPTIONS_BYTES: GenericGauge<prometheus::core::AtomicI64> = register_int_gauge!(opts!("literpc_signature_subscriptions_estimated_bytes", "Estimated memory used by signature subscription store in bytes")).unwrap();
 }
 
 #[cfg(all(tokio_unstable, not(loom)))]
@@ -26,7 +31,7 @@ lazy_static::lazy_static! {
 /// Background worker which captures metrics
 #[derive(Clone)]
 pub struct MetricsCapture {
-    txs_store: TxStore,
+    data_cache: DataCache,
     metrics: Arc<RwLock<Metrics>>,
 }
 
@@ -41,9 +46,9 @@ pub struct Metrics {
 }
 
 impl MetricsCapture {
-    pub fn new(txs_store: TxStore) -> Self {
+    pub fn new(data_cache: DataCache) -> Self {
         Self {
-            txs_store,
+            data_cache,
             metrics: Default::default(),
         }
     }
@@ -65,11 +70,12 @@ impl MetricsCapture {
             loop {
                 one_second.tick().await;
 
-                let txs_sent = self.txs_store.len();
+                let txs_store = &self.data_cache.txs;
+                let txs_sent = txs_store.len();
                 let mut txs_confirmed: usize = 0;
                 let mut txs_finalized: usize = 0;
 
-                for tx in self.txs_store.store.iter() {
+                for tx in txs_store.store.iter() {
                     if let Some(tx) = &tx.value().status {
                         match tx.confirmation_status() {
                             TransactionConfirmationStatus::Confirmed => txs_confirmed += 1,
@@ -96,6 +102,15 @@ impl MetricsCapture {
                 metrics.txs_confirmed = txs_confirmed;
                 metrics.txs_finalized = txs_finalized;
                 TXS_IN_STORE.set(txs_sent as i64);
+                TXS_STORE_BYTES.set(txs_store.estimated_size_in_bytes() as i64);
+
+                let block_store = &self.data_cache.block_information_store;
+                BLOCKS_IN_STORE.set(block_store.number_of_blocks_in_store() as i64);
+                BLOCKS_STORE_BYTES.set(block_store.estimated_size_in_bytes() as i64);
+
+                let tx_subs = &self.data_cache.tx_subs;
+                SIGNATURE_SUBSCRIPTIONS.set(tx_subs.number_of_subscribers() as i64);
+                SIGNATURE_SUBSCRIPTIONS_BYTES.set(tx_subs.estimated_size_in_bytes() as i64);
 
                 #[cfg(all(tokio_unstable, not(loom)))]
                 #[cfg_attr(docsrs, doc(cfg(tokio_unstable)))]
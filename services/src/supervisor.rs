@@ -0,0 +1,130 @@
+use std::time::{Duration, Instant};
+
+use log::{error, info};
+use prometheus::{opts, register_int_counter_vec, IntCounterVec};
+use solana_lite_rpc_core::AnyhowJoinHandle;
+
+lazy_static::lazy_static! {
+    static ref SERVICE_RESTARTS: IntCounterVec =
+    register_int_counter_vec!(opts!("literpc_service_restarts", "Number of restarts of a supervised service"), &["service"]).unwrap();
+}
+
+/// Backoff between the restarts of a supervised service
+#[derive(Debug, Clone, Copy)]
+pub struct RestartPolicy {
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+    /// the backoff goes back to `min_backoff` once the service ran that long without failing
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            reset_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// backoff before the next restart, given the previous one and how long the service ran
+    fn next_backoff(&self, previous: Option<Duration>, ran_for: Duration) -> Duration {
+        match previous {
+            Some(previous) if ran_for < self.reset_after => (previous * 2).min(self.max_backoff),
+            _ => self.min_backoff,
+        }
+    }
+}
+
+/// aborts the task when dropped, so a task aborted while awaiting it does not leave it running
+struct AbortOnDrop(AnyhowJoinHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Restarts the service spawned by `spawn` every time it returns or panics, so a failing
+/// service does not take down the whole process. The returned handle never resolves,
+/// aborting it aborts the running service too.
+pub fn supervise<F>(name: &'static str, policy: RestartPolicy, mut spawn: F) -> AnyhowJoinHandle
+where
+    F: FnMut() -> AnyhowJoinHandle + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut backoff = None;
+        loop {
+            let started_at = Instant::now();
+            let mut service = AbortOnDrop(spawn());
+            match (&mut service.0).await {
+                Ok(Ok(())) => error!("Service {name} stopped"),
+                Ok(Err(e)) => error!("Service {name} failed {e:?}"),
+                Err(e) => error!("Service {name} panicked {e:?}"),
+            }
+            let delay = policy.next_backoff(backoff, started_at.elapsed());
+            backoff = Some(delay);
+
+            info!("Restarting service {name} in {delay:?}");
+            tokio::time::sleep(delay).await;
+            SERVICE_RESTARTS.with_label_values(&[name]).inc();
+        }
+    })
+}
+
+/// Single handle over a group of tasks restarted together, resolves with the first task
+/// that returns and aborts the others
+pub fn join_group(tasks: Vec<AnyhowJoinHandle>) -> AnyhowJoinHandle {
+    tokio::spawn(async move {
+        if tasks.is_empty() {
+            return std::future::pending().await;
+        }
+        let mut tasks = tasks.into_iter().map(AbortOnDrop).collect::<Vec<_>>();
+        let (res, _, _) =
+            futures::future::select_all(tasks.iter_mut().map(|task| &mut task.0)).await;
+        res?
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn aborting_the_supervisor_aborts_the_service() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<()>(1);
+        let supervisor = supervise("test", RestartPolicy::default(), move || {
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                let _sender = sender;
+                std::future::pending().await
+            })
+        });
+        tokio::task::yield_now().await;
+        supervisor.abort();
+        // the channel closes once the service holding the last sender is dropped
+        tokio::time::timeout(Duration::from_secs(1), receiver.recv())
+            .await
+            .expect("service still running after its supervisor was aborted");
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max_and_resets_after_a_long_run() {
+        let policy = RestartPolicy::default();
+        let short_run = Duration::from_secs(1);
+
+        let mut backoff = policy.next_backoff(None, short_run);
+        assert_eq!(backoff, policy.min_backoff);
+        for _ in 0..10 {
+            backoff = policy.next_backoff(Some(backoff), short_run);
+        }
+        assert_eq!(backoff, policy.max_backoff);
+
+        assert_eq!(
+            policy.next_backoff(Some(backoff), policy.reset_after),
+            policy.min_backoff
+        );
+    }
+}
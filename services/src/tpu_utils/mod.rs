@@ -1,6 +1,8 @@
 pub mod tpu_service;
 
+#[cfg(feature = "quic-proxy")]
 pub mod quic_proxy_connection_manager;
+#[cfg(feature = "quic-proxy")]
 pub mod quinn_auto_reconnect;
 pub mod tpu_connection_manager;
 pub mod tpu_connection_path;
@@ -1,27 +1,37 @@
 use std::collections::HashMap;
 use std::net::{SocketAddr, UdpSocket};
-use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::{Arc, Mutex};
 
-use anyhow::bail;
+use anyhow::{bail, Context};
+use solana_lite_rpc_core::structures::shutdown::until_deadline;
 use solana_lite_rpc_core::structures::transaction_sent_info::SentTransactionInfo;
 use std::time::Duration;
 
 use itertools::Itertools;
 use log::{debug, info, trace, warn};
+use prometheus::{opts, register_int_counter, register_int_gauge_vec, IntCounter, IntGaugeVec};
 use quinn::{ClientConfig, Endpoint, EndpointConfig, TokioRuntime, TransportConfig, VarInt};
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::slot_history::Slot;
 
 use tokio::sync::broadcast::error::TryRecvError;
-use tokio::sync::{broadcast::Receiver, RwLock};
+use tokio::sync::{broadcast::Receiver, Notify, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Instant};
 
+use solana_lite_rpc_core::identity_signer::QuicClientIdentity;
 use solana_lite_rpc_core::quic_connection_utils::{
-    QuicConnectionParameters, SkipServerVerification,
+    PinnedServerVerification, QuicConnectionParameters, SkipServerVerification,
+};
+use solana_lite_rpc_core::structures::proxy_protocol::{
+    Capabilities, ALPN_TPU_FORWARDPROXY_PROTOCOL_ID, ALPN_TPU_FORWARDPROXY_PROTOCOL_ID_V2,
 };
 use solana_lite_rpc_core::structures::proxy_request_format::{TpuForwardingRequest, TxData};
 
 use crate::tpu_utils::quinn_auto_reconnect::AutoReconnect;
+use crate::tpu_utils::tpu_connection_path::ProxyBalancePolicy;
 
 #[derive(Clone, Copy, Debug)]
 pub struct TpuNode {
@@ -29,36 +39,164 @@ pub struct TpuNode {
     pub tpu_address: SocketAddr,
 }
 
+/// where the transactions go during the current slot
+#[derive(Clone, Debug, Default)]
+struct Fanout {
+    tpu_nodes: Vec<TpuNode>,
+    /// first and last slot of the leaders in `tpu_nodes`, for proxies resolving them
+    slots: (Slot, Slot),
+}
+
+lazy_static::lazy_static! {
+    static ref PROXY_HEALTHY: IntGaugeVec =
+    register_int_gauge_vec!(opts!("literpc_quic_proxy_healthy", "1 if the quic forward proxy is reachable"), &["proxy"]).unwrap();
+    static ref PROXY_FAILOVERS: IntCounter =
+    register_int_counter!(opts!("literpc_quic_proxy_failovers", "Number of batches sent to another proxy after a failure")).unwrap();
+}
+
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct ProxyTarget {
+    connection: AutoReconnect,
+    healthy: AtomicBool,
+}
+
+impl ProxyTarget {
+    fn set_healthy(&self, healthy: bool) {
+        let address = self.connection.target_address;
+        if self.healthy.swap(healthy, Relaxed) != healthy {
+            if healthy {
+                info!("Quic proxy {address} is healthy");
+            } else {
+                warn!("Quic proxy {address} is unhealthy");
+            }
+        }
+        PROXY_HEALTHY
+            .with_label_values(&[&address.to_string()])
+            .set(healthy as i64);
+    }
+}
+
+/// Proxies in the order they are tried for the next batch
+struct ProxyTargets {
+    targets: Vec<ProxyTarget>,
+    policy: ProxyBalancePolicy,
+    round: AtomicUsize,
+}
+
+impl ProxyTargets {
+    fn next_candidates(&self) -> Vec<&ProxyTarget> {
+        let healthy = self
+            .targets
+            .iter()
+            .map(|target| target.healthy.load(Relaxed))
+            .collect_vec();
+        let round = self.round.fetch_add(1, Relaxed);
+        candidate_order(self.policy, &healthy, round)
+            .into_iter()
+            .map(|index| &self.targets[index])
+            .collect()
+    }
+
+    async fn health_check(&self, exit_signal: Arc<AtomicBool>) {
+        while !exit_signal.load(Relaxed) {
+            for target in &self.targets {
+                target.connection.revive().await;
+                let healthy = timeout(HEALTH_CHECK_TIMEOUT, target.connection.refresh_and_get())
+                    .await
+                    .map_or(false, |connection| connection.is_ok());
+                target.set_healthy(healthy);
+            }
+            tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+        }
+    }
+}
+
+/// indexes of the proxies to try in order: the healthy ones as given by the policy, then the
+/// unhealthy ones as a last resort
+fn candidate_order(policy: ProxyBalancePolicy, healthy: &[bool], round: usize) -> Vec<usize> {
+    let (mut candidates, unhealthy): (Vec<usize>, Vec<usize>) =
+        (0..healthy.len()).partition(|index| healthy[*index]);
+    if policy == ProxyBalancePolicy::RoundRobin && !candidates.is_empty() {
+        let len = candidates.len();
+        candidates.rotate_left(round % len);
+    }
+    candidates.extend(unhealthy);
+    candidates
+}
+
 pub struct QuicProxyConnectionManager {
-    endpoint: Endpoint,
     simple_thread_started: AtomicBool,
-    proxy_addr: SocketAddr,
-    current_tpu_nodes: Arc<RwLock<Vec<TpuNode>>>,
+    proxies: Arc<ProxyTargets>,
+    current_fanout: Arc<RwLock<Fanout>>,
+    leader_fanout: bool,
     exit_signal: Arc<AtomicBool>,
+    // wakes the sender loop waiting for transactions so it sees the exit signal
+    exit_notify: Arc<Notify>,
+    sender_loop: Mutex<Option<JoinHandle<()>>>,
 }
 
 const CHUNK_SIZE_PER_STREAM: usize = 20;
+/// transactions already queued are sent together, in one frame if the proxy supports batching
+const MAX_TRANSACTIONS_PER_BATCH: usize = 256;
 
 impl QuicProxyConnectionManager {
+    /// the proxies authenticate lite-rpc by the identity in `client_identity`, lite-rpc
+    /// authenticates the proxies by `proxy_identity` when given
     pub async fn new(
-        certificate: rustls::Certificate,
-        key: rustls::PrivateKey,
-        proxy_addr: SocketAddr,
+        client_identity: Arc<QuicClientIdentity>,
+        proxy_addrs: Vec<SocketAddr>,
+        proxy_identity: Option<Pubkey>,
+        balance_policy: ProxyBalancePolicy,
+        leader_fanout: bool,
     ) -> Self {
-        info!("Configure Quic proxy connection manager to {}", proxy_addr);
-        let endpoint = Self::create_proxy_client_endpoint(certificate, key);
+        info!(
+            "Configure Quic proxy connection manager to {:?} ({:?})",
+            proxy_addrs, balance_policy
+        );
+        let endpoint = Self::create_proxy_client_endpoint(client_identity, proxy_identity);
+
+        let proxies = ProxyTargets {
+            targets: proxy_addrs
+                .into_iter()
+                .map(|proxy_addr| ProxyTarget {
+                    connection: AutoReconnect::new(endpoint.clone(), proxy_addr),
+                    // until the first health check
+                    healthy: AtomicBool::new(true),
+                })
+                .collect(),
+            policy: balance_policy,
+            round: AtomicUsize::new(0),
+        };
 
         Self {
-            endpoint,
             simple_thread_started: AtomicBool::from(false),
-            proxy_addr,
-            current_tpu_nodes: Arc::new(RwLock::new(vec![])),
+            proxies: Arc::new(proxies),
+            current_fanout: Arc::new(RwLock::new(Fanout::default())),
+            leader_fanout,
             exit_signal: Arc::new(AtomicBool::from(false)),
+            exit_notify: Arc::new(Notify::new()),
+            sender_loop: Mutex::new(None),
         }
     }
 
+    /// the sender loop forwards the transactions already queued, then stops
     pub fn signal_shutdown(&self) {
         self.exit_signal.store(true, Relaxed);
+        self.exit_notify.notify_one();
+    }
+
+    /// signals the shutdown and waits until `deadline` for the queued transactions to be
+    /// sent to the proxy
+    pub async fn shutdown(&self, deadline: Option<Instant>) {
+        self.signal_shutdown();
+        let sender_loop = self.sender_loop.lock().unwrap().take();
+        if let Some(sender_loop) = sender_loop {
+            if until_deadline(deadline, sender_loop).await.is_none() {
+                warn!("Transactions still queued for the quic proxy at the shutdown deadline - dropping them");
+            }
+        }
     }
 
     pub async fn update_connection(
@@ -66,6 +204,7 @@ impl QuicProxyConnectionManager {
         broadcast_receiver: Receiver<SentTransactionInfo>,
         // for duration of this slot these tpu nodes will receive the transactions
         connections_to_keep: HashMap<Pubkey, SocketAddr>,
+        slots: (Slot, Slot),
         connection_parameters: QuicConnectionParameters,
     ) {
         debug!(
@@ -82,8 +221,11 @@ impl QuicProxyConnectionManager {
                 })
                 .collect_vec();
 
-            let mut lock = self.current_tpu_nodes.write().await;
-            *lock = list_of_nodes;
+            let mut lock = self.current_fanout.write().await;
+            *lock = Fanout {
+                tpu_nodes: list_of_nodes,
+                slots,
+            };
         }
 
         if self.simple_thread_started.load(Relaxed) {
@@ -94,23 +236,27 @@ impl QuicProxyConnectionManager {
 
         info!("Starting very simple proxy thread");
 
+        let proxies = self.proxies.clone();
         let exit_signal = self.exit_signal.clone();
-        tokio::spawn(Self::read_transactions_and_broadcast(
+        tokio::spawn(async move { proxies.health_check(exit_signal).await });
+
+        let exit_signal = self.exit_signal.clone();
+        let sender_loop = tokio::spawn(Self::read_transactions_and_broadcast(
             broadcast_receiver,
-            self.current_tpu_nodes.clone(),
-            self.proxy_addr,
-            self.endpoint.clone(),
+            self.current_fanout.clone(),
+            self.leader_fanout,
+            self.proxies.clone(),
             exit_signal,
+            self.exit_notify.clone(),
             connection_parameters,
         ));
+        *self.sender_loop.lock().unwrap() = Some(sender_loop);
     }
 
     fn create_proxy_client_endpoint(
-        certificate: rustls::Certificate,
-        key: rustls::PrivateKey,
+        client_identity: Arc<QuicClientIdentity>,
+        proxy_identity: Option<Pubkey>,
     ) -> Endpoint {
-        const ALPN_TPU_FORWARDPROXY_PROTOCOL_ID: &[u8] = b"solana-tpu-forward-proxy";
-
         let mut endpoint = {
             let client_socket = UdpSocket::bind("[::]:0").unwrap();
             let config = EndpointConfig::default();
@@ -118,14 +264,28 @@ impl QuicProxyConnectionManager {
                 .expect("create_endpoint quinn::Endpoint::new")
         };
 
+        let server_verifier: Arc<dyn rustls::client::ServerCertVerifier> = match proxy_identity {
+            Some(proxy_identity) => {
+                info!("Quic proxy must present identity {proxy_identity}");
+                PinnedServerVerification::new(proxy_identity)
+            }
+            None => {
+                warn!("No quic proxy identity configured, the proxy is not authenticated");
+                SkipServerVerification::new()
+            }
+        };
+
         let mut crypto = rustls::ClientConfig::builder()
             .with_safe_defaults()
-            .with_custom_certificate_verifier(SkipServerVerification::new())
-            .with_single_cert(vec![certificate], key)
-            .expect("Failed to set QUIC client certificates");
+            .with_custom_certificate_verifier(server_verifier)
+            .with_client_cert_resolver(client_identity);
 
         crypto.enable_early_data = true;
-        crypto.alpn_protocols = vec![ALPN_TPU_FORWARDPROXY_PROTOCOL_ID.to_vec()];
+        // preferred first, v1 proxies only know the original protocol id
+        crypto.alpn_protocols = vec![
+            ALPN_TPU_FORWARDPROXY_PROTOCOL_ID_V2.to_vec(),
+            ALPN_TPU_FORWARDPROXY_PROTOCOL_ID.to_vec(),
+        ];
 
         let mut config = ClientConfig::new(Arc::new(crypto));
 
@@ -144,83 +304,117 @@ impl QuicProxyConnectionManager {
         endpoint
     }
 
-    // send transactions to quic proxy
+    // send transactions to quic proxy, once the exit signal is set only the transactions
+    // already queued are sent
     async fn read_transactions_and_broadcast(
         mut transaction_receiver: Receiver<SentTransactionInfo>,
-        current_tpu_nodes: Arc<RwLock<Vec<TpuNode>>>,
-        proxy_addr: SocketAddr,
-        endpoint: Endpoint,
+        current_fanout: Arc<RwLock<Fanout>>,
+        leader_fanout: bool,
+        proxies: Arc<ProxyTargets>,
         exit_signal: Arc<AtomicBool>,
+        exit_notify: Arc<Notify>,
         connection_parameters: QuicConnectionParameters,
     ) {
-        let auto_connection = AutoReconnect::new(endpoint, proxy_addr);
-
         loop {
-            // exit signal set
-            if exit_signal.load(Relaxed) {
-                warn!("Caught exit signal - stopping sending transactions to quic proxy");
-                break;
-            }
-
-            tokio::select! {
-                tx = transaction_receiver.recv() => {
-
-                    let first_tx: TxData = match tx {
-                        Ok(SentTransactionInfo{
-                            signature,
-                            transaction,
-                            ..
-                        }) => {
-                            TxData::new(signature, transaction)
-                        },
+            let first_tx = if exit_signal.load(Relaxed) {
+                match transaction_receiver.try_recv() {
+                    Ok(tx) => tx,
+                    Err(TryRecvError::Empty) => {
+                        info!("Queued transactions sent - stopping sending transactions to quic proxy");
+                        break;
+                    }
+                    Err(e) => {
+                        warn!("Broadcast channel error (close) on drain: {} - aborting", e);
+                        return;
+                    }
+                }
+            } else {
+                tokio::select! {
+                    tx = transaction_receiver.recv() => match tx {
+                        Ok(tx) => tx,
                         Err(e) => {
                             warn!("Broadcast channel error (close) on recv: {} - aborting", e);
                             return;
                         }
-                    };
-
-                    let mut txs: Vec<TxData> = vec![first_tx];
-                    for _ in 1..connection_parameters.number_of_transactions_per_unistream {
-                        match transaction_receiver.try_recv() {
-                            Ok(SentTransactionInfo{
-                                signature,
-                                transaction,
-                                ..
-                            }) => {
-                                txs.push(TxData::new(signature, transaction));
-                            },
-                            Err(TryRecvError::Empty) => {
-                                break;
-                            }
-                            Err(e) => {
-                                warn!(
-                                    "Broadcast channel error (close) on more recv: {} - aborting", e);
-                                return;
-                            }
-                        };
+                    },
+                    _ = exit_notify.notified() => {
+                        warn!("Caught exit signal - sending the queued transactions to quic proxy");
+                        continue;
+                    }
+                }
+            };
+
+            let mut txs: Vec<TxData> = vec![TxData::new(first_tx.signature, first_tx.transaction)];
+            let max_batch_size = connection_parameters
+                .number_of_transactions_per_unistream
+                .max(MAX_TRANSACTIONS_PER_BATCH);
+            for _ in 1..max_batch_size {
+                match transaction_receiver.try_recv() {
+                    Ok(SentTransactionInfo {
+                        signature,
+                        transaction,
+                        ..
+                    }) => {
+                        txs.push(TxData::new(signature, transaction));
+                    }
+                    Err(TryRecvError::Empty) => {
+                        break;
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Broadcast channel error (close) on more recv: {} - aborting",
+                            e
+                        );
+                        return;
                     }
+                };
+            }
 
-                    let tpu_fanout_nodes = current_tpu_nodes.read().await.clone();
+            let fanout = current_fanout.read().await.clone();
 
-                    if tpu_fanout_nodes.is_empty() {
-                        warn!("No tpu nodes to send transactions to - skip");
-                        continue;
-                    }
+            if fanout.tpu_nodes.is_empty() {
+                warn!("No tpu nodes to send transactions to - skip");
+                continue;
+            }
 
-                    trace!("Sending copy of transaction batch of {} txs to {} tpu nodes via quic proxy",
-                            txs.len(), tpu_fanout_nodes.len());
-
-                    let send_result =
-                        Self::send_copy_of_txs_to_quicproxy(
-                            &txs, &auto_connection,
-                            proxy_addr,
-                            tpu_fanout_nodes)
-                        .await;
-                    if let Err(e) = send_result {
-                        warn!("Failed to send copy of txs to quic proxy - skip (error {})", e);
-                    }
+            trace!(
+                "Sending copy of transaction batch of {} txs to {} tpu nodes via quic proxy",
+                txs.len(),
+                fanout.tpu_nodes.len()
+            );
 
-                },
+            let mut failed = false;
+            for proxy in proxies.next_candidates() {
+                if failed {
+                    PROXY_FAILOVERS.inc();
+                }
+                let send_result = Self::send_copy_of_txs_to_quicproxy(
+                    &txs,
+                    &proxy.connection,
+                    &fanout,
+                    leader_fanout,
+                )
+                .await;
+                match send_result {
+                    Ok(()) => {
+                        failed = false;
+                        break;
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to send copy of txs to quic proxy {} (error {})",
+                            proxy.connection.target_address, e
+                        );
+                        proxy.set_healthy(false);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                warn!(
+                    "No quic proxy accepted the batch of {} txs - skip",
+                    txs.len()
+                );
             }
         } // -- loop
     }
@@ -228,33 +422,76 @@ impl QuicProxyConnectionManager {
     async fn send_copy_of_txs_to_quicproxy(
         txs: &[TxData],
         auto_connection: &AutoReconnect,
-        _proxy_address: SocketAddr,
-        tpu_fanout_nodes: Vec<TpuNode>,
+        fanout: &Fanout,
+        leader_fanout: bool,
     ) -> anyhow::Result<()> {
-        let tpu_data = tpu_fanout_nodes
+        if leader_fanout {
+            let (_, session) = auto_connection.refresh_and_get().await?;
+            if session.capabilities.contains(Capabilities::LEADER_FANOUT) {
+                let transactions = txs
+                    .iter()
+                    .map(|tx| tx.transaction_bytes().to_vec())
+                    .collect_vec();
+                return auto_connection
+                    .send_to_leaders(fanout.slots, transactions)
+                    .await
+                    .context("Failed to send data to quic proxy");
+            }
+        }
+
+        let tpu_data = fanout
+            .tpu_nodes
             .iter()
             .map(|tpu| (tpu.tpu_address, tpu.tpu_identity))
             .collect_vec();
 
-        for chunk in txs.chunks(CHUNK_SIZE_PER_STREAM) {
-            let forwarding_request = TpuForwardingRequest::new(&tpu_data, chunk);
-            debug!("forwarding_request: {}", forwarding_request);
+        let proxy_requests_raw = txs
+            .chunks(CHUNK_SIZE_PER_STREAM)
+            .map(|chunk| {
+                let forwarding_request = TpuForwardingRequest::new(&tpu_data, chunk);
+                debug!("forwarding_request: {}", forwarding_request);
 
-            let proxy_request_raw =
-                bincode::serialize(&forwarding_request).expect("Expect to serialize transactions");
+                bincode::serialize(&forwarding_request).expect("Expect to serialize transactions")
+            })
+            .collect_vec();
 
-            let send_result = auto_connection.send_uni(&proxy_request_raw).await;
+        let send_result = auto_connection.send_requests(proxy_requests_raw).await;
 
-            match send_result {
-                Ok(()) => {
-                    debug!("Successfully sent {} txs to quic proxy", txs.len());
-                }
-                Err(e) => {
-                    bail!("Failed to send data to quic proxy: {:?}", e);
-                }
+        match send_result {
+            Ok(()) => {
+                debug!("Successfully sent {} txs to quic proxy", txs.len());
+            }
+            Err(e) => {
+                bail!("Failed to send data to quic proxy: {:?}", e);
             }
-        } // -- one chunk
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unhealthy_proxies_are_tried_last() {
+        let healthy = [false, true, true];
+        assert_eq!(
+            candidate_order(ProxyBalancePolicy::Failover, &healthy, 7),
+            vec![1, 2, 0]
+        );
+        assert_eq!(
+            candidate_order(ProxyBalancePolicy::RoundRobin, &healthy, 0),
+            vec![1, 2, 0]
+        );
+        assert_eq!(
+            candidate_order(ProxyBalancePolicy::RoundRobin, &healthy, 1),
+            vec![2, 1, 0]
+        );
+        assert_eq!(
+            candidate_order(ProxyBalancePolicy::RoundRobin, &[false, false], 1),
+            vec![0, 1]
+        );
+    }
+}
@@ -1,21 +1,62 @@
 use anyhow::{bail, Context};
 use log::{info, warn};
+use prometheus::{opts, register_int_counter, IntCounter};
 use quinn::{Connection, ConnectionError, Endpoint};
+use solana_lite_rpc_core::structures::proxy_protocol::{
+    read_frame, Capabilities, Frame, ALPN_TPU_FORWARDPROXY_PROTOCOL_ID_V2, PROTOCOL_VERSION,
+};
+use solana_sdk::slot_history::Slot;
 use std::fmt;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 use tokio::sync::RwLock;
 use tokio::time::timeout;
 use tracing::debug;
 
+lazy_static::lazy_static! {
+    static ref PROXY_ACKED_REQUESTS: IntCounter =
+    register_int_counter!(opts!("literpc_quic_proxy_acked_requests", "Requests the quic proxy accepted")).unwrap();
+    static ref PROXY_REJECTED_REQUESTS: IntCounter =
+    register_int_counter!(opts!("literpc_quic_proxy_rejected_requests", "Requests the quic proxy rejected")).unwrap();
+}
+
 /// copy of quic-proxy AutoReconnect - used that for reference
 
 const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
 const MAX_RETRY_ATTEMPTS: u32 = 10;
+/// capabilities of the proxy protocol implemented by this client
+const CLIENT_CAPABILITIES: Capabilities = Capabilities::ACKS
+    .union(Capabilities::BATCHING)
+    .union(Capabilities::COMPRESSION)
+    .union(Capabilities::LEADER_FANOUT);
+
+/// protocol agreed with the proxy for one connection
+#[derive(Clone, Copy, Debug)]
+pub struct ProxySession {
+    pub version: u16,
+    pub capabilities: Capabilities,
+}
+
+impl ProxySession {
+    const V1: Self = Self {
+        version: 1,
+        capabilities: Capabilities::NONE,
+    };
+
+    fn encode(&self, frame: &Frame) -> anyhow::Result<Vec<u8>> {
+        if self.capabilities.contains(Capabilities::COMPRESSION) {
+            frame.encode_compressed()
+        } else {
+            frame.encode()
+        }
+    }
+}
 
 enum ConnectionState {
     NotConnected,
-    Connection(Connection),
+    Connection(Connection, ProxySession),
     PermanentError,
     FailedAttempt(u32),
 }
@@ -25,6 +66,7 @@ pub struct AutoReconnect {
     endpoint: Endpoint,
     current: RwLock<ConnectionState>,
     pub target_address: SocketAddr,
+    next_request_id: AtomicU64,
 }
 
 impl AutoReconnect {
@@ -33,6 +75,7 @@ impl AutoReconnect {
             endpoint,
             current: RwLock::new(ConnectionState::NotConnected),
             target_address,
+            next_request_id: AtomicU64::new(0),
         }
     }
 
@@ -41,8 +84,73 @@ impl AutoReconnect {
         matches!(&*lock, ConnectionState::PermanentError)
     }
 
-    pub async fn send_uni(&self, payload: &Vec<u8>) -> anyhow::Result<()> {
-        let mut send_stream = timeout(SEND_TIMEOUT, self.refresh_and_get().await?.open_uni())
+    /// gives a target that failed permanently another round of reconnect attempts
+    pub async fn revive(&self) {
+        let mut lock = self.current.write().await;
+        if matches!(&*lock, ConnectionState::PermanentError) {
+            info!(
+                "Retrying connection to {} after permanent error",
+                self.target_address
+            );
+            *lock = ConnectionState::NotConnected;
+        }
+    }
+
+    /// sends `TpuForwardingRequest`s in the v1 wire format, framed for the negotiated version:
+    /// all in one stream if the proxy supports batching, one stream per request otherwise
+    pub async fn send_requests(&self, requests: Vec<Vec<u8>>) -> anyhow::Result<()> {
+        let (connection, session) = self.refresh_and_get().await?;
+        let payloads = if session.version < 2 {
+            requests
+        } else if session.capabilities.contains(Capabilities::BATCHING) {
+            let first_request_id = self
+                .next_request_id
+                .fetch_add(requests.len() as u64, Ordering::Relaxed);
+            vec![session.encode(&Frame::ForwardBatch {
+                first_request_id,
+                requests,
+            })?]
+        } else {
+            requests
+                .into_iter()
+                .map(|request| {
+                    session.encode(&Frame::Forward {
+                        request_id: self.next_request_id.fetch_add(1, Ordering::Relaxed),
+                        request,
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?
+        };
+
+        for payload in payloads {
+            let mut send_stream = timeout(SEND_TIMEOUT, connection.open_uni())
+                .await
+                .context("open uni stream for sending")??;
+            send_stream.write_all(payload.as_slice()).await?;
+            send_stream.finish().await?;
+        }
+        Ok(())
+    }
+
+    /// sends the transactions once for the leaders of `first_slot..=last_slot`, only for
+    /// sessions with `LEADER_FANOUT`
+    pub async fn send_to_leaders(
+        &self,
+        (first_slot, last_slot): (Slot, Slot),
+        transactions: Vec<Vec<u8>>,
+    ) -> anyhow::Result<()> {
+        let (connection, session) = self.refresh_and_get().await?;
+        if !session.capabilities.contains(Capabilities::LEADER_FANOUT) {
+            bail!("proxy {} does not resolve leaders", self.target_address);
+        }
+        let payload = session.encode(&Frame::ForwardToLeaders {
+            request_id: self.next_request_id.fetch_add(1, Ordering::Relaxed),
+            first_slot,
+            last_slot,
+            transactions,
+        })?;
+
+        let mut send_stream = timeout(SEND_TIMEOUT, connection.open_uni())
             .await
             .context("open uni stream for sending")??;
         send_stream.write_all(payload.as_slice()).await?;
@@ -50,13 +158,13 @@ impl AutoReconnect {
         Ok(())
     }
 
-    pub async fn refresh_and_get(&self) -> anyhow::Result<Connection> {
+    pub async fn refresh_and_get(&self) -> anyhow::Result<(Connection, ProxySession)> {
         self.refresh().await;
 
         let lock = self.current.read().await;
         match &*lock {
             ConnectionState::NotConnected => bail!("not connected"),
-            ConnectionState::Connection(conn) => Ok(conn.clone()),
+            ConnectionState::Connection(conn, session) => Ok((conn.clone(), *session)),
             ConnectionState::PermanentError => bail!("permanent error"),
             ConnectionState::FailedAttempt(_) => bail!("failed connection attempt"),
         }
@@ -66,7 +174,7 @@ impl AutoReconnect {
         {
             // first check for existing connection using a cheap read-lock
             let lock = self.current.read().await;
-            if let ConnectionState::Connection(conn) = &*lock {
+            if let ConnectionState::Connection(conn, _) = &*lock {
                 if conn.close_reason().is_none() {
                     debug!(
                         "Reuse connection {} to {}",
@@ -79,7 +187,7 @@ impl AutoReconnect {
         }
         let mut lock = self.current.write().await;
         match &*lock {
-            ConnectionState::Connection(current) => {
+            ConnectionState::Connection(current, _) => {
                 if current.close_reason().is_some() {
                     let old_stable_id = current.stable_id();
                     warn!(
@@ -90,8 +198,8 @@ impl AutoReconnect {
                     );
 
                     match self.create_connection().await {
-                        Some(new_connection) => {
-                            *lock = ConnectionState::Connection(new_connection.clone());
+                        Some((new_connection, session)) => {
+                            *lock = ConnectionState::Connection(new_connection.clone(), session);
                             info!(
                                 "Restored closed connection {} with {} to target {}",
                                 old_stable_id,
@@ -117,13 +225,14 @@ impl AutoReconnect {
             }
             ConnectionState::NotConnected => {
                 match self.create_connection().await {
-                    Some(new_connection) => {
-                        *lock = ConnectionState::Connection(new_connection.clone());
+                    Some((new_connection, session)) => {
+                        *lock = ConnectionState::Connection(new_connection.clone(), session);
 
                         info!(
-                            "Create initial connection {} to {}",
+                            "Create initial connection {} to {} with {:?}",
                             new_connection.stable_id(),
-                            self.target_address
+                            self.target_address,
+                            session
                         );
                     }
                     None => {
@@ -141,8 +250,8 @@ impl AutoReconnect {
             }
             ConnectionState::FailedAttempt(attempts) => {
                 match self.create_connection().await {
-                    Some(new_connection) => {
-                        *lock = ConnectionState::Connection(new_connection);
+                    Some((new_connection, session)) => {
+                        *lock = ConnectionState::Connection(new_connection, session);
                     }
                     None => {
                         if *attempts < MAX_RETRY_ATTEMPTS {
@@ -164,25 +273,92 @@ impl AutoReconnect {
         }
     }
 
-    async fn create_connection(&self) -> Option<Connection> {
-        let connection = self
-            .endpoint
-            .connect(self.target_address, "localhost")
-            .expect("handshake");
+    async fn create_connection(&self) -> Option<(Connection, ProxySession)> {
+        let connection = match self.endpoint.connect(self.target_address, "localhost") {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Cannot connect to {}: {}", self.target_address, e);
+                return None;
+            }
+        };
 
-        match connection.await {
-            Ok(conn) => Some(conn),
-            Err(ConnectionError::TimedOut) => None,
-            // maybe we should also treat TransportError explicitly
+        let connection = match connection.await {
+            Ok(conn) => conn,
+            Err(ConnectionError::TimedOut) => return None,
+            // e.g. the target rejected our certificate, retried like a timeout
             Err(unexpected_error) => {
-                panic!(
+                warn!(
                     "Connection to {} failed with unexpected error: {}",
                     self.target_address, unexpected_error
                 );
+                return None;
+            }
+        };
+
+        match Self::handshake(&connection).await {
+            Ok(session) => Some((connection, session)),
+            Err(e) => {
+                warn!("Handshake with {} failed: {:?}", self.target_address, e);
+                connection.close(0u32.into(), b"handshake failed");
+                None
             }
         }
     }
 
+    /// negotiates the protocol version and capabilities, proxies not offering v2 by ALPN
+    /// get v1 requests
+    async fn handshake(connection: &Connection) -> anyhow::Result<ProxySession> {
+        let protocol = connection
+            .handshake_data()
+            .and_then(|data| data.downcast::<quinn::crypto::rustls::HandshakeData>().ok())
+            .and_then(|data| data.protocol);
+        if protocol.as_deref() != Some(ALPN_TPU_FORWARDPROXY_PROTOCOL_ID_V2) {
+            return Ok(ProxySession::V1);
+        }
+
+        let (mut send, mut recv) = connection.open_bi().await?;
+        let hello = Frame::Hello {
+            max_version: PROTOCOL_VERSION,
+            capabilities: CLIENT_CAPABILITIES,
+        };
+        send.write_all(&hello.encode()?).await?;
+
+        let mut buf = Vec::new();
+        let session = match timeout(HANDSHAKE_TIMEOUT, read_frame(&mut recv, &mut buf))
+            .await
+            .context("waiting for hello ack")??
+        {
+            Some(Frame::HelloAck {
+                version,
+                capabilities,
+            }) => ProxySession {
+                version,
+                capabilities,
+            },
+            other => bail!("expected hello ack, got {other:?}"),
+        };
+
+        // the control stream lives as long as the connection
+        tokio::spawn(async move {
+            let _send = send;
+            while let Ok(Some(frame)) = read_frame(&mut recv, &mut buf).await {
+                match frame {
+                    Frame::Ack { accepted: true, .. } => PROXY_ACKED_REQUESTS.inc(),
+                    Frame::Ack {
+                        request_id,
+                        accepted: false,
+                    } => {
+                        debug!("Quic proxy rejected request {request_id}");
+                        PROXY_REJECTED_REQUESTS.inc();
+                    }
+                    other => debug!("Unexpected frame on the proxy control stream {other:?}"),
+                }
+            }
+        });
+
+        Ok(session)
+    }
+
     //  stable_id 140266619216912, rtt=2.156683ms,
     // stats FrameStats { ACK: 3, CONNECTION_CLOSE: 0, CRYPTO: 3,
     // DATA_BLOCKED: 0, DATAGRAM: 0, HANDSHAKE_DONE: 1, MAX_DATA: 0,
@@ -193,7 +369,7 @@ impl AutoReconnect {
     pub async fn connection_stats(&self) -> String {
         let lock = self.current.read().await;
         match &*lock {
-            ConnectionState::Connection(conn) => format!(
+            ConnectionState::Connection(conn, _) => format!(
                 "stable_id {} stats {:?}, rtt={:?}",
                 conn.stable_id(),
                 conn.stats().frame_rx,
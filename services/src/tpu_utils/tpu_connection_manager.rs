@@ -1,14 +1,15 @@
 use dashmap::DashMap;
-use log::{error, trace};
+use log::{error, trace, warn};
 use prometheus::{core::GenericGauge, opts, register_int_gauge};
 use quinn::Endpoint;
 use solana_lite_rpc_core::{
+    identity_signer::QuicClientIdentity,
     quic_connection::{PooledConnection, QuicConnectionPool},
     quic_connection_utils::{QuicConnectionParameters, QuicConnectionUtils},
     stores::data_cache::DataCache,
     structures::{
         identity_stakes::IdentityStakesData, rotating_queue::RotatingQueue,
-        transaction_sent_info::SentTransactionInfo,
+        shutdown::until_deadline, transaction_sent_info::SentTransactionInfo,
     },
 };
 use solana_sdk::pubkey::Pubkey;
@@ -17,11 +18,14 @@ use std::{
     collections::HashMap,
     net::SocketAddr,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, RwLock,
     },
 };
-use tokio::sync::{broadcast::Receiver, broadcast::Sender};
+use tokio::{
+    sync::{broadcast::Receiver, broadcast::Sender, Notify},
+    time::Instant,
+};
 
 lazy_static::lazy_static! {
     static ref NB_QUIC_CONNECTIONS: GenericGauge<prometheus::core::AtomicI64> =
@@ -34,6 +38,36 @@ lazy_static::lazy_static! {
         register_int_gauge!(opts!("literpc_quic_tasks", "Number of connections to keep asked by tpu service")).unwrap();
 }
 
+/// listening tasks and transactions being sent, shared by all the connections of the manager
+#[derive(Default)]
+struct InFlight {
+    count: AtomicUsize,
+    done: Notify,
+}
+
+impl InFlight {
+    fn start(&self) {
+        self.count.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn finish(&self) {
+        if self.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.done.notify_waiters();
+        }
+    }
+
+    async fn wait(&self) {
+        loop {
+            // registered before checking the count so a notification in between is not lost
+            let done = self.done.notified();
+            if self.count.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            done.await;
+        }
+    }
+}
+
 #[derive(Clone)]
 struct ActiveConnection {
     endpoints: RotatingQueue<Endpoint>,
@@ -42,6 +76,7 @@ struct ActiveConnection {
     exit_signal: Arc<AtomicBool>,
     data_cache: DataCache,
     connection_parameters: QuicConnectionParameters,
+    in_flight: Arc<InFlight>,
 }
 
 impl ActiveConnection {
@@ -51,6 +86,7 @@ impl ActiveConnection {
         identity: Pubkey,
         data_cache: DataCache,
         connection_parameters: QuicConnectionParameters,
+        in_flight: Arc<InFlight>,
     ) -> Self {
         Self {
             endpoints,
@@ -59,6 +95,7 @@ impl ActiveConnection {
             exit_signal: Arc::new(AtomicBool::new(false)),
             data_cache,
             connection_parameters,
+            in_flight,
         }
     }
 
@@ -100,6 +137,8 @@ impl ActiveConnection {
             }
 
             tokio::select! {
+                // transactions already broadcasted are sent before handling the exit channel
+                biased;
                 tx = transaction_reciever.recv() => {
                     // exit signal set
                     if exit_signal.load(Ordering::Relaxed) {
@@ -134,12 +173,15 @@ impl ActiveConnection {
                         },
                     };
 
+                    let in_flight = self.in_flight.clone();
+                    in_flight.start();
                     tokio::spawn(async move {
                         // permit will be used to send all the transaction and then destroyed
                         let _permit = permit;
                         NB_QUIC_TASKS.inc();
                         connection.send_transaction(tx).await;
                         NB_QUIC_TASKS.dec();
+                        in_flight.finish();
                     });
                 },
                 _ = exit_oneshot_channel.recv() => {
@@ -160,6 +202,7 @@ impl ActiveConnection {
     ) {
         let addr = self.tpu_address;
         let this = self.clone();
+        self.in_flight.start();
         tokio::spawn(async move {
             this.listen(
                 transaction_reciever,
@@ -168,6 +211,7 @@ impl ActiveConnection {
                 identity_stakes,
             )
             .await;
+            this.in_flight.finish();
         });
     }
 }
@@ -175,28 +219,48 @@ impl ActiveConnection {
 struct ActiveConnectionWithExitChannel {
     pub active_connection: ActiveConnection,
     pub exit_stream: tokio::sync::mpsc::Sender<()>,
+    pub identity_generation: u64,
 }
 
 pub struct TpuConnectionManager {
-    endpoints: RotatingQueue<Endpoint>,
+    /// replaced on identity rotation, connections keep the endpoints they were created with
+    endpoints: RwLock<RotatingQueue<Endpoint>>,
+    number_of_clients: usize,
+    /// incremented on identity rotation, connections of an older generation are replaced
+    identity_generation: AtomicU64,
     identity_to_active_connection: Arc<DashMap<Pubkey, Arc<ActiveConnectionWithExitChannel>>>,
+    in_flight: Arc<InFlight>,
 }
 
 impl TpuConnectionManager {
-    pub async fn new(
-        certificate: rustls::Certificate,
-        key: rustls::PrivateKey,
-        fanout: usize,
-    ) -> Self {
+    pub async fn new(client_identity: Arc<QuicClientIdentity>, fanout: usize) -> Self {
         let number_of_clients = fanout * 2;
         Self {
-            endpoints: RotatingQueue::new(number_of_clients, || {
-                QuicConnectionUtils::create_endpoint(certificate.clone(), key.clone())
-            }),
+            endpoints: RwLock::new(Self::create_endpoints(client_identity, number_of_clients)),
+            number_of_clients,
+            identity_generation: AtomicU64::new(0),
             identity_to_active_connection: Arc::new(DashMap::new()),
+            in_flight: Arc::new(InFlight::default()),
         }
     }
 
+    fn create_endpoints(
+        client_identity: Arc<QuicClientIdentity>,
+        number_of_clients: usize,
+    ) -> RotatingQueue<Endpoint> {
+        RotatingQueue::new(number_of_clients, || {
+            QuicConnectionUtils::create_endpoint(client_identity.clone())
+        })
+    }
+
+    /// connections opened from now on present the new identity, the current ones are replaced
+    /// on the next update and finish the transactions they are sending
+    pub fn rotate_identity(&self, client_identity: Arc<QuicClientIdentity>) {
+        let endpoints = Self::create_endpoints(client_identity, self.number_of_clients);
+        *self.endpoints.write().unwrap() = endpoints;
+        self.identity_generation.fetch_add(1, Ordering::Relaxed);
+    }
+
     pub async fn update_connections(
         &self,
         broadcast_sender: Arc<Sender<SentTransactionInfo>>,
@@ -206,15 +270,25 @@ impl TpuConnectionManager {
         connection_parameters: QuicConnectionParameters,
     ) {
         NB_CONNECTIONS_TO_KEEP.set(connections_to_keep.len() as i64);
+        let endpoints = self.endpoints.read().unwrap().clone();
+        let identity_generation = self.identity_generation.load(Ordering::Relaxed);
         for (identity, socket_addr) in &connections_to_keep {
-            if self.identity_to_active_connection.get(identity).is_none() {
+            let existing_connection = self
+                .identity_to_active_connection
+                .get(identity)
+                .map(|connection| connection.value().clone());
+            let is_current = existing_connection.as_ref().map_or(false, |connection| {
+                connection.identity_generation == identity_generation
+            });
+            if !is_current {
                 trace!("added a connection for {}, {}", identity, socket_addr);
                 let active_connection = ActiveConnection::new(
-                    self.endpoints.clone(),
+                    endpoints.clone(),
                     *socket_addr,
                     *identity,
                     data_cache.clone(),
                     connection_parameters,
+                    self.in_flight.clone(),
                 );
                 // using mpsc as a oneshot channel/ because with one shot channel we cannot reuse the reciever
                 let (sx, rx) = tokio::sync::mpsc::channel(1);
@@ -226,8 +300,16 @@ impl TpuConnectionManager {
                     Arc::new(ActiveConnectionWithExitChannel {
                         active_connection,
                         exit_stream: sx,
+                        identity_generation,
                     }),
                 );
+
+                // connection opened with the previous identity, it stops taking transactions
+                // but its exit signal stays unset so the retries in flight still complete,
+                // shutdown waits for them with the other transactions in flight
+                if let Some(existing_connection) = existing_connection {
+                    let _ = existing_connection.exit_stream.send(()).await;
+                }
             }
         }
 
@@ -250,4 +332,57 @@ impl TpuConnectionManager {
             }
         }
     }
+
+    /// closes all the connections once the transactions already broadcasted and the streams
+    /// in flight are sent, waits for them until `deadline`
+    pub async fn shutdown(&self, deadline: Option<Instant>) {
+        let active_connections = self
+            .identity_to_active_connection
+            .iter()
+            .map(|x| x.value().clone())
+            .collect::<Vec<_>>();
+        self.identity_to_active_connection.clear();
+        for connection in active_connections {
+            // ignore error for exit channel
+            let _ = connection.exit_stream.send(()).await;
+        }
+
+        if until_deadline(deadline, self.in_flight.wait())
+            .await
+            .is_none()
+        {
+            warn!(
+                "{} transaction(s) still in flight at the shutdown deadline - closing anyway",
+                self.in_flight.count.load(Ordering::Acquire)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn in_flight_wait_returns_once_the_last_task_finishes() {
+        let in_flight = Arc::new(InFlight::default());
+        in_flight.wait().await;
+
+        in_flight.start();
+        in_flight.start();
+        let waiter = tokio::spawn({
+            let in_flight = in_flight.clone();
+            async move { in_flight.wait().await }
+        });
+        in_flight.finish();
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+
+        in_flight.finish();
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+    }
 }
@@ -1,20 +1,51 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
 use std::fmt::Display;
 use std::net::SocketAddr;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum TpuConnectionPath {
     QuicDirectPath,
-    QuicForwardProxyPath { forward_proxy_address: SocketAddr },
+    #[cfg(feature = "quic-proxy")]
+    QuicForwardProxyPath {
+        /// in order of preference for the failover policy
+        forward_proxy_addresses: Vec<SocketAddr>,
+        /// identity the proxies have to present, any proxy is accepted when unset
+        proxy_identity: Option<Pubkey>,
+        balance_policy: ProxyBalancePolicy,
+        /// send every transaction once with the slots to cover and let the proxies resolve
+        /// the leaders, for the proxies supporting it
+        leader_fanout: bool,
+    },
+}
+
+/// How the transactions are spread over the forward proxies, the unhealthy proxies are
+/// only used when none is healthy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProxyBalancePolicy {
+    /// everything goes to the first healthy proxy
+    #[default]
+    Failover,
+    /// batches go to the healthy proxies in turn
+    RoundRobin,
 }
 
 impl Display for TpuConnectionPath {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             TpuConnectionPath::QuicDirectPath => write!(f, "Direct QUIC connection to TPU"),
+            #[cfg(feature = "quic-proxy")]
             TpuConnectionPath::QuicForwardProxyPath {
-                forward_proxy_address,
+                forward_proxy_addresses,
+                balance_policy,
+                ..
             } => {
-                write!(f, "QUIC Forward Proxy on {}", forward_proxy_address)
+                write!(
+                    f,
+                    "QUIC Forward Proxy on {:?} ({:?})",
+                    forward_proxy_addresses, balance_policy
+                )
             }
         }
     }
@@ -1,22 +1,29 @@
 use anyhow::Context;
-use prometheus::{core::GenericGauge, opts, register_int_gauge};
-use solana_lite_rpc_core::structures::transaction_sent_info::SentTransactionInfo;
+use log::info;
+use prometheus::{core::GenericGauge, opts, register_int_counter, register_int_gauge, IntCounter};
+use solana_lite_rpc_core::structures::{
+    heartbeat::Heartbeat,
+    runtime_config::{RuntimeConfig, RuntimeConfigReceiver},
+    send_pacer::SendPacer,
+    transaction_sent_info::SentTransactionInfo,
+};
 
 use super::tpu_connection_manager::TpuConnectionManager;
+#[cfg(feature = "quic-proxy")]
 use crate::tpu_utils::quic_proxy_connection_manager::QuicProxyConnectionManager;
 use crate::tpu_utils::tpu_connection_path::TpuConnectionPath;
-use crate::tpu_utils::tpu_service::ConnectionManager::{DirectTpu, QuicProxy};
+use crate::tpu_utils::tpu_service::ConnectionManager::DirectTpu;
+#[cfg(feature = "quic-proxy")]
+use crate::tpu_utils::tpu_service::ConnectionManager::QuicProxy;
+use solana_lite_rpc_core::identity_signer::{IdentitySigner, QuicClientIdentity};
 use solana_lite_rpc_core::quic_connection_utils::QuicConnectionParameters;
 use solana_lite_rpc_core::stores::data_cache::DataCache;
 use solana_lite_rpc_core::traits::leaders_fetcher_interface::LeaderFetcherInterface;
 use solana_lite_rpc_core::types::SlotStream;
 use solana_lite_rpc_core::AnyhowJoinHandle;
-use solana_sdk::{quic::QUIC_PORT_OFFSET, signature::Keypair, slot_history::Slot};
-use solana_streamer::tls_certificates::new_self_signed_tls_certificate;
-use std::{
-    net::{IpAddr, Ipv4Addr},
-    sync::Arc,
-};
+use solana_sdk::{quic::QUIC_PORT_OFFSET, slot_history::Slot};
+use std::sync::Arc;
+use tokio::time::Instant;
 
 lazy_static::lazy_static! {
     static ref NB_CLUSTER_NODES: GenericGauge<prometheus::core::AtomicI64> =
@@ -30,9 +37,15 @@ lazy_static::lazy_static! {
 
     static ref ESTIMATED_SLOT: GenericGauge<prometheus::core::AtomicI64> =
     register_int_gauge!(opts!("literpc_estimated_slot", "Estimated slot seen by last rpc")).unwrap();
+
+    static ref IDENTITY_ROTATIONS: IntCounter =
+    register_int_counter!(opts!("literpc_identity_rotations", "Number of identity rotations")).unwrap();
+
+    static ref IDENTITY_ROTATED_AT: GenericGauge<prometheus::core::AtomicI64> =
+    register_int_gauge!(opts!("literpc_identity_rotated_at", "Unix timestamp of the last identity rotation")).unwrap();
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct TpuServiceConfig {
     pub fanout_slots: u64,
     pub maximum_transaction_in_queue: usize,
@@ -46,7 +59,13 @@ pub struct TpuService {
     connection_manager: ConnectionManager,
     leader_schedule: Arc<dyn LeaderFetcherInterface>,
     config: TpuServiceConfig,
+    runtime_config: RuntimeConfigReceiver,
     data_cache: DataCache,
+    heartbeat: Heartbeat,
+    /// set once connections to the upcoming leaders are set up for the first time
+    connections_ready: Arc<tokio::sync::watch::Sender<bool>>,
+    /// keeps every sender together under `max_send_tps`
+    send_pacer: Arc<SendPacer>,
 }
 
 #[derive(Clone)]
@@ -54,6 +73,7 @@ enum ConnectionManager {
     DirectTpu {
         tpu_connection_manager: Arc<TpuConnectionManager>,
     },
+    #[cfg(feature = "quic-proxy")]
     QuicProxy {
         quic_proxy_connection_manager: Arc<QuicProxyConnectionManager>,
     },
@@ -62,30 +82,40 @@ enum ConnectionManager {
 impl TpuService {
     pub async fn new(
         config: TpuServiceConfig,
-        identity: Arc<Keypair>,
+        identity: Arc<dyn IdentitySigner>,
         leader_schedule: Arc<dyn LeaderFetcherInterface>,
         data_cache: DataCache,
+        runtime_config: RuntimeConfigReceiver,
     ) -> anyhow::Result<Self> {
         let (sender, _) = tokio::sync::broadcast::channel(config.maximum_transaction_in_queue);
-        let (certificate, key) = new_self_signed_tls_certificate(
-            identity.as_ref(),
-            IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
-        )
-        .expect("Failed to initialize QUIC client certificates");
+        let client_identity = Arc::new(
+            QuicClientIdentity::new(identity)
+                .context("Failed to initialize QUIC client certificates")?,
+        );
 
-        let connection_manager = match config.tpu_connection_path {
+        let connection_manager = match &config.tpu_connection_path {
             TpuConnectionPath::QuicDirectPath => {
                 let tpu_connection_manager =
-                    TpuConnectionManager::new(certificate, key, config.fanout_slots as usize).await;
+                    TpuConnectionManager::new(client_identity, config.fanout_slots as usize).await;
                 DirectTpu {
                     tpu_connection_manager: Arc::new(tpu_connection_manager),
                 }
             }
+            #[cfg(feature = "quic-proxy")]
             TpuConnectionPath::QuicForwardProxyPath {
-                forward_proxy_address,
+                forward_proxy_addresses,
+                proxy_identity,
+                balance_policy,
+                leader_fanout,
             } => {
-                let quic_proxy_connection_manager =
-                    QuicProxyConnectionManager::new(certificate, key, forward_proxy_address).await;
+                let quic_proxy_connection_manager = QuicProxyConnectionManager::new(
+                    client_identity,
+                    forward_proxy_addresses.clone(),
+                    *proxy_identity,
+                    *balance_policy,
+                    *leader_fanout,
+                )
+                .await;
 
                 QuicProxy {
                     quic_proxy_connection_manager: Arc::new(quic_proxy_connection_manager),
@@ -98,11 +128,39 @@ impl TpuService {
             broadcast_sender: Arc::new(sender),
             connection_manager,
             config,
+            runtime_config,
             data_cache,
+            heartbeat: Heartbeat::new("tpu service"),
+            connections_ready: Arc::new(tokio::sync::watch::channel(false).0),
+            send_pacer: Arc::new(SendPacer::default()),
         })
     }
 
-    pub fn send_transaction(&self, transaction: &SentTransactionInfo) -> anyhow::Result<()> {
+    /// latest runtime config, shared with the services sending through this tpu service
+    pub fn runtime_config(&self) -> RuntimeConfig {
+        *self.runtime_config.borrow()
+    }
+
+    /// beats every time connections are updated for the upcoming leaders
+    pub fn heartbeat(&self) -> Heartbeat {
+        self.heartbeat.clone()
+    }
+
+    /// resolves once connections to the upcoming leaders are set up
+    pub async fn wait_for_connections(&self) {
+        let mut connections_ready = self.connections_ready.subscribe();
+        while !*connections_ready.borrow_and_update() {
+            if connections_ready.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// waits while over `max_send_tps`, replays included
+    pub async fn send_transaction(&self, transaction: &SentTransactionInfo) -> anyhow::Result<()> {
+        if let Some(max_send_tps) = self.runtime_config().max_send_tps {
+            self.send_pacer.wait(max_send_tps).await;
+        }
         self.broadcast_sender.send(transaction.clone())?;
         Ok(())
     }
@@ -113,7 +171,7 @@ impl TpuService {
         current_slot: Slot,
         estimated_slot: Slot,
     ) -> anyhow::Result<()> {
-        let fanout = self.config.fanout_slots;
+        let fanout = self.runtime_config().fanout_slots;
         let last_slot = estimated_slot + fanout;
 
         let cluster_nodes = self.data_cache.cluster_info.cluster_nodes.clone();
@@ -156,6 +214,7 @@ impl TpuService {
                     )
                     .await;
             }
+            #[cfg(feature = "quic-proxy")]
             QuicProxy {
                 quic_proxy_connection_manager,
             } => {
@@ -164,6 +223,7 @@ impl TpuService {
                     .update_connection(
                         transaction_receiver,
                         connections_to_keep,
+                        (current_slot, last_slot),
                         self.config.quic_connection_params,
                     )
                     .await;
@@ -172,6 +232,53 @@ impl TpuService {
         Ok(())
     }
 
+    /// new connections to the tpus use `identity` while the current ones drain
+    pub async fn rotate_identity(&self, identity: Arc<dyn IdentitySigner>) -> anyhow::Result<()> {
+        let tpu_connection_manager = match &self.connection_manager {
+            DirectTpu {
+                tpu_connection_manager,
+            } => tpu_connection_manager,
+            #[cfg(feature = "quic-proxy")]
+            QuicProxy { .. } => {
+                anyhow::bail!(
+                    "The tpu identity is the one of the quic forward proxy, rotate it on the proxy"
+                )
+            }
+        };
+
+        let pubkey = identity.pubkey();
+        let client_identity = Arc::new(
+            QuicClientIdentity::new(identity)
+                .context("Failed to initialize QUIC client certificates")?,
+        );
+        tpu_connection_manager.rotate_identity(client_identity);
+        self.data_cache.identity_stakes.set_identity(pubkey).await;
+
+        IDENTITY_ROTATIONS.inc();
+        IDENTITY_ROTATED_AT.set(chrono::Utc::now().timestamp());
+        info!("Rotated identity to {pubkey}");
+        Ok(())
+    }
+
+    /// closes the connections to the tpus or the proxy once the transactions in flight are
+    /// sent, they get at most half of the time left before `deadline`, the rest is left to
+    /// the shutdown stages after this one
+    pub async fn shutdown(&self, deadline: Option<Instant>) {
+        let deadline = deadline.map(|deadline| {
+            let now = Instant::now();
+            now + deadline.saturating_duration_since(now) / 2
+        });
+        match &self.connection_manager {
+            DirectTpu {
+                tpu_connection_manager,
+            } => tpu_connection_manager.shutdown(deadline).await,
+            #[cfg(feature = "quic-proxy")]
+            QuicProxy {
+                quic_proxy_connection_manager,
+            } => quic_proxy_connection_manager.shutdown(deadline).await,
+        }
+    }
+
     pub fn start(&self, slot_notifications: SlotStream) -> AnyhowJoinHandle {
         let this = self.clone();
         tokio::spawn(async move {
@@ -186,6 +293,10 @@ impl TpuService {
                     notification.estimated_processed_slot,
                 )
                 .await?;
+                this.heartbeat.beat();
+                if !*this.connections_ready.borrow() {
+                    this.connections_ready.send_replace(true);
+                }
             }
         })
     }
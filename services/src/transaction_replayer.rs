@@ -1,12 +1,15 @@
 use crate::tpu_utils::tpu_service::TpuService;
 use anyhow::{bail, Context};
-use log::error;
+use log::{error, info};
 use prometheus::{core::GenericGauge, opts, register_int_gauge};
 use solana_lite_rpc_core::{
-    stores::tx_store::TxStore, structures::transaction_sent_info::SentTransactionInfo,
+    stores::tx_store::TxStore,
+    structures::{
+        shutdown::{wait_for_shutdown_stage, ShutdownReceiver, ShutdownStage},
+        transaction_sent_info::SentTransactionInfo,
+    },
     AnyhowJoinHandle,
 };
-use std::time::Duration;
 use tokio::{
     sync::mpsc::{UnboundedReceiver, UnboundedSender},
     time::Instant,
@@ -30,20 +33,19 @@ pub struct TransactionReplay {
 /// They will be replayed max_replay times
 /// The replay time will be linearly increasing by after count * replay after
 /// So the transasctions will be replayed like retry_after, retry_after*2, retry_after*3 ...
+/// Pending replays are dropped on shutdown
 
 #[derive(Clone)]
 pub struct TransactionReplayer {
     pub tpu_service: TpuService,
     pub tx_store: TxStore,
-    pub retry_offset: Duration,
 }
 
 impl TransactionReplayer {
-    pub fn new(tpu_service: TpuService, tx_store: TxStore, retry_offset: Duration) -> Self {
+    pub fn new(tpu_service: TpuService, tx_store: TxStore) -> Self {
         Self {
             tpu_service,
             tx_store,
-            retry_offset,
         }
     }
 
@@ -51,14 +53,15 @@ impl TransactionReplayer {
         &self,
         sender: UnboundedSender<TransactionReplay>,
         mut reciever: UnboundedReceiver<TransactionReplay>,
+        shutdown: ShutdownReceiver,
     ) -> AnyhowJoinHandle {
         let tpu_service = self.tpu_service.clone();
         let tx_store = self.tx_store.clone();
-        let retry_offset = self.retry_offset;
 
-        tokio::spawn(async move {
+        let replay = async move {
             while let Some(mut tx_replay) = reciever.recv().await {
                 MESSAGES_IN_REPLAY_QUEUE.dec();
+                let retry_offset = tpu_service.runtime_config().retry_after;
                 let now = Instant::now();
                 if now < tx_replay.replay_at {
                     if tx_replay.replay_at > now + retry_offset {
@@ -79,7 +82,7 @@ impl TransactionReplayer {
                     continue;
                 }
                 // ignore reset error
-                let _ = tpu_service.send_transaction(&tx_replay.transaction);
+                let _ = tpu_service.send_transaction(&tx_replay.transaction).await;
 
                 if tx_replay.replay_count < tx_replay.max_replay {
                     tx_replay.replay_count += 1;
@@ -91,6 +94,16 @@ impl TransactionReplayer {
             }
             error!("transaction replay channel broken");
             bail!("transaction replay channel broken");
+        };
+
+        tokio::spawn(async move {
+            tokio::select! {
+                res = replay => res,
+                _ = wait_for_shutdown_stage(shutdown, ShutdownStage::DrainSendQueue) => {
+                    info!("Transaction replayer stopped, pending replays are dropped");
+                    Ok(())
+                }
+            }
         })
     }
 }
@@ -1,16 +1,21 @@
 // This class will manage the lifecycle for a transaction
 // It will send, replay if necessary and confirm by listening to blocks
 
-use std::time::Duration;
-
 use crate::{
     tpu_utils::tpu_service::TpuService,
     transaction_replayer::{TransactionReplay, TransactionReplayer, MESSAGES_IN_REPLAY_QUEUE},
-    tx_sender::TxSender,
+    tx_sender::{PrioritizedReceiver, TxSender},
 };
-use anyhow::bail;
+use anyhow::{bail, Context};
+use log::info;
 use solana_lite_rpc_core::{
-    solana_utils::SerializableTransaction, structures::transaction_sent_info::SentTransactionInfo,
+    solana_utils::SerializableTransaction,
+    structures::{
+        priority_class::PriorityClass,
+        runtime_config::RuntimeConfigReceiver,
+        shutdown::{shutdown_deadline, ShutdownReceiver},
+        transaction_sent_info::SentTransactionInfo,
+    },
     types::SlotStream,
 };
 use solana_lite_rpc_core::{
@@ -51,10 +56,14 @@ impl TransactionServiceBuilder {
         self,
         notifier: Option<NotificationSender>,
         block_information_store: BlockInformationStore,
-        max_retries: usize,
+        runtime_config: RuntimeConfigReceiver,
         slot_notifications: SlotStream,
+        shutdown: ShutdownReceiver,
     ) -> (TransactionService, AnyhowJoinHandle) {
         let (transaction_channel, tx_recv) = mpsc::channel(self.max_nb_txs_in_queue);
+        let (priority_transaction_channel, priority_tx_recv) =
+            mpsc::channel(self.max_nb_txs_in_queue);
+        let tx_recv = PrioritizedReceiver::new(priority_tx_recv, tx_recv);
         let (replay_channel, replay_reciever) = tokio::sync::mpsc::unbounded_channel();
 
         let jh_services: AnyhowJoinHandle = {
@@ -64,47 +73,72 @@ impl TransactionServiceBuilder {
             let replay_channel_task = replay_channel.clone();
 
             tokio::spawn(async move {
-                let tpu_service_fx = tpu_service.start(slot_notifications);
+                let mut tpu_service_fx = tpu_service.start(slot_notifications);
+
+                let tx_sender_jh =
+                    tx_sender
+                        .clone()
+                        .execute(tx_recv, notifier.clone(), shutdown.clone());
 
-                let tx_sender_jh = tx_sender.clone().execute(tx_recv, notifier.clone());
+                let replay_service = tx_replayer.start_service(
+                    replay_channel_task,
+                    replay_reciever,
+                    shutdown.clone(),
+                );
 
-                let replay_service =
-                    tx_replayer.start_service(replay_channel_task, replay_reciever);
+                // sender and replayer only return without error once the send queue is drained
+                let drained = async {
+                    tokio::try_join!(
+                        flatten_join(tx_sender_jh, "Tx Sender"),
+                        flatten_join(replay_service, "Replay Service"),
+                    )
+                };
 
                 tokio::select! {
-                    res = tpu_service_fx => {
+                    res = &mut tpu_service_fx => {
                         bail!("Tpu Service {res:?}")
                     },
-                    res = tx_sender_jh => {
-                        bail!("Tx Sender {res:?}")
-                    },
-                    res = replay_service => {
-                        bail!("Replay Service {res:?}")
+                    res = drained => {
+                        res?;
                     },
                 }
+
+                // stop opening connections to the next leaders
+                tpu_service_fx.abort();
+                info!("Send queue drained, closing tpu connections");
+                tpu_service.shutdown(shutdown_deadline(&shutdown)).await;
+                Ok(())
             })
         };
 
         (
             TransactionService {
                 transaction_channel,
+                priority_transaction_channel,
                 replay_channel,
                 block_information_store,
-                max_retries,
-                replay_offset: self.tx_replayer.retry_offset,
+                runtime_config,
             },
             jh_services,
         )
     }
 }
 
+async fn flatten_join(jh: AnyhowJoinHandle, service: &str) -> anyhow::Result<()> {
+    jh.await
+        .with_context(|| format!("{service} panicked"))?
+        .with_context(|| format!("{service} failed"))
+}
+
 #[derive(Clone)]
 pub struct TransactionService {
+    /// best effort transactions
     pub transaction_channel: Sender<SentTransactionInfo>,
+    /// forwarded before the best effort ones
+    pub priority_transaction_channel: Sender<SentTransactionInfo>,
     pub replay_channel: UnboundedSender<TransactionReplay>,
     pub block_information_store: BlockInformationStore,
-    pub max_retries: usize,
-    pub replay_offset: Duration,
+    pub runtime_config: RuntimeConfigReceiver,
 }
 
 impl TransactionService {
@@ -112,6 +146,7 @@ impl TransactionService {
         &self,
         raw_tx: Vec<u8>,
         max_retries: Option<u16>,
+        priority: PriorityClass,
     ) -> anyhow::Result<String> {
         let tx = match bincode::deserialize::<VersionedTransaction>(&raw_tx) {
             Ok(tx) => tx,
@@ -132,24 +167,25 @@ impl TransactionService {
             bail!("Blockhash not found in block store".to_string());
         };
 
-        let max_replay = max_retries.map_or(self.max_retries, |x| x as usize);
+        let runtime_config = *self.runtime_config.borrow();
+        let max_replay = max_retries.map_or(runtime_config.max_retries, |x| x as usize);
         let transaction_info = SentTransactionInfo {
             signature: signature.to_string(),
             last_valid_block_height: last_valid_blockheight,
             slot,
             transaction: raw_tx,
         };
-        if let Err(e) = self
-            .transaction_channel
-            .send(transaction_info.clone())
-            .await
-        {
+        let transaction_channel = match priority {
+            PriorityClass::High => &self.priority_transaction_channel,
+            PriorityClass::BestEffort => &self.transaction_channel,
+        };
+        if let Err(e) = transaction_channel.send(transaction_info.clone()).await {
             bail!(
                 "Internal error sending transaction on send channel error {}",
                 e
             );
         }
-        let replay_at = Instant::now() + self.replay_offset;
+        let replay_at = Instant::now() + runtime_config.retry_after;
         // ignore error for replay service
         if self
             .replay_channel
@@ -2,7 +2,7 @@ use std::time::{Duration, Instant};
 
 use anyhow::bail;
 use chrono::Utc;
-use log::{trace, warn};
+use log::{info, trace, warn};
 
 use prometheus::{
     core::GenericGauge, histogram_opts, opts, register_histogram, register_int_counter,
@@ -14,7 +14,11 @@ use crate::tpu_utils::tpu_service::TpuService;
 use solana_lite_rpc_core::{
     stores::{data_cache::DataCache, tx_store::TxProps},
     structures::{
+        heartbeat::Heartbeat,
         notifications::{NotificationMsg, NotificationSender, TransactionNotification},
+        shutdown::{
+            shutdown_stage_reached, wait_for_shutdown_stage, ShutdownReceiver, ShutdownStage,
+        },
         transaction_sent_info::SentTransactionInfo,
     },
     AnyhowJoinHandle,
@@ -41,12 +45,50 @@ lazy_static::lazy_static! {
 const INTERVAL_PER_BATCH_IN_MS: u64 = 50;
 const MAX_BATCH_SIZE_IN_PER_INTERVAL: usize = 2000;
 
+/// Receives the transactions of the high priority channel before the best effort ones
+pub struct PrioritizedReceiver {
+    high: Receiver<SentTransactionInfo>,
+    best_effort: Receiver<SentTransactionInfo>,
+}
+
+impl PrioritizedReceiver {
+    pub fn new(
+        high: Receiver<SentTransactionInfo>,
+        best_effort: Receiver<SentTransactionInfo>,
+    ) -> Self {
+        Self { high, best_effort }
+    }
+
+    /// none once both channels are closed and empty
+    async fn recv(&mut self) -> Option<SentTransactionInfo> {
+        tokio::select! {
+            biased;
+            Some(transaction_info) = self.high.recv() => Some(transaction_info),
+            Some(transaction_info) = self.best_effort.recv() => Some(transaction_info),
+            else => None,
+        }
+    }
+
+    fn try_recv(&mut self) -> Option<SentTransactionInfo> {
+        self.high
+            .try_recv()
+            .or_else(|_| self.best_effort.try_recv())
+            .ok()
+    }
+
+    fn close(&mut self) {
+        self.high.close();
+        self.best_effort.close();
+    }
+}
+
 /// Retry transactions to a maximum of `u16` times, keep a track of confirmed transactions
 #[derive(Clone)]
 pub struct TxSender {
     /// TpuClient to call the tpu port
     tpu_service: TpuService,
     data_cache: DataCache,
+    heartbeat: Heartbeat,
 }
 
 impl TxSender {
@@ -54,9 +96,15 @@ impl TxSender {
         Self {
             tpu_service,
             data_cache,
+            heartbeat: Heartbeat::new("tx sender"),
         }
     }
 
+    /// beats on every batch, at least every batch interval
+    pub fn heartbeat(&self) -> Heartbeat {
+        self.heartbeat.clone()
+    }
+
     /// retry enqued_tx(s)
     async fn forward_txs(
         &self,
@@ -93,7 +141,7 @@ impl TxSender {
                 transaction_info.signature.clone(),
                 TxProps::new(transaction_info.last_valid_block_height),
             );
-            let quic_response = match tpu_client.send_transaction(transaction_info) {
+            let quic_response = match tpu_client.send_transaction(transaction_info).await {
                 Ok(_) => {
                     TXS_SENT.inc_by(1);
                     1
@@ -133,19 +181,29 @@ impl TxSender {
     }
 
     /// retry and confirm transactions every 2ms (avg time to confirm tx)
+    /// on shutdown the transactions left in the channel are forwarded before returning
     pub fn execute(
         self,
-        mut recv: Receiver<SentTransactionInfo>,
+        mut recv: PrioritizedReceiver,
         notifier: Option<NotificationSender>,
+        shutdown: ShutdownReceiver,
     ) -> AnyhowJoinHandle {
         tokio::spawn(async move {
             loop {
-                let mut transaction_infos = Vec::with_capacity(MAX_BATCH_SIZE_IN_PER_INTERVAL);
+                self.heartbeat.beat();
+                if shutdown_stage_reached(&shutdown, ShutdownStage::DrainSendQueue) {
+                    self.drain(recv, notifier).await;
+                    return Ok(());
+                }
+
+                let max_send_tps = self.tpu_service.runtime_config().max_send_tps;
+                let max_batch_size = Self::max_batch_size(max_send_tps);
+                let mut transaction_infos = Vec::with_capacity(max_batch_size);
                 let mut timeout_interval = INTERVAL_PER_BATCH_IN_MS;
 
                 // In solana there in sig verify stage rate is limited to 2000 txs in 50ms
                 // taking this as reference
-                while transaction_infos.len() <= MAX_BATCH_SIZE_IN_PER_INTERVAL {
+                while transaction_infos.len() < max_batch_size {
                     let instance = tokio::time::Instant::now();
                     match tokio::time::timeout(Duration::from_millis(timeout_interval), recv.recv())
                         .await
@@ -169,6 +227,16 @@ impl TxSender {
                                     .max(1);
                             }
                             None => {
+                                // the rpc servers holding the senders are gone on shutdown
+                                if shutdown_stage_reached(&shutdown, ShutdownStage::StopRpc) {
+                                    self.forward_txs(transaction_infos, notifier).await;
+                                    wait_for_shutdown_stage(
+                                        shutdown,
+                                        ShutdownStage::DrainSendQueue,
+                                    )
+                                    .await;
+                                    return Ok(());
+                                }
                                 log::error!("Channel Disconnected");
                                 bail!("Channel Disconnected");
                             }
@@ -189,4 +257,37 @@ impl TxSender {
             }
         })
     }
+
+    async fn drain(&self, mut recv: PrioritizedReceiver, notifier: Option<NotificationSender>) {
+        recv.close();
+        let mut transaction_infos = vec![];
+        while let Some(transaction_info) = recv.try_recv() {
+            TXS_IN_CHANNEL.dec();
+            if !self
+                .data_cache
+                .txs
+                .contains_key(&transaction_info.signature)
+            {
+                transaction_infos.push(transaction_info);
+            }
+        }
+
+        info!(
+            "Forwarding {} queued transaction(s) before shutdown",
+            transaction_infos.len()
+        );
+        for chunk in transaction_infos.chunks(MAX_BATCH_SIZE_IN_PER_INTERVAL) {
+            self.forward_txs(chunk.to_vec(), notifier.clone()).await;
+        }
+    }
+
+    /// transactions the tpu service takes in about one batch interval under `max_send_tps`, it
+    /// paces the sends itself
+    fn max_batch_size(max_send_tps: Option<u64>) -> usize {
+        let Some(max_send_tps) = max_send_tps else {
+            return MAX_BATCH_SIZE_IN_PER_INTERVAL;
+        };
+        let max_batch_size = (max_send_tps * INTERVAL_PER_BATCH_IN_MS + 999) / 1000;
+        (max_batch_size as usize).clamp(1, MAX_BATCH_SIZE_IN_PER_INTERVAL)
+    }
 }